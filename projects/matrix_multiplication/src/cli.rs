@@ -1,4 +1,11 @@
-use clap::{Parser, Subcommand};
+use std::path::PathBuf;
+
+use clap::{Parser, Subcommand, ValueEnum};
+
+use matrix_multiplication::matrix_multiplication::{
+    distributed::DEFAULT_PANEL_WIDTH, element::ElementType, generate::Distribution,
+    partition::Partition,
+};
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
@@ -21,6 +28,48 @@ pub struct Cli {
     /// Only run parallel matrix multiplication
     pub parallel_only: bool,
 
+    #[arg(long)]
+    /// Seed of the random matrix generator, making the benchmark reproducible
+    pub seed: Option<u64>,
+
+    #[arg(short, long, default_value_t = Distribution::default())]
+    /// Distribution of the generated matrices: uniform[:LOW:HIGH], normal[:MEAN:STD_DEV],
+    /// identity, sparse[:DENSITY], banded[:BANDWIDTH], symmetric or diagonally-dominant
+    pub distribution: Distribution,
+
+    #[arg(short, long, value_enum, default_value_t = ElementType::I32)]
+    /// Type of the matrix elements
+    pub element: ElementType,
+
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    /// Also run the sparse kernels, with the matrices converted to CSR format
+    pub sparse: bool,
+
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    /// Also run the register-blocked micro-kernel, scalar and with the best SIMD instructions
+    pub simd: bool,
+
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    /// Also run the packed GEMM, with cache blocking and the best SIMD micro-kernel
+    pub packed: bool,
+
+    #[arg(long, value_enum, value_delimiter = ',')]
+    /// Also run the given partitions of the product into parallel jobs, each on 1, 2, 4...
+    /// threads up to `--threads`, and report how they scale
+    pub partition: Vec<Partition>,
+
+    #[arg(long, value_enum, num_args = 0..=1, require_equals = true, default_missing_value = "full")]
+    /// Check every method's result, failing the run on the first mismatching cell
+    pub verify: Option<VerifyMode>,
+
+    #[arg(long, default_value_t = 1e-9)]
+    /// Relative tolerance used when verifying floating point results
+    pub tolerance: f64,
+
+    #[arg(long, default_value_t = 10)]
+    /// Number of random vectors tried by `--verify=freivalds`
+    pub freivalds_rounds: usize,
+
     #[command(subcommand)]
     pub subcommands: Option<Commands>,
 }
@@ -30,4 +79,121 @@ pub enum Commands {
     #[command(name = "os_threads")]
     /// Print the number of available OS threads
     OsThreads,
+
+    /// Multiply two matrices read from files
+    ///
+    /// The format of each file is chosen from its extension: `.csv`, `.tsv` and `.txt`
+    /// for text, `.mtx` for Matrix Market and `.bin` for the binary format.
+    Multiply {
+        /// File holding the left operand
+        a: PathBuf,
+
+        /// File holding the right operand
+        b: PathBuf,
+
+        #[arg(short, long)]
+        /// File to write the product to, printed as text to stdout if omitted
+        output: Option<PathBuf>,
+
+        #[arg(short, long, value_enum, default_value_t = ElementType::F64)]
+        /// Type of the matrix elements
+        element: ElementType,
+
+        #[arg(short, long, default_value_t = 4)]
+        /// Number of threads to use for the multiplication
+        threads: usize,
+
+        #[arg(long, value_enum, default_value_t = Partition::Rows)]
+        /// How to split a dense product into parallel jobs
+        partition: Partition,
+
+        #[arg(long, value_enum)]
+        /// Read the given operands as sparse matrices and use the sparse kernels
+        sparse: Option<SparseOperands>,
+
+        #[arg(long, value_parser = parse_memory_size, conflicts_with_all = ["sparse", "partition"])]
+        /// Multiply out of core, mapping the `.bin` operands and output instead of reading
+        /// them, with tiles fitting in this many bytes; accepts K, M and G suffixes
+        memory_limit: Option<usize>,
+    },
+
+    /// Serve distributed multiplications over TCP
+    Worker {
+        #[arg(short, long, default_value = "127.0.0.1:0")]
+        /// Address to listen on, with port 0 for any free port
+        listen: String,
+
+        #[arg(long, action = clap::ArgAction::SetTrue)]
+        /// Exit after serving one multiplication
+        once: bool,
+    },
+
+    /// Multiply random matrices with SUMMA on worker processes over TCP
+    ///
+    /// Without `--connect`, the workers are spawned as local processes.
+    Distributed {
+        #[arg(default_value_t = 512)]
+        /// Size of the matrix
+        size: usize,
+
+        #[arg(short, long, default_value_t = 4)]
+        /// Number of local worker processes to spawn
+        workers: usize,
+
+        #[arg(short, long, value_delimiter = ',')]
+        /// Addresses of running workers to use instead of spawning local ones
+        connect: Vec<String>,
+
+        #[arg(short, long, default_value_t = DEFAULT_PANEL_WIDTH)]
+        /// Number of columns of A, and rows of B, broadcast at every step
+        panel: usize,
+
+        #[arg(short, long, value_enum, default_value_t = ElementType::F64)]
+        /// Type of the matrix elements
+        element: ElementType,
+
+        #[arg(long)]
+        /// Seed of the random matrix generator
+        seed: Option<u64>,
+
+        #[arg(long, action = clap::ArgAction::SetTrue)]
+        /// Check the product against the packed GEMM of the coordinator
+        verify: bool,
+
+        #[arg(long, default_value_t = 1e-9)]
+        /// Relative tolerance used when verifying floating point results
+        tolerance: f64,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum SparseOperands {
+    /// Sparse A times dense B, producing a dense matrix
+    A,
+    /// Sparse A times sparse B, producing a sparse matrix
+    Both,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum VerifyMode {
+    /// Compare every cell with the result of sequential ijk
+    Full,
+    /// Run Freivalds' randomized check, which costs O(n^2) per round
+    Freivalds,
+}
+
+/// Parses a size in bytes, optionally followed by a binary `K`, `M` or `G` suffix
+fn parse_memory_size(value: &str) -> Result<usize, String> {
+    let (digits, multiplier) = match value.chars().last().map(|c| c.to_ascii_uppercase()) {
+        Some('K') => (&value[..value.len() - 1], 1 << 10),
+        Some('M') => (&value[..value.len() - 1], 1 << 20),
+        Some('G') => (&value[..value.len() - 1], 1 << 30),
+        _ => (value, 1),
+    };
+
+    digits
+        .parse::<usize>()
+        .ok()
+        .and_then(|size| size.checked_mul(multiplier))
+        .ok_or_else(|| format!("invalid memory size `{}`", value))
 }
@@ -0,0 +1,2 @@
+pub mod matrix_multiplication;
+pub mod thread_pool;
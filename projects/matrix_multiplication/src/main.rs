@@ -1,87 +1,578 @@
-use std::{thread, time::Instant};
+use std::{
+    env,
+    error::Error,
+    fmt::Display,
+    io::{self, BufRead, BufReader, Write},
+    net::{SocketAddr, TcpListener, ToSocketAddrs},
+    path::Path,
+    process::{self, Child, Command, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
 
 use clap::Parser;
 use log::debug;
 
-use crate::{
-    cli::Cli,
-    matrix_multiplication::{
-        generate::generate_square_matrix_of_size, matrix_multiplication_parallel_i_loop,
-        matrix_multiplication_sequential_ijk, matrix_multiplication_sequential_ikj,
+use matrix_multiplication::matrix_multiplication::{
+    disk::{multiply_out_of_core, DiskMatrix},
+    distributed::{serve_worker, summa},
+    element::{Element, ElementType},
+    gemm::{gemm_parallel, Transpose},
+    generate::{generate_zero_matrix, Distribution, MatrixGenerator},
+    io::{
+        read_matrix, read_sparse_matrix, text::write_text, write_matrix, write_sparse_matrix,
+        MatrixFormat,
     },
+    matrix_multiplication_parallel_i_loop, matrix_multiplication_sequential_ijk,
+    matrix_multiplication_sequential_ikj,
+    packed::matrix_multiplication_packed,
+    partition::{matrix_multiplication_partitioned, Partition},
+    sanitize::{sanitize_product, SanitizeResult},
+    simd::{matrix_multiplication_simd, SimdLevel},
+    sparse::{spgemm_parallel, spmm_parallel, CsrMatrix},
+    verify::{verify_against_reference, verify_freivalds},
 };
 
+use crate::cli::{Cli, SparseOperands, VerifyMode};
+
 mod cli;
-mod matrix_multiplication;
-mod thread_pool;
 
-fn matrix_multiplication_benchmark(cli: &Cli) {
+/// Checks the result `c` of `method` according to the `--verify` mode
+///
+/// In full mode `reference` caches the sequential ijk result of the current iteration,
+/// computing it on first use if the benchmark did not run sequential ijk itself.
+fn verify_result<T: Element>(
+    cli: &Cli,
+    method: &str,
+    a: &[Vec<T>],
+    b: &[Vec<T>],
+    reference: &mut Option<Vec<Vec<T>>>,
+    c: &Option<Vec<Vec<T>>>,
+) -> Result<(), Box<dyn Error>> {
+    let mode = match cli.verify {
+        Some(mode) => mode,
+        None => return Ok(()),
+    };
+
+    let c = c
+        .as_ref()
+        .ok_or_else(|| format!("{} produced no result", method))?;
+
+    let result = match mode {
+        VerifyMode::Full => {
+            let reference = match reference {
+                Some(reference) => reference,
+                None => reference.insert(
+                    matrix_multiplication_sequential_ijk(a, b)
+                        .ok_or("sequential ijk produced no reference result")?,
+                ),
+            };
+            verify_against_reference(reference, c, cli.tolerance)
+        }
+        VerifyMode::Freivalds => verify_freivalds(
+            a,
+            b,
+            c,
+            cli.freivalds_rounds,
+            cli.tolerance,
+            &mut rand::thread_rng(),
+        ),
+    };
+
+    result.map_err(|e| format!("{} failed verification: {}", method, e))?;
+
+    println!("verified {}", method);
+    Ok(())
+}
+
+/// Execution times of each benchmarked method, in the order they first ran
+#[derive(Default)]
+struct Timings(Vec<(String, Vec<Duration>)>);
+
+impl Timings {
+    /// Runs `kernel`, recording its execution time under `method`
+    fn time<R>(&mut self, method: &str, kernel: impl FnOnce() -> R) -> R {
+        self.time_as(method, method, kernel)
+    }
+
+    /// Runs `kernel` like `time`, but records its execution time under `average_name`, the
+    /// name its average is printed with
+    fn time_as<R>(&mut self, method: &str, average_name: &str, kernel: impl FnOnce() -> R) -> R {
+        let start = Instant::now();
+        let result = kernel();
+        let end = Instant::now();
+        let elapsed = end.duration_since(start);
+
+        match self.0.iter_mut().find(|(name, _)| name == average_name) {
+            Some((_, times)) => times.push(elapsed),
+            None => self.0.push((average_name.to_string(), vec![elapsed])),
+        }
+
+        println!("finished {}", method);
+        debug!("finished {}", method);
+
+        result
+    }
+
+    /// Returns the average execution time of `method`, if it ran
+    fn average(&self, method: &str) -> Option<Duration> {
+        self.0
+            .iter()
+            .find(|(name, _)| name == method)
+            .map(|(_, times)| times.iter().sum::<Duration>() / times.len() as u32)
+    }
+
+    /// Prints the average execution time of each method
+    fn print_averages(&self) {
+        for (method, _) in &self.0 {
+            println!(
+                "{} average: {:.3} ms",
+                method,
+                milliseconds(self.average(method).unwrap())
+            );
+        }
+    }
+}
+
+/// Returns the thread counts a partition is benchmarked with: the powers of two below
+/// `threads`, then `threads` itself
+fn scaling_thread_counts(threads: usize) -> Vec<usize> {
+    let mut counts: Vec<usize> = (0..)
+        .map(|power| 1 << power)
+        .take_while(|&count| count < threads)
+        .collect();
+    counts.push(threads.max(1));
+    counts
+}
+
+/// Returns the name under which the benchmark times `partition` on `threads` threads
+fn partition_method(partition: Partition, threads: usize) -> String {
+    format!("{} partition on {} threads", partition, threads)
+}
+
+fn matrix_multiplication_benchmark<T: Element>(cli: &Cli) -> Result<(), Box<dyn Error>> {
     let n = cli.size;
     let iterations = cli.iterations;
     let threads = cli.threads;
     let parallel_only: bool = cli.parallel_only;
+    cli.distribution.check_element(T::TYPE)?;
 
     // collect execution times for different matrix multiplication methods
 
-    let mut sequential_ijk_times = Vec::new();
-    let mut sequential_ikj_times = Vec::new();
-    let mut parallel_ijk_times = Vec::new();
+    let mut timings = Timings::default();
 
     println!("Welcome to Matrix Multiplication Benchmark!");
     println!("Matrix size: {}", n);
     println!("Number of threads: {}", threads);
     println!("Number of iterations: {}", iterations);
     println!("Parallel only: {}", parallel_only);
+    println!("Distribution: {}", cli.distribution);
+    match cli.seed {
+        Some(seed) => println!("Seed: {}", seed),
+        None => println!("Seed: random"),
+    }
+    println!("Sparse kernels: {}", cli.sparse);
+    let simd_level = SimdLevel::detect();
+    if cli.simd {
+        println!("SIMD level: {}", simd_level);
+    }
+
+    let mut generator = MatrixGenerator::new(cli.seed, cli.distribution.clone());
+
+    // output of `gemm`, allocated once and reused by every iteration
+    let mut c_buffer: Vec<Vec<T>> = generate_zero_matrix(n, n);
 
     for i in 0..iterations {
         println!("starting iteration {} of {}", i + 1, iterations);
 
-        let a = generate_square_matrix_of_size(n, true);
-        let b = generate_square_matrix_of_size(n, true);
+        let a: Vec<Vec<T>> = generator.generate(n, n);
+        let b: Vec<Vec<T>> = generator.generate(n, n);
+
+        let mut reference = None;
 
-        if parallel_only == false {
-            let start = Instant::now();
-            let _c = matrix_multiplication_sequential_ijk(&a, &b);
-            let end = Instant::now();
-            sequential_ijk_times.push(end.duration_since(start).as_millis());
+        if !parallel_only {
+            let c = timings.time("sequential ijk", || {
+                matrix_multiplication_sequential_ijk(&a, &b)
+            });
+            if let Some(VerifyMode::Freivalds) = cli.verify {
+                verify_result(cli, "sequential ijk", &a, &b, &mut reference, &c)?;
+            }
+            reference = c;
 
-            println!("finished sequential ijk");
-            debug!("finished sequential ijk");
+            let c = timings.time("sequential ikj", || {
+                matrix_multiplication_sequential_ikj(&a, &b)
+            });
+            verify_result(cli, "sequential ikj", &a, &b, &mut reference, &c)?;
+        }
+
+        let c = timings.time_as("parallel i-loop", "parallel ijk", || {
+            matrix_multiplication_parallel_i_loop(&a, &b, threads)
+        });
+        verify_result(cli, "parallel i-loop", &a, &b, &mut reference, &c)?;
 
-            let start = Instant::now();
-            let _c = matrix_multiplication_sequential_ikj(&a, &b);
-            let end = Instant::now();
-            sequential_ikj_times.push(end.duration_since(start).as_millis());
+        if cli.simd {
+            let mut levels = vec![SimdLevel::Scalar];
+            if simd_level != SimdLevel::Scalar {
+                levels.push(simd_level);
+            }
 
-            println!("finished sequential ikj");
-            debug!("finished sequential ikj");
+            for level in levels {
+                let method = format!("{} micro-kernel", level);
+                let c = timings.time(&method, || matrix_multiplication_simd(&a, &b, level));
+                verify_result(cli, &method, &a, &b, &mut reference, &c)?;
+            }
         }
 
-        let start = Instant::now();
-        let _c = matrix_multiplication_parallel_i_loop(&a, &b, threads);
-        let end = Instant::now();
-        parallel_ijk_times.push(end.duration_since(start).as_millis());
+        if cli.packed {
+            let c = timings.time("packed GEMM", || {
+                matrix_multiplication_packed(&a, &b, threads)
+            });
+            verify_result(cli, "packed GEMM", &a, &b, &mut reference, &c)?;
 
-        println!("finished parallel i-loop");
-        debug!("finished parallel i-loop");
+            timings.time("gemm into reused buffer", || {
+                gemm_parallel(
+                    Transpose::No,
+                    Transpose::No,
+                    T::one(),
+                    &a,
+                    &b,
+                    T::zero(),
+                    &mut c_buffer,
+                    threads,
+                )
+            })?;
+            let c = Some(c_buffer.clone());
+            verify_result(cli, "gemm into reused buffer", &a, &b, &mut reference, &c)?;
+        }
 
-        println!();
-    }
+        for &partition in &cli.partition {
+            for partition_threads in scaling_thread_counts(threads) {
+                let method = partition_method(partition, partition_threads);
+                let c = timings.time(&method, || {
+                    matrix_multiplication_partitioned(&a, &b, partition_threads, partition)
+                });
+                verify_result(cli, &method, &a, &b, &mut reference, &c)?;
+            }
+        }
 
-    // calculate average execution times
+        if cli.sparse {
+            let sparse_a = CsrMatrix::from_dense(&a);
+            let sparse_b = CsrMatrix::from_dense(&b);
+            println!(
+                "density of A: {:.4}",
+                sparse_a.nnz() as f64 / (n * n).max(1) as f64
+            );
+
+            let c = timings.time("parallel sparse x dense", || {
+                spmm_parallel(&sparse_a, &b, threads)
+            });
+            verify_result(cli, "parallel sparse x dense", &a, &b, &mut reference, &c)?;
 
-    let sequential_ijk_average = sequential_ijk_times.iter().sum::<u128>() / iterations as u128;
-    let sequential_ikj_average = sequential_ikj_times.iter().sum::<u128>() / iterations as u128;
-    let parallel_ijk_average = parallel_ijk_times.iter().sum::<u128>() / iterations as u128;
+            let c = timings.time("parallel sparse x sparse", || {
+                spgemm_parallel(&sparse_a, &sparse_b, threads)
+            });
+            let c = c.map(|c| c.to_dense());
+            verify_result(cli, "parallel sparse x sparse", &a, &b, &mut reference, &c)?;
+        }
+
+        println!();
+    }
 
     // print results
 
     println!("Benchmark Results");
-    if parallel_only == false {
-        println!("sequential ijk average: {} ms", sequential_ijk_average);
-        println!("sequential ikj average: {} ms", sequential_ikj_average);
+    timings.print_averages();
+
+    if !cli.partition.is_empty() {
+        println!();
+        println!("Partition Scaling");
+        for &partition in &cli.partition {
+            let base = timings.average(&partition_method(partition, 1)).unwrap();
+            for partition_threads in scaling_thread_counts(threads) {
+                let average = timings
+                    .average(&partition_method(partition, partition_threads))
+                    .unwrap();
+                println!(
+                    "{} on {} threads: {:.3} ms, speedup {:.2}x",
+                    partition,
+                    partition_threads,
+                    milliseconds(average),
+                    base.as_secs_f64() / average.as_secs_f64()
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Prefixes `error` with the path of the file it concerns
+fn with_path(path: &Path, error: impl Display) -> String {
+    format!("{}: {}", path.display(), error)
+}
+
+/// Writes `c` to `output`, or prints it as text to stdout
+fn write_product<T: Element>(c: &[Vec<T>], output: Option<&Path>) -> Result<(), Box<dyn Error>> {
+    match output {
+        Some(path) => write_matrix(path, c).map_err(|e| with_path(path, e))?,
+        None => {
+            let mut stdout = io::stdout().lock();
+            write_text(&mut stdout, c, ' ')?;
+            stdout.flush()?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns whether `a` and `b` are paths of the same existing file
+fn same_file(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+/// Multiplies the binary matrix files `a` and `b` out of core, with the buffers fitting
+/// in `memory_limit` bytes, into the binary matrix file `output`
+fn multiply_files_out_of_core<T: Element>(
+    a: &Path,
+    b: &Path,
+    output: Option<&Path>,
+    threads: usize,
+    memory_limit: usize,
+) -> Result<(), Box<dyn Error>> {
+    let output = output.ok_or("an out-of-core product needs an --output file")?;
+    for path in [a, b, output] {
+        if MatrixFormat::from_path(path).map_err(|e| with_path(path, e))? != MatrixFormat::Binary {
+            return Err(with_path(path, "out-of-core multiplication needs .bin files").into());
+        }
+    }
+    if [a, b].into_iter().any(|operand| same_file(operand, output)) {
+        return Err(with_path(output, "the output would overwrite an operand").into());
+    }
+
+    let a: DiskMatrix<T> = DiskMatrix::open(a).map_err(|e| with_path(a, e))?;
+    let b: DiskMatrix<T> = DiskMatrix::open(b).map_err(|e| with_path(b, e))?;
+    multiply_out_of_core(&a, &b, output, memory_limit, threads)?;
+
+    Ok(())
+}
+
+/// Multiplies the matrices stored in `a` and `b`, writing the product to `output`
+///
+/// With `sparse`, the operands it names are read as CSR matrices and multiplied with
+/// the sparse kernels; a sparse product is written in coordinate form to `.mtx` files.
+fn multiply_files<T: Element>(
+    a: &Path,
+    b: &Path,
+    output: Option<&Path>,
+    threads: usize,
+    partition: Partition,
+    sparse: Option<SparseOperands>,
+) -> Result<(), Box<dyn Error>> {
+    match sparse {
+        None => {
+            let a: Vec<Vec<T>> = read_matrix(a).map_err(|e| with_path(a, e))?;
+            let b: Vec<Vec<T>> = read_matrix(b).map_err(|e| with_path(b, e))?;
+
+            if let SanitizeResult::NotOk(e) = sanitize_product(&a, &b) {
+                return Err(format!("cannot multiply: {}", e).into());
+            }
+
+            // the operands were sanitized above, so the kernel always returns a result
+            let c = matrix_multiplication_partitioned(&a, &b, threads, partition).unwrap();
+            write_product(&c, output)
+        }
+        Some(SparseOperands::A) => {
+            let a: CsrMatrix<T> = read_sparse_matrix(a).map_err(|e| with_path(a, e))?;
+            let b: Vec<Vec<T>> = read_matrix(b).map_err(|e| with_path(b, e))?;
+
+            let c = spmm_parallel(&a, &b, threads).ok_or_else(|| {
+                format!(
+                    "cannot multiply: A is {}x{} but B is {}x{}",
+                    a.rows(),
+                    a.cols(),
+                    b.len(),
+                    b.first().map_or(0, |row| row.len())
+                )
+            })?;
+            write_product(&c, output)
+        }
+        Some(SparseOperands::Both) => {
+            let a: CsrMatrix<T> = read_sparse_matrix(a).map_err(|e| with_path(a, e))?;
+            let b: CsrMatrix<T> = read_sparse_matrix(b).map_err(|e| with_path(b, e))?;
+
+            let c = spgemm_parallel(&a, &b, threads).ok_or_else(|| {
+                format!(
+                    "cannot multiply: A has {} columns but B has {} rows",
+                    a.cols(),
+                    b.rows()
+                )
+            })?;
+            match output {
+                Some(path) => write_sparse_matrix(path, &c).map_err(|e| with_path(path, e))?,
+                None => write_product(&c.to_dense(), None)?,
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Worker processes spawned on this machine, killed when dropped
+struct LocalWorkers(Vec<Child>);
+
+impl Drop for LocalWorkers {
+    fn drop(&mut self) {
+        for child in &mut self.0 {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+/// Spawns `count` processes of this executable serving one multiplication each
+///
+/// # Returns
+///
+/// The processes and the addresses they listen on
+fn spawn_local_workers(count: usize) -> Result<(LocalWorkers, Vec<SocketAddr>), Box<dyn Error>> {
+    let executable = env::current_exe()?;
+    let mut workers = LocalWorkers(Vec::with_capacity(count));
+    let mut addresses = Vec::with_capacity(count);
+
+    for _ in 0..count {
+        let mut child = Command::new(&executable)
+            .args(["worker", "--once"])
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let stdout = child.stdout.take().unwrap();
+        workers.0.push(child);
+
+        let mut line = String::new();
+        BufReader::new(stdout).read_line(&mut line)?;
+        let address = line
+            .trim()
+            .strip_prefix("listening on ")
+            .ok_or_else(|| format!("unexpected output from a worker: {:?}", line))?;
+        addresses.push(address.parse()?);
     }
-    println!("parallel ijk average: {} ms", parallel_ijk_average);
+
+    Ok((workers, addresses))
+}
+
+/// Resolves the address of a running worker
+fn resolve_worker(address: &str) -> Result<SocketAddr, Box<dyn Error>> {
+    address
+        .to_socket_addrs()
+        .map_err(|e| format!("{}: {}", address, e))?
+        .next()
+        .ok_or_else(|| format!("{}: no address found", address).into())
+}
+
+/// Returns the addresses of the running workers in `connect`, or spawns `count` local
+/// workers if it is empty
+///
+/// The local workers, if any, are killed when the returned `LocalWorkers` is dropped.
+fn distributed_workers(
+    count: usize,
+    connect: &[String],
+) -> Result<(Option<LocalWorkers>, Vec<SocketAddr>), Box<dyn Error>> {
+    if connect.is_empty() {
+        let (local_workers, addresses) = spawn_local_workers(count)?;
+        return Ok((Some(local_workers), addresses));
+    }
+
+    let addresses = connect
+        .iter()
+        .map(|address| resolve_worker(address))
+        .collect::<Result<_, _>>()?;
+    Ok((None, addresses))
+}
+
+/// Listens on `address` and serves distributed multiplications until killed, or only
+/// one with `once`
+fn run_worker(address: &str, once: bool) -> Result<(), Box<dyn Error>> {
+    let listener = TcpListener::bind(address)?;
+
+    // the coordinator spawning local workers reads the address from this line
+    println!("listening on {}", listener.local_addr()?);
+    io::stdout().flush()?;
+
+    loop {
+        match serve_worker(&listener) {
+            Ok(()) => debug!("served a multiplication"),
+            Err(e) if !once => eprintln!("Worker error: {}", e),
+            Err(e) => return Err(e.into()),
+        }
+
+        if once {
+            return Ok(());
+        }
+    }
+}
+
+fn milliseconds(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1e3
+}
+
+/// Multiplies random `size` x `size` matrices with SUMMA on `workers`, reporting where
+/// the time went
+fn distributed_benchmark<T: Element>(
+    size: usize,
+    workers: &[SocketAddr],
+    panel: usize,
+    seed: Option<u64>,
+    verify: Option<f64>,
+) -> Result<(), Box<dyn Error>> {
+    let mut generator = MatrixGenerator::new(seed, Distribution::default());
+    let a: Vec<Vec<T>> = generator.generate(size, size);
+    let b: Vec<Vec<T>> = generator.generate(size, size);
+
+    let (c, report) = summa(&a, &b, workers, panel)?;
+
+    println!("Matrix size: {}", size);
+    println!("Grid: {}x{} workers", report.grid.0, report.grid.1);
+    println!("Panel width: {}", panel);
+    println!("distribution: {:.3} ms", milliseconds(report.distribution));
+    println!(
+        "multiplication: {:.3} ms",
+        milliseconds(report.multiplication)
+    );
+    for (rank, worker) in report.workers.iter().enumerate() {
+        println!(
+            "worker {} at ({}, {}): compute {:.3} ms, communication {:.3} ms",
+            rank,
+            worker.position.0,
+            worker.position.1,
+            milliseconds(worker.compute),
+            milliseconds(worker.communication)
+        );
+    }
+    let compute: Duration = report.workers.iter().map(|worker| worker.compute).sum();
+    let communication: Duration = report
+        .workers
+        .iter()
+        .map(|worker| worker.communication)
+        .sum();
+    println!(
+        "total: compute {:.3} ms, communication {:.3} ms ({:.1}% communication)",
+        milliseconds(compute),
+        milliseconds(communication),
+        100.0 * communication.as_secs_f64()
+            / (compute + communication)
+                .as_secs_f64()
+                .max(f64::MIN_POSITIVE)
+    );
+
+    if let Some(tolerance) = verify {
+        let reference = matrix_multiplication_packed(&a, &b, 1).unwrap();
+        verify_against_reference(&reference, &c, tolerance)
+            .map_err(|e| format!("distributed SUMMA failed verification: {}", e))?;
+        println!("verified distributed SUMMA");
+    }
+
+    Ok(())
 }
 
 fn main() {
@@ -94,8 +585,119 @@ fn main() {
                 thread::available_parallelism().unwrap()
             );
         }
+        Some(cli::Commands::Multiply {
+            a,
+            b,
+            output,
+            element,
+            threads,
+            memory_limit: Some(memory_limit),
+            ..
+        }) => {
+            let output = output.as_deref();
+            let result = match element {
+                ElementType::I32 => {
+                    multiply_files_out_of_core::<i32>(a, b, output, *threads, *memory_limit)
+                }
+                ElementType::I64 => {
+                    multiply_files_out_of_core::<i64>(a, b, output, *threads, *memory_limit)
+                }
+                ElementType::F32 => {
+                    multiply_files_out_of_core::<f32>(a, b, output, *threads, *memory_limit)
+                }
+                ElementType::F64 => {
+                    multiply_files_out_of_core::<f64>(a, b, output, *threads, *memory_limit)
+                }
+            };
+
+            if let Err(e) = result {
+                eprintln!("Multiply error: {}", e);
+                process::exit(1);
+            }
+        }
+        Some(cli::Commands::Multiply {
+            a,
+            b,
+            output,
+            element,
+            threads,
+            partition,
+            sparse,
+            memory_limit: None,
+        }) => {
+            let output = output.as_deref();
+            let result = match element {
+                ElementType::I32 => {
+                    multiply_files::<i32>(a, b, output, *threads, *partition, *sparse)
+                }
+                ElementType::I64 => {
+                    multiply_files::<i64>(a, b, output, *threads, *partition, *sparse)
+                }
+                ElementType::F32 => {
+                    multiply_files::<f32>(a, b, output, *threads, *partition, *sparse)
+                }
+                ElementType::F64 => {
+                    multiply_files::<f64>(a, b, output, *threads, *partition, *sparse)
+                }
+            };
+
+            if let Err(e) = result {
+                eprintln!("Multiply error: {}", e);
+                process::exit(1);
+            }
+        }
+        Some(cli::Commands::Worker { listen, once }) => {
+            if let Err(e) = run_worker(listen, *once) {
+                eprintln!("Worker error: {}", e);
+                process::exit(1);
+            }
+        }
+        Some(cli::Commands::Distributed {
+            size,
+            workers,
+            connect,
+            panel,
+            element,
+            seed,
+            verify,
+            tolerance,
+        }) => {
+            let verify = verify.then_some(*tolerance);
+            let result =
+                distributed_workers(*workers, connect).and_then(|(_local_workers, addresses)| {
+                    match element {
+                        ElementType::I32 => {
+                            distributed_benchmark::<i32>(*size, &addresses, *panel, *seed, verify)
+                        }
+                        ElementType::I64 => {
+                            distributed_benchmark::<i64>(*size, &addresses, *panel, *seed, verify)
+                        }
+                        ElementType::F32 => {
+                            distributed_benchmark::<f32>(*size, &addresses, *panel, *seed, verify)
+                        }
+                        ElementType::F64 => {
+                            distributed_benchmark::<f64>(*size, &addresses, *panel, *seed, verify)
+                        }
+                    }
+                });
+
+            if let Err(e) = result {
+                eprintln!("Distributed error: {}", e);
+                process::exit(1);
+            }
+        }
         None => {
-            matrix_multiplication_benchmark(&cli);
+            let result = match cli.element {
+                ElementType::I32 => matrix_multiplication_benchmark::<i32>(&cli),
+                ElementType::I64 => matrix_multiplication_benchmark::<i64>(&cli),
+                ElementType::F32 => matrix_multiplication_benchmark::<f32>(&cli),
+                ElementType::F64 => matrix_multiplication_benchmark::<f64>(&cli),
+            };
+
+            if let Err(e) = result {
+                eprintln!("Benchmark error: {}", e);
+                process::exit(1);
+            }
         }
     }
 }
@@ -6,20 +6,36 @@ use types::{MatrixRowMutPtr, MatrixRowPtr};
 use crate::thread_pool;
 
 use self::{
-    generate::generate_square_matrix_of_size,
-    sanitize::{sanitize_matrices, SanitizeResult},
+    element::Element,
+    generate::generate_zero_matrix,
+    sanitize::{sanitize_product, SanitizeResult},
     types::SquareMatrixPtr,
 };
 
+pub mod chain;
+pub mod disk;
+pub mod distributed;
+pub mod element;
+pub mod expr;
+pub mod gemm;
 pub mod generate;
-mod sanitize;
+pub mod io;
+pub mod lu;
+pub mod matrix;
+pub mod packed;
+pub mod partition;
+pub mod sanitize;
+pub mod simd;
+pub mod smatrix;
+pub mod sparse;
 mod types;
+pub mod verify;
 
-pub fn matrix_multiplication_sequential_ijk(
-    a: &Vec<Vec<i32>>,
-    b: &Vec<Vec<i32>>,
-) -> Option<Vec<Vec<i32>>> {
-    match sanitize_matrices(a, b) {
+pub fn matrix_multiplication_sequential_ijk<T: Element>(
+    a: &[Vec<T>],
+    b: &[Vec<T>],
+) -> Option<Vec<Vec<T>>> {
+    match sanitize_product(a, b) {
         SanitizeResult::Ok => (),
         SanitizeResult::NotOk(error) => {
             error!("Error: {:?}", error);
@@ -27,13 +43,13 @@ pub fn matrix_multiplication_sequential_ijk(
         }
     };
 
-    let size = a.len();
+    let (rows, inner, cols) = (a.len(), b.len(), b[0].len());
 
-    let mut c = generate_square_matrix_of_size(size, false);
+    let mut c = generate_zero_matrix(rows, cols);
 
-    for i in 0..size {
-        for j in 0..size {
-            for k in 0..size {
+    for i in 0..rows {
+        for j in 0..cols {
+            for k in 0..inner {
                 c[i][j] += a[i][k] * b[k][j];
             }
         }
@@ -42,11 +58,11 @@ pub fn matrix_multiplication_sequential_ijk(
     Some(c)
 }
 
-pub fn matrix_multiplication_sequential_ikj(
-    a: &Vec<Vec<i32>>,
-    b: &Vec<Vec<i32>>,
-) -> Option<Vec<Vec<i32>>> {
-    match sanitize_matrices(a, b) {
+pub fn matrix_multiplication_sequential_ikj<T: Element>(
+    a: &[Vec<T>],
+    b: &[Vec<T>],
+) -> Option<Vec<Vec<T>>> {
+    match sanitize_product(a, b) {
         SanitizeResult::Ok => (),
         SanitizeResult::NotOk(error) => {
             error!("Error: {:?}", error);
@@ -54,13 +70,13 @@ pub fn matrix_multiplication_sequential_ikj(
         }
     };
 
-    let size = a.len();
+    let (rows, inner, cols) = (a.len(), b.len(), b[0].len());
 
-    let mut c = generate_square_matrix_of_size(size, false);
+    let mut c = generate_zero_matrix(rows, cols);
 
-    for i in 0..size {
-        for k in 0..size {
-            for j in 0..size {
+    for i in 0..rows {
+        for k in 0..inner {
+            for j in 0..cols {
                 c[i][j] += a[i][k] * b[k][j];
             }
         }
@@ -69,12 +85,12 @@ pub fn matrix_multiplication_sequential_ikj(
     Some(c)
 }
 
-pub fn matrix_multiplication_parallel_i_loop(
-    a: &Vec<Vec<i32>>,
-    b: &Vec<Vec<i32>>,
+pub fn matrix_multiplication_parallel_i_loop<T: Element>(
+    a: &[Vec<T>],
+    b: &[Vec<T>],
     preferred_number_of_threads: usize,
-) -> Option<Vec<Vec<i32>>> {
-    match sanitize_matrices(a, b) {
+) -> Option<Vec<Vec<T>>> {
+    match sanitize_product(a, b) {
         SanitizeResult::Ok => (),
         SanitizeResult::NotOk(error) => {
             error!("Error: {:?}", error);
@@ -82,22 +98,22 @@ pub fn matrix_multiplication_parallel_i_loop(
         }
     };
 
-    let size = a.len();
+    let (rows, inner, cols) = (a.len(), b.len(), b[0].len());
 
-    let mut c = generate_square_matrix_of_size(size, false);
+    let mut c = generate_zero_matrix(rows, cols);
 
     let pool = ThreadPool::new(preferred_number_of_threads);
 
-    for i in 0..size {
+    for i in 0..rows {
         let a_i = MatrixRowPtr(a[i].as_ptr());
         let mut c_i = MatrixRowMutPtr(c[i].as_mut_ptr());
         let b = SquareMatrixPtr::new(b);
 
         unsafe {
             pool.execute(move || {
-                for k in 0..size {
+                for k in 0..inner {
                     let b_k = b.get_row(k);
-                    for j in 0..size {
+                    for j in 0..cols {
                         *c_i.add(j) += *a_i.add(k) * *b_k.add(j);
                     }
                 }
@@ -149,4 +165,25 @@ mod tests {
 
         assert_eq!(c, vec![vec![19, 22], vec![43, 50]]);
     }
+
+    #[test]
+    fn test_rectangular_product() {
+        let a = vec![vec![1, 2, 3], vec![4, 5, 6]];
+        let b = vec![vec![1], vec![0], vec![-1]];
+        let expected = vec![vec![-2], vec![-2]];
+
+        assert_eq!(
+            super::matrix_multiplication_sequential_ijk(&a, &b),
+            Some(expected.clone())
+        );
+        assert_eq!(
+            super::matrix_multiplication_sequential_ikj(&a, &b),
+            Some(expected.clone())
+        );
+        assert_eq!(
+            super::matrix_multiplication_parallel_i_loop(&a, &b, 2),
+            Some(expected)
+        );
+        assert_eq!(super::matrix_multiplication_sequential_ijk(&b, &a), None);
+    }
 }
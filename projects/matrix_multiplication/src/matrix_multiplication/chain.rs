@@ -0,0 +1,352 @@
+use std::thread;
+
+use log::error;
+
+use super::{
+    element::Element,
+    matrix::Matrix,
+    matrix_multiplication_parallel_i_loop, matrix_multiplication_sequential_ikj,
+    sanitize::{is_matrix_rectangular, sanitize_matrices, SanitizeError, SanitizeResult},
+};
+
+/// Computes `a` raised to the power `k` by repeated squaring
+///
+/// Needs about `2 * log2(k)` products, computed with the sequential ikj kernel.
+///
+/// # Arguments
+///
+/// * `a` - The square matrix
+/// * `k` - The exponent, `a^0` being the identity
+///
+/// # Returns
+///
+/// The power, or `None` if `a` is not square
+pub fn matrix_power<T: Element>(a: &[Vec<T>], k: u32) -> Option<Vec<Vec<T>>> {
+    power(a, k, |x, y| matrix_multiplication_sequential_ikj(x, y))
+}
+
+/// Computes `a` raised to the power `k` by repeated squaring, with every product
+/// computed by the parallel i-loop kernel on `threads` threads
+pub fn matrix_power_parallel<T: Element>(
+    a: &[Vec<T>],
+    k: u32,
+    threads: usize,
+) -> Option<Vec<Vec<T>>> {
+    power(a, k, |x, y| {
+        matrix_multiplication_parallel_i_loop(x, y, threads)
+    })
+}
+
+fn power<T: Element>(
+    a: &[Vec<T>],
+    mut k: u32,
+    multiply: impl Fn(&[Vec<T>], &[Vec<T>]) -> Option<Vec<Vec<T>>>,
+) -> Option<Vec<Vec<T>>> {
+    match sanitize_matrices(a, a) {
+        SanitizeResult::Ok => (),
+        SanitizeResult::NotOk(error) => {
+            error!("Error: {:?}", error);
+            return None;
+        }
+    };
+
+    let mut result: Option<Vec<Vec<T>>> = None;
+    let mut square = a.to_vec();
+
+    // invariant: a^k_initial = result * square^k
+    while k > 0 {
+        if k % 2 == 1 {
+            result = Some(match result {
+                Some(result) => multiply(&result, &square)?,
+                None => square.clone(),
+            });
+        }
+        k /= 2;
+        if k > 0 {
+            square = multiply(&square, &square)?;
+        }
+    }
+
+    Some(result.unwrap_or_else(|| Matrix::identity(a.len()).into_inner()))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// Cheapest parenthesization of a chain of matrix products
+///
+/// Found by the classic matrix-chain-order dynamic program in `O(n^3)` time.
+pub struct ChainOrder {
+    /// `cost[i][j]` is the number of scalar multiplications for matrices `i..=j`
+    cost: Vec<Vec<u128>>,
+    /// `split[i][j]` is the `k` such that `i..=j` is computed as `(i..=k) * (k+1..=j)`
+    split: Vec<Vec<usize>>,
+}
+
+impl ChainOrder {
+    /// Runs the dynamic program
+    ///
+    /// # Arguments
+    ///
+    /// * `dims` - The `n + 1` dimensions of a chain of `n` matrices, matrix `i` being
+    ///   `dims[i]` x `dims[i + 1]`
+    ///
+    /// # Panics
+    ///
+    /// Panics if `dims` describes an empty chain.
+    pub fn new(dims: &[usize]) -> Self {
+        assert!(dims.len() >= 2, "A chain needs at least one matrix");
+
+        let n = dims.len() - 1;
+        let mut cost = vec![vec![0_u128; n]; n];
+        let mut split = vec![vec![0; n]; n];
+
+        for length in 2..=n {
+            for i in 0..=n - length {
+                let j = i + length - 1;
+                let (best_k, best_cost) = (i..j)
+                    .map(|k| {
+                        let product = dims[i] as u128 * dims[k + 1] as u128 * dims[j + 1] as u128;
+                        (k, cost[i][k] + cost[k + 1][j] + product)
+                    })
+                    .min_by_key(|&(_, cost)| cost)
+                    .unwrap();
+                cost[i][j] = best_cost;
+                split[i][j] = best_k;
+            }
+        }
+
+        ChainOrder { cost, split }
+    }
+
+    /// The number of matrices in the chain
+    pub fn len(&self) -> usize {
+        self.split.len()
+    }
+
+    /// Whether the chain is empty, which `new` never allows
+    pub fn is_empty(&self) -> bool {
+        self.split.is_empty()
+    }
+
+    /// The number of scalar multiplications of the whole chain
+    pub fn cost(&self) -> u128 {
+        self.cost[0][self.len() - 1]
+    }
+
+    /// The parenthesization, with matrices numbered from 1, e.g. `((A1A2)A3)`
+    pub fn parenthesization(&self) -> String {
+        self.format(0, self.len() - 1)
+    }
+
+    fn format(&self, i: usize, j: usize) -> String {
+        match i == j {
+            true => format!("A{}", i + 1),
+            false => {
+                let k = self.split[i][j];
+                format!("({}{})", self.format(i, k), self.format(k + 1, j))
+            }
+        }
+    }
+}
+
+/// Checks that consecutive matrices of the chain can be multiplied
+///
+/// # Returns
+///
+/// The dimensions of the chain, as expected by `ChainOrder::new`
+fn chain_dims<T>(matrices: &[Vec<Vec<T>>]) -> Result<Vec<usize>, SanitizeError> {
+    let first = matrices
+        .first()
+        .ok_or_else(|| SanitizeError::EmptyMatrix("chain".to_string()))?;
+
+    for (index, matrix) in matrices.iter().enumerate() {
+        let name = format!("A{}", index + 1);
+        if let SanitizeResult::NotOk(e) = is_matrix_rectangular(matrix, &name) {
+            return Err(e);
+        }
+    }
+    for pair in matrices.windows(2) {
+        if pair[0][0].len() != pair[1].len() {
+            return Err(SanitizeError::NotConformable {
+                a_cols: pair[0][0].len(),
+                b_rows: pair[1].len(),
+            });
+        }
+    }
+
+    let mut dims = vec![first.len()];
+    dims.extend(matrices.iter().map(|matrix| matrix[0].len()));
+
+    Ok(dims)
+}
+
+/// Multiplies a chain of matrices in the cheapest order
+///
+/// # Arguments
+///
+/// * `matrices` - The matrices, each one having as many columns as the next one has rows
+///
+/// # Returns
+///
+/// The product, or `None` if the chain is empty or two consecutive matrices can't be
+/// multiplied
+pub fn multiply_chain<T: Element>(matrices: &[Vec<Vec<T>>]) -> Option<Vec<Vec<T>>> {
+    multiply_chain_parallel(matrices, 1)
+}
+
+/// Multiplies a chain of matrices in the cheapest order, on `threads` threads
+///
+/// The two sides of every product are independent, so they are computed concurrently
+/// while there are threads to spare, and the remaining threads go to the parallel i-loop
+/// kernel.
+pub fn multiply_chain_parallel<T: Element>(
+    matrices: &[Vec<Vec<T>>],
+    threads: usize,
+) -> Option<Vec<Vec<T>>> {
+    let dims = match chain_dims(matrices) {
+        Ok(dims) => dims,
+        Err(error) => {
+            error!("Error: {:?}", error);
+            return None;
+        }
+    };
+
+    let order = ChainOrder::new(&dims);
+
+    Some(execute(
+        &order,
+        matrices,
+        0,
+        matrices.len() - 1,
+        threads.max(1),
+    ))
+}
+
+/// Computes the product of matrices `i..=j` following `order`
+fn execute<T: Element>(
+    order: &ChainOrder,
+    matrices: &[Vec<Vec<T>>],
+    i: usize,
+    j: usize,
+    threads: usize,
+) -> Vec<Vec<T>> {
+    if i == j {
+        return matrices[i].clone();
+    }
+
+    let k = order.split[i][j];
+    let (left, right) = match threads > 1 && k > i && k + 1 < j {
+        true => thread::scope(|scope| {
+            let left_threads = threads / 2;
+            let left = scope.spawn(move || execute(order, matrices, i, k, left_threads));
+            let right = execute(order, matrices, k + 1, j, threads - left_threads);
+            (left.join().unwrap(), right)
+        }),
+        false => (
+            execute(order, matrices, i, k, threads),
+            execute(order, matrices, k + 1, j, threads),
+        ),
+    };
+
+    // the chain was sanitized, so the kernels always return a result
+    match threads > 1 {
+        true => matrix_multiplication_parallel_i_loop(&left, &right, threads).unwrap(),
+        false => matrix_multiplication_sequential_ikj(&left, &right).unwrap(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matrix_multiplication::generate::{Distribution, MatrixGenerator};
+
+    fn get_chain(dims: &[usize]) -> Vec<Vec<Vec<i64>>> {
+        let mut generator = MatrixGenerator::new(
+            Some(32),
+            Distribution::Uniform {
+                low: -3.0,
+                high: 3.0,
+            },
+        );
+
+        dims.windows(2)
+            .map(|pair| generator.generate(pair[0], pair[1]))
+            .collect()
+    }
+
+    fn multiply_left_to_right(matrices: &[Vec<Vec<i64>>]) -> Vec<Vec<i64>> {
+        matrices[1..]
+            .iter()
+            .fold(matrices[0].clone(), |product, matrix| {
+                matrix_multiplication_sequential_ikj(&product, matrix).unwrap()
+            })
+    }
+
+    #[test]
+    fn test_matrix_power() {
+        let a = get_chain(&[6, 6]).remove(0);
+
+        for k in 0..=6 {
+            let expected = match k {
+                0 => Matrix::identity(6).into_inner(),
+                _ => multiply_left_to_right(&vec![a.clone(); k as usize]),
+            };
+
+            assert_eq!(matrix_power(&a, k).unwrap(), expected, "k = {}", k);
+            assert_eq!(
+                matrix_power_parallel(&a, k, 3).unwrap(),
+                expected,
+                "k = {}",
+                k
+            );
+        }
+    }
+
+    #[test]
+    fn test_matrix_power_fibonacci() {
+        let fibonacci = vec![vec![1_i64, 1], vec![1, 0]];
+
+        assert_eq!(matrix_power(&fibonacci, 50).unwrap()[0][1], 12_586_269_025);
+    }
+
+    #[test]
+    fn test_matrix_power_not_square() {
+        assert!(matrix_power(&[vec![1, 2]], 2).is_none());
+    }
+
+    #[test]
+    fn test_chain_order() {
+        let order = ChainOrder::new(&[30, 35, 15, 5, 10, 20, 25]);
+
+        assert_eq!(order.cost(), 15125);
+        assert_eq!(order.parenthesization(), "((A1(A2A3))((A4A5)A6))");
+        assert_eq!(ChainOrder::new(&[3, 4]).parenthesization(), "A1");
+
+        let large = 1 << 30;
+        let order = ChainOrder::new(&[large, large, large]);
+        assert_eq!(order.cost(), 1 << 90);
+    }
+
+    #[test]
+    fn test_multiply_chain() {
+        let matrices = get_chain(&[10, 2, 30, 3, 25, 4, 9]);
+        let expected = multiply_left_to_right(&matrices);
+
+        assert_eq!(multiply_chain(&matrices).unwrap(), expected);
+        for threads in [2, 3, 8] {
+            assert_eq!(
+                multiply_chain_parallel(&matrices, threads).unwrap(),
+                expected
+            );
+        }
+        assert_eq!(multiply_chain(&matrices[..1]).unwrap(), matrices[0]);
+    }
+
+    #[test]
+    fn test_multiply_chain_invalid() {
+        let matrices = get_chain(&[2, 3, 4]);
+
+        assert!(multiply_chain::<i64>(&[]).is_none());
+        assert!(multiply_chain(&[matrices[1].clone(), matrices[0].clone()]).is_none());
+        assert!(multiply_chain(&[vec![vec![1], vec![2, 3]]]).is_none());
+    }
+}
@@ -0,0 +1,506 @@
+use std::error::Error;
+use std::ffi::OsString;
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io;
+use std::marker::PhantomData;
+use std::mem::size_of;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use memmap2::{Mmap, MmapMut};
+
+use super::{
+    element::Element,
+    gemm::{gemm_parallel, Transpose},
+    io::{
+        binary::{BinaryHeader, HEADER_SIZE},
+        MatrixIoError,
+    },
+    packed::{packing_buffer_len, BlockSizes},
+    sanitize::SanitizeError,
+    simd::SimdLevel,
+};
+
+/// Number of tiles alive during `multiply_out_of_core`: those of A, B and C
+const TILE_BUFFERS: usize = 3;
+
+#[derive(Debug)]
+/// Enum to represent the errors that can occur during an out-of-core multiplication
+pub enum OutOfCoreError {
+    /// A file couldn't be mapped, or isn't a binary matrix of the right element type
+    Io(MatrixIoError),
+    /// The operands can't be multiplied
+    Operands(SanitizeError),
+    /// The memory limit can't hold even 1x1 tiles, with the minimum limit in bytes
+    MemoryLimit { limit: usize, minimum: usize },
+}
+
+impl fmt::Display for OutOfCoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OutOfCoreError::Io(e) => write!(f, "{}", e),
+            OutOfCoreError::Operands(e) => write!(f, "cannot multiply: {}", e),
+            OutOfCoreError::MemoryLimit { limit, minimum } => write!(
+                f,
+                "memory limit of {} bytes is below the minimum of {} bytes",
+                limit, minimum
+            ),
+        }
+    }
+}
+
+impl Error for OutOfCoreError {}
+
+impl From<MatrixIoError> for OutOfCoreError {
+    fn from(e: MatrixIoError) -> Self {
+        OutOfCoreError::Io(e)
+    }
+}
+
+impl From<io::Error> for OutOfCoreError {
+    fn from(e: io::Error) -> Self {
+        OutOfCoreError::Io(MatrixIoError::Io(e))
+    }
+}
+
+enum Mapping {
+    ReadOnly(Mmap),
+    ReadWrite(MmapMut),
+}
+
+impl Mapping {
+    fn bytes(&self) -> &[u8] {
+        match self {
+            Mapping::ReadOnly(map) => map,
+            Mapping::ReadWrite(map) => map,
+        }
+    }
+}
+
+/// A matrix stored in a file of the binary matrix format, mapped into memory
+///
+/// Only the parts of the file being accessed are paged in, and the operating system is
+/// free to evict them again, so the matrix can be larger than the available memory.
+/// Tiles are copied in and out of the mapping with `read_tile` and `write_tile`.
+pub struct DiskMatrix<T> {
+    mapping: Mapping,
+    rows: usize,
+    cols: usize,
+    element: PhantomData<T>,
+}
+
+/// Returns the size in bytes of the binary file of a `rows` x `cols` matrix of `T`
+fn file_size<T>(rows: usize, cols: usize) -> Result<usize, MatrixIoError> {
+    rows.checked_mul(cols)
+        .and_then(|count| count.checked_mul(size_of::<T>()))
+        .and_then(|size| size.checked_add(HEADER_SIZE))
+        .ok_or_else(|| {
+            MatrixIoError::Unsupported(format!("a {}x{} matrix is too large", rows, cols))
+        })
+}
+
+impl<T: Element> DiskMatrix<T> {
+    /// Maps the binary matrix file at `path` for reading
+    ///
+    /// # Returns
+    ///
+    /// The matrix, or an error if the file can't be mapped, isn't a complete binary
+    /// matrix file or holds another element type
+    pub fn open(path: &Path) -> Result<DiskMatrix<T>, MatrixIoError> {
+        let file = File::open(path)?;
+        let header = BinaryHeader::read(&mut &file)?;
+
+        if header.element_type != T::TYPE {
+            return Err(MatrixIoError::Unsupported(format!(
+                "file holds {:?} elements, expected {:?}",
+                header.element_type,
+                T::TYPE
+            )));
+        }
+        let size = file_size::<T>(header.rows, header.cols)?;
+        if file.metadata()?.len() < size as u64 {
+            return Err(MatrixIoError::Unsupported(format!(
+                "file is truncated: a {}x{} matrix needs {} bytes",
+                header.rows, header.cols, size
+            )));
+        }
+
+        // SAFETY: the file must not be truncated by another process while it is mapped,
+        // as for any memory-mapped file
+        let map = unsafe { Mmap::map(&file)? };
+
+        Ok(DiskMatrix {
+            mapping: Mapping::ReadOnly(map),
+            rows: header.rows,
+            cols: header.cols,
+            element: PhantomData,
+        })
+    }
+
+    /// Creates a zero `rows` x `cols` binary matrix file at `path`, replacing any
+    /// existing file, and maps it for reading and writing
+    pub fn create(path: &Path, rows: usize, cols: usize) -> Result<DiskMatrix<T>, MatrixIoError> {
+        let size = file_size::<T>(rows, cols)?;
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+
+        let header = BinaryHeader {
+            element_type: T::TYPE,
+            rows,
+            cols,
+        };
+        header.write(&mut &file)?;
+        // the elements are zero, and take no disk space on file systems with sparse files
+        file.set_len(size as u64)?;
+
+        // SAFETY: see `open`
+        let map = unsafe { MmapMut::map_mut(&file)? };
+
+        Ok(DiskMatrix {
+            mapping: Mapping::ReadWrite(map),
+            rows,
+            cols,
+            element: PhantomData,
+        })
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    /// Returns the byte range of the elements of `row` in the columns `cols`
+    fn byte_range(&self, row: usize, cols: &Range<usize>) -> Range<usize> {
+        let start = HEADER_SIZE + (row * self.cols + cols.start) * size_of::<T>();
+        start..start + cols.len() * size_of::<T>()
+    }
+
+    /// Copies the block at `rows` and `cols` into memory
+    ///
+    /// # Panics
+    ///
+    /// Panics if the block doesn't lie within the matrix
+    pub fn read_tile(&self, rows: Range<usize>, cols: Range<usize>) -> Vec<Vec<T>> {
+        assert!(rows.end <= self.rows && cols.end <= self.cols);
+        let bytes = self.mapping.bytes();
+
+        rows.map(|row| {
+            let mut reader = &bytes[self.byte_range(row, &cols)];
+            cols.clone()
+                .map(|_| T::read_le(&mut reader).unwrap())
+                .collect()
+        })
+        .collect()
+    }
+
+    /// Copies `tile` into the matrix, with its first element at `(row, col)`
+    ///
+    /// # Panics
+    ///
+    /// Panics if the matrix was opened read-only, or if the tile doesn't lie within
+    /// the matrix
+    pub fn write_tile(&mut self, row: usize, col: usize, tile: &[Vec<T>]) {
+        assert!(row + tile.len() <= self.rows);
+        let ranges: Vec<Range<usize>> = tile
+            .iter()
+            .enumerate()
+            .map(|(i, tile_row)| {
+                assert!(col + tile_row.len() <= self.cols);
+                self.byte_range(row + i, &(col..col + tile_row.len()))
+            })
+            .collect();
+
+        let map = match &mut self.mapping {
+            Mapping::ReadWrite(map) => map,
+            Mapping::ReadOnly(_) => panic!("cannot write to a matrix mapped read-only"),
+        };
+        for (tile_row, range) in tile.iter().zip(ranges) {
+            let mut writer = &mut map[range];
+            for &value in tile_row {
+                value.write_le(&mut writer).unwrap();
+            }
+        }
+    }
+
+    /// Writes the modified pages back to the file
+    pub fn flush(&self) -> io::Result<()> {
+        match &self.mapping {
+            Mapping::ReadWrite(map) => map.flush(),
+            Mapping::ReadOnly(_) => Ok(()),
+        }
+    }
+
+    /// Copies the whole matrix into memory
+    pub fn to_dense(&self) -> Vec<Vec<T>> {
+        self.read_tile(0..self.rows, 0..self.cols)
+    }
+}
+
+/// Returns the bytes of the buffers `multiply_out_of_core` holds with square tiles of
+/// `side` on `threads` threads: the tiles and the packing buffers of the GEMM
+fn tile_memory<T: Element>(side: usize, threads: usize) -> Option<usize> {
+    let packing = packing_buffer_len::<T>(
+        side,
+        side,
+        side,
+        threads,
+        BlockSizes::default(),
+        SimdLevel::detect(),
+    )?;
+
+    side.checked_mul(side)?
+        .checked_mul(TILE_BUFFERS)?
+        .checked_add(packing)?
+        .checked_mul(size_of::<T>())
+}
+
+/// Returns the side of the largest square tiles of `T` that `multiply_out_of_core` can
+/// use within `memory_limit` bytes on `threads` threads, or `None` if not even 1x1
+/// tiles fit
+pub fn tile_size_for<T: Element>(memory_limit: usize, threads: usize) -> Option<usize> {
+    let fits = |side| tile_memory::<T>(side, threads).is_some_and(|bytes| bytes <= memory_limit);
+    if !fits(1) {
+        return None;
+    }
+
+    // the memory grows with the side, and the tiles alone bound it
+    let (mut low, mut high) = (1, (memory_limit / size_of::<T>()).isqrt());
+    while low < high {
+        let side = low + (high - low).div_ceil(2);
+        match fits(side) {
+            true => low = side,
+            false => high = side - 1,
+        }
+    }
+
+    Some(low)
+}
+
+/// Splits `0..len` into consecutive ranges of `size` elements, the last one shorter
+fn tiles(len: usize, size: usize) -> impl Iterator<Item = Range<usize>> {
+    (0..len)
+        .step_by(size)
+        .map(move |start| start..(start + size).min(len))
+}
+
+/// Returns the path of a hidden file next to `path`, to write its new contents to
+fn temporary_path(path: &Path) -> PathBuf {
+    let mut name = OsString::from(".");
+    name.push(path.file_name().unwrap_or_default());
+    name.push(format!(".{}.tmp", process::id()));
+    path.with_file_name(name)
+}
+
+/// Multiplies two disk-backed matrices tile by tile, writing the product to `c`
+///
+/// Each tile of C is accumulated in memory from the products of the tiles of A and B
+/// along the inner dimension, computed with the packed GEMM, and then written to the
+/// file. The tiles are as large as `memory_limit` allows. The limit covers the buffers
+/// of the multiplication, not the pages of the mapped files, which the operating system
+/// evicts as needed.
+///
+/// The product is written to a temporary file next to `c`, which replaces `c` once
+/// complete, so `c` is left untouched if the multiplication fails.
+///
+/// # Arguments
+///
+/// * `a` - The first matrix
+/// * `b` - The second matrix
+/// * `c` - The path of the binary matrix file to create for the product
+/// * `memory_limit` - The memory available to the buffers, in bytes
+/// * `threads` - The number of threads used to multiply each pair of tiles
+///
+/// # Returns
+///
+/// The product mapped from `c`, or the first error that occurred
+pub fn multiply_out_of_core<T: Element>(
+    a: &DiskMatrix<T>,
+    b: &DiskMatrix<T>,
+    c: &Path,
+    memory_limit: usize,
+    threads: usize,
+) -> Result<DiskMatrix<T>, OutOfCoreError> {
+    for (matrix, name) in [(a, "A"), (b, "B")] {
+        if matrix.rows() == 0 || matrix.cols() == 0 {
+            return Err(OutOfCoreError::Operands(SanitizeError::EmptyMatrix(
+                name.to_string(),
+            )));
+        }
+    }
+    if a.cols() != b.rows() {
+        return Err(OutOfCoreError::Operands(SanitizeError::NotConformable {
+            a_cols: a.cols(),
+            b_rows: b.rows(),
+        }));
+    }
+
+    let tile = tile_size_for::<T>(memory_limit, threads).ok_or(OutOfCoreError::MemoryLimit {
+        limit: memory_limit,
+        // 1x1 tiles need only a few panels, far from overflowing
+        minimum: tile_memory::<T>(1, threads).unwrap(),
+    })?;
+
+    let temporary = temporary_path(c);
+    let result = multiply_tiles(a, b, &temporary, tile, threads).and_then(|product| {
+        fs::rename(&temporary, c)?;
+        Ok(product)
+    });
+    if result.is_err() {
+        // the file may not have been created
+        let _ = fs::remove_file(&temporary);
+    }
+
+    result
+}
+
+/// Multiplies `a` and `b` into a new file at `c`, with square tiles of side `tile`
+fn multiply_tiles<T: Element>(
+    a: &DiskMatrix<T>,
+    b: &DiskMatrix<T>,
+    c: &Path,
+    tile: usize,
+    threads: usize,
+) -> Result<DiskMatrix<T>, OutOfCoreError> {
+    let (m, k, n) = (a.rows(), a.cols(), b.cols());
+    let mut product = DiskMatrix::create(c, m, n)?;
+
+    for rows in tiles(m, tile) {
+        for cols in tiles(n, tile) {
+            let mut c_tile = vec![vec![T::zero(); cols.len()]; rows.len()];
+
+            for depth in tiles(k, tile) {
+                let a_tile = a.read_tile(rows.clone(), depth.clone());
+                let b_tile = b.read_tile(depth, cols.clone());
+                gemm_parallel(
+                    Transpose::No,
+                    Transpose::No,
+                    T::one(),
+                    &a_tile,
+                    &b_tile,
+                    T::one(),
+                    &mut c_tile,
+                    threads,
+                )
+                .unwrap();
+            }
+
+            product.write_tile(rows.start, cols.start, &c_tile);
+        }
+    }
+
+    product.flush()?;
+    Ok(product)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matrix_multiplication::{
+        generate::{Distribution, MatrixGenerator},
+        io::{read_matrix, write_matrix},
+        matrix_multiplication_sequential_ikj,
+    };
+
+    #[test]
+    fn test_disk_matrix_tiles() {
+        let directory = std::env::temp_dir().join(format!("disk_tiles_{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let (a_path, c_path) = (directory.join("a.bin"), directory.join("c.bin"));
+
+        let a = vec![vec![1.0, 2.0, 3.0], vec![4.0, 5.0, 6.0]];
+        write_matrix(&a_path, &a).unwrap();
+        let disk_a = DiskMatrix::<f64>::open(&a_path).unwrap();
+        assert_eq!((disk_a.rows(), disk_a.cols()), (2, 3));
+        assert_eq!(
+            disk_a.read_tile(0..2, 1..3),
+            vec![vec![2.0, 3.0], vec![5.0, 6.0]]
+        );
+        assert_eq!(disk_a.to_dense(), a);
+        assert!(DiskMatrix::<f32>::open(&a_path).is_err());
+
+        let mut disk_c = DiskMatrix::<i32>::create(&c_path, 3, 3).unwrap();
+        disk_c.write_tile(1, 1, &[vec![1, 2], vec![3, 4]]);
+        disk_c.flush().unwrap();
+        assert_eq!(
+            read_matrix::<i32>(&c_path).unwrap(),
+            vec![vec![0, 0, 0], vec![0, 1, 2], vec![0, 3, 4]]
+        );
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn test_tile_size_for() {
+        let limit = tile_memory::<f64>(10, 1).unwrap();
+        assert_eq!(tile_size_for::<f64>(limit, 1), Some(10));
+        assert_eq!(tile_size_for::<f64>(limit - 1, 1), Some(9));
+        assert!(tile_size_for::<f64>(limit, 4).unwrap() < 10);
+
+        // the packing buffers are padded to whole panels
+        let minimum = tile_memory::<i32>(1, 1).unwrap();
+        assert!(minimum > TILE_BUFFERS * 4);
+        assert_eq!(tile_size_for::<i32>(minimum, 1), Some(1));
+        assert_eq!(tile_size_for::<i32>(minimum - 1, 1), None);
+        assert!(tile_size_for::<i32>(usize::MAX, 1).is_some());
+    }
+
+    #[test]
+    fn test_multiply_out_of_core() {
+        let directory = std::env::temp_dir().join(format!("disk_multiply_{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let (a_path, b_path, c_path) = (
+            directory.join("a.bin"),
+            directory.join("b.bin"),
+            directory.join("c.bin"),
+        );
+
+        let mut generator = MatrixGenerator::new(Some(38), Distribution::default());
+        let a: Vec<Vec<i64>> = generator.generate(7, 11);
+        let b: Vec<Vec<i64>> = generator.generate(11, 5);
+        write_matrix(&a_path, &a).unwrap();
+        write_matrix(&b_path, &b).unwrap();
+        let disk_a = DiskMatrix::<i64>::open(&a_path).unwrap();
+        let disk_b = DiskMatrix::open(&b_path).unwrap();
+        let expected = matrix_multiplication_sequential_ikj(&a, &b).unwrap();
+
+        // tiles of 1, 3 and more than every dimension
+        for limit in [
+            tile_memory::<i64>(1, 2).unwrap(),
+            tile_memory::<i64>(3, 2).unwrap(),
+            1 << 20,
+        ] {
+            let c = multiply_out_of_core(&disk_a, &disk_b, &c_path, limit, 2).unwrap();
+            assert_eq!(c.to_dense(), expected, "limit {}", limit);
+            assert_eq!(read_matrix::<i64>(&c_path).unwrap(), expected);
+        }
+
+        assert!(matches!(
+            multiply_out_of_core(&disk_a, &disk_b, &c_path, 8, 1),
+            Err(OutOfCoreError::MemoryLimit { .. })
+        ));
+        assert_eq!(read_matrix::<i64>(&c_path).unwrap(), expected);
+
+        // the operand stays mapped while its file is replaced by the product
+        let c = multiply_out_of_core(&disk_a, &disk_b, &a_path, 1 << 20, 1).unwrap();
+        assert_eq!(c.to_dense(), expected);
+        assert_eq!(read_matrix::<i64>(&a_path).unwrap(), expected);
+        assert_eq!(std::fs::read_dir(&directory).unwrap().count(), 3);
+        assert!(matches!(
+            multiply_out_of_core(&disk_a, &disk_a, &c_path, 1 << 20, 1),
+            Err(OutOfCoreError::Operands(
+                SanitizeError::NotConformable { .. }
+            ))
+        ));
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,675 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::io::{self, BufReader, BufWriter, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream};
+use std::ops::Range;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::{
+    element::{Element, ElementType},
+    gemm::{gemm, Transpose},
+    generate::generate_zero_matrix,
+    io::MatrixIoError,
+    partition::tile_grid,
+    sanitize::{sanitize_product, SanitizeError, SanitizeResult},
+};
+
+mod wire;
+
+use wire::{write_panel, Message, Operand, Setup};
+
+/// Default number of columns of A, and rows of B, broadcast at every step of SUMMA
+pub const DEFAULT_PANEL_WIDTH: usize = 64;
+
+#[derive(Debug)]
+/// Enum to represent the errors that can occur during a distributed multiplication
+pub enum DistributedError {
+    /// A connection failed
+    Io(io::Error),
+    /// The operands can't be multiplied
+    Operands(SanitizeError),
+    /// There is no worker to multiply on
+    NoWorkers,
+    /// A peer sent something unexpected or closed its connection too early
+    Protocol(String),
+}
+
+impl fmt::Display for DistributedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DistributedError::Io(e) => write!(f, "{}", e),
+            DistributedError::Operands(e) => write!(f, "cannot multiply: {}", e),
+            DistributedError::NoWorkers => write!(f, "no workers to multiply on"),
+            DistributedError::Protocol(message) => write!(f, "protocol error: {}", message),
+        }
+    }
+}
+
+impl Error for DistributedError {}
+
+impl From<io::Error> for DistributedError {
+    fn from(e: io::Error) -> Self {
+        DistributedError::Io(e)
+    }
+}
+
+impl From<MatrixIoError> for DistributedError {
+    fn from(e: MatrixIoError) -> Self {
+        match e {
+            MatrixIoError::Io(e) => DistributedError::Io(e),
+            e => DistributedError::Protocol(e.to_string()),
+        }
+    }
+}
+
+impl From<SanitizeError> for DistributedError {
+    fn from(e: SanitizeError) -> Self {
+        DistributedError::Operands(e)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Where a worker spent its time
+pub struct WorkerReport {
+    /// The position of the worker in the grid, as `(row, column)`
+    pub position: (usize, usize),
+    /// Time spent multiplying panels
+    pub compute: Duration,
+    /// Time spent receiving and broadcasting panels, including waiting for them
+    pub communication: Duration,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// Timings of a distributed multiplication
+pub struct SummaReport {
+    /// The shape of the grid of workers, as `(rows, columns)`
+    pub grid: (usize, usize),
+    /// Time the coordinator spent connecting and sending the operands to the workers
+    pub distribution: Duration,
+    /// Time from the end of the distribution until every block of C was gathered
+    pub multiplication: Duration,
+    /// The report of every worker, by rank
+    pub workers: Vec<WorkerReport>,
+}
+
+/// The partition of the product among the grid of workers
+///
+/// Worker `(r, c)` computes the block of C made of row block `r` and column block `c`.
+/// The inner dimension is cut into panels dealt cyclically: panel `p` of the rows of A
+/// in row block `r` is owned by worker `(r, p % columns)`, and panel `p` of the columns
+/// of B in column block `c` by worker `(p % rows, c)`.
+struct Layout {
+    grid: (usize, usize),
+    dimensions: (usize, usize, usize),
+    panel_width: usize,
+}
+
+impl Layout {
+    fn new(setup: &Setup) -> Layout {
+        Layout {
+            grid: setup.grid,
+            dimensions: setup.dimensions,
+            panel_width: setup.panel_width.max(1),
+        }
+    }
+
+    fn position(&self, rank: usize) -> (usize, usize) {
+        (rank / self.grid.1, rank % self.grid.1)
+    }
+
+    fn rank(&self, position: (usize, usize)) -> usize {
+        position.0 * self.grid.1 + position.1
+    }
+
+    fn panels(&self) -> usize {
+        self.dimensions.1.div_ceil(self.panel_width)
+    }
+
+    fn panel(&self, index: usize) -> Range<usize> {
+        index * self.panel_width..((index + 1) * self.panel_width).min(self.dimensions.1)
+    }
+
+    /// The rows of C in row block `row`, which may be empty
+    fn rows(&self, row: usize) -> Range<usize> {
+        let (m, blocks) = (self.dimensions.0, self.grid.0);
+        m * row / blocks..m * (row + 1) / blocks
+    }
+
+    /// The columns of C in column block `col`, which may be empty
+    fn cols(&self, col: usize) -> Range<usize> {
+        let (n, blocks) = (self.dimensions.2, self.grid.1);
+        n * col / blocks..n * (col + 1) / blocks
+    }
+
+    /// The shape of panel `index` of `operand` for the worker at `position`, as
+    /// `(rows, columns)`
+    fn panel_shape(
+        &self,
+        operand: Operand,
+        position: (usize, usize),
+        index: usize,
+    ) -> (usize, usize) {
+        match operand {
+            Operand::A => (self.rows(position.0).len(), self.panel(index).len()),
+            Operand::B => (self.panel(index).len(), self.cols(position.1).len()),
+        }
+    }
+
+    fn owner(&self, operand: Operand, position: (usize, usize), index: usize) -> usize {
+        match operand {
+            Operand::A => self.rank((position.0, index % self.grid.1)),
+            Operand::B => self.rank((index % self.grid.0, position.1)),
+        }
+    }
+}
+
+/// Copies the block of `matrix` at `rows` and `cols`
+fn block<T: Element>(matrix: &[Vec<T>], rows: Range<usize>, cols: Range<usize>) -> Vec<Vec<T>> {
+    matrix[rows]
+        .iter()
+        .map(|row| row[cols.clone()].to_vec())
+        .collect()
+}
+
+/// Returns whether `block`, as received, is a `rows` x `cols` matrix
+///
+/// A block without columns is received without rows, since the binary format stores it
+/// as a 0x0 matrix.
+fn has_shape<T>(block: &[Vec<T>], (rows, cols): (usize, usize)) -> bool {
+    let expected_rows = if cols == 0 { 0 } else { rows };
+    block.len() == expected_rows && block.iter().all(|row| row.len() == cols)
+}
+
+/// Multiplies the matrices with SUMMA on the workers listening at `workers`
+///
+/// The workers form a grid as close to square as possible, each computing one block
+/// of C. The coordinator sends every worker the panels of A and B it owns; at each
+/// step the owners broadcast their panel of A along their grid row and their panel of
+/// B along their grid column, and every worker adds the product of the two panels to
+/// its block. The coordinator then gathers the blocks of C.
+///
+/// # Arguments
+///
+/// * `a` - The first matrix
+/// * `b` - The second matrix
+/// * `workers` - The addresses of the workers, each serving with `serve_worker`
+/// * `panel_width` - The number of columns of A, and rows of B, in each panel
+///
+/// # Returns
+///
+/// The product of the matrices and the timings of the run, or the first error that
+/// occurred
+pub fn summa<T: Element>(
+    a: &[Vec<T>],
+    b: &[Vec<T>],
+    workers: &[SocketAddr],
+    panel_width: usize,
+) -> Result<(Vec<Vec<T>>, SummaReport), DistributedError> {
+    if let SanitizeResult::NotOk(e) = sanitize_product(a, b) {
+        return Err(DistributedError::Operands(e));
+    }
+    if workers.is_empty() {
+        return Err(DistributedError::NoWorkers);
+    }
+
+    let (m, k, n) = (a.len(), b.len(), b[0].len());
+    let mut setup = Setup {
+        rank: 0,
+        grid: tile_grid(workers.len(), m, n),
+        dimensions: (m, k, n),
+        panel_width: panel_width.max(1),
+        element_type: T::TYPE,
+        workers: workers.iter().map(|worker| worker.to_string()).collect(),
+    };
+    let layout = Layout::new(&setup);
+
+    let start = Instant::now();
+
+    // connect to every worker before sending any setup, so the first connection each
+    // worker accepts is the coordinator's rather than one of another worker
+    let streams = workers
+        .iter()
+        .map(|worker| {
+            let stream = TcpStream::connect(worker)
+                .map_err(|e| io::Error::new(e.kind(), format!("worker {}: {}", worker, e)))?;
+            stream.set_nodelay(true)?;
+            Ok(stream)
+        })
+        .collect::<Result<Vec<_>, DistributedError>>()?;
+
+    let mut connections = Vec::with_capacity(workers.len());
+    for (rank, stream) in streams.into_iter().enumerate() {
+        let mut writer = BufWriter::new(stream.try_clone()?);
+
+        setup.rank = rank;
+        setup.write(&mut writer)?;
+
+        let position = layout.position(rank);
+        let (rows, cols) = (layout.rows(position.0), layout.cols(position.1));
+        for index in 0..layout.panels() {
+            if layout.owner(Operand::A, position, index) == rank {
+                let panel = block(a, rows.clone(), layout.panel(index));
+                write_panel(&mut writer, Operand::A, index, &panel)?;
+            }
+            if layout.owner(Operand::B, position, index) == rank {
+                let panel = block(b, layout.panel(index), cols.clone());
+                write_panel(&mut writer, Operand::B, index, &panel)?;
+            }
+        }
+        writer.flush()?;
+
+        connections.push(BufReader::new(stream));
+    }
+
+    let distribution = start.elapsed();
+
+    let mut c = generate_zero_matrix(m, n);
+    let mut reports = Vec::with_capacity(workers.len());
+    for (rank, reader) in connections.iter_mut().enumerate() {
+        let position = layout.position(rank);
+        let (rows, cols) = (layout.rows(position.0), layout.cols(position.1));
+
+        let (compute, communication, result) = match Message::read(reader)? {
+            Some(Message::Result {
+                compute,
+                communication,
+                block,
+            }) => (compute, communication, block),
+            Some(_) => {
+                return Err(DistributedError::Protocol(format!(
+                    "worker {} sent something else than its result",
+                    rank
+                )))
+            }
+            None => {
+                return Err(DistributedError::Protocol(format!(
+                    "worker {} closed the connection before sending its result",
+                    rank
+                )))
+            }
+        };
+
+        if !has_shape(&result, (rows.len(), cols.len())) {
+            return Err(DistributedError::Protocol(format!(
+                "worker {} sent a block of the wrong shape",
+                rank
+            )));
+        }
+        for (c_row, result_row) in c[rows].iter_mut().zip(result) {
+            c_row[cols.clone()].copy_from_slice(&result_row);
+        }
+
+        reports.push(WorkerReport {
+            position,
+            compute,
+            communication,
+        });
+    }
+
+    Ok((
+        c,
+        SummaReport {
+            grid: layout.grid,
+            distribution,
+            multiplication: start.elapsed() - distribution,
+            workers: reports,
+        },
+    ))
+}
+
+/// The connections of a worker to the other workers of its grid row and column
+struct Peers<T> {
+    writers: HashMap<usize, BufWriter<TcpStream>>,
+    /// The messages of every peer, read by one thread per connection
+    messages: Receiver<Result<Message<T>, DistributedError>>,
+    /// Panels received before they were needed
+    pending: HashMap<(Operand, usize), Vec<Vec<T>>>,
+}
+
+impl<T: Element> Peers<T> {
+    /// Sends `panel` to the workers with the given ranks
+    fn broadcast(
+        &mut self,
+        ranks: &[usize],
+        operand: Operand,
+        index: usize,
+        panel: &[Vec<T>],
+    ) -> Result<(), DistributedError> {
+        for rank in ranks {
+            let writer = self.writers.get_mut(rank).unwrap();
+            write_panel(writer, operand, index, panel)?;
+            writer.flush()?;
+        }
+
+        Ok(())
+    }
+
+    /// Waits for panel `index` of `operand`, checking that it has the shape `layout` gives
+    /// it at `position`
+    fn receive(
+        &mut self,
+        layout: &Layout,
+        position: (usize, usize),
+        operand: Operand,
+        index: usize,
+    ) -> Result<Vec<Vec<T>>, DistributedError> {
+        let panel = self.wait(operand, index)?;
+
+        if !has_shape(&panel, layout.panel_shape(operand, position, index)) {
+            return Err(DistributedError::Protocol(format!(
+                "panel {} of {:?} has the wrong shape",
+                index, operand
+            )));
+        }
+
+        Ok(panel)
+    }
+
+    fn wait(&mut self, operand: Operand, index: usize) -> Result<Vec<Vec<T>>, DistributedError> {
+        if let Some(panel) = self.pending.remove(&(operand, index)) {
+            return Ok(panel);
+        }
+
+        loop {
+            match self.messages.recv() {
+                Ok(Ok(Message::Panel {
+                    operand: received,
+                    index: received_index,
+                    block,
+                })) => {
+                    if (received, received_index) == (operand, index) {
+                        return Ok(block);
+                    }
+                    self.pending.insert((received, received_index), block);
+                }
+                Ok(Ok(_)) => {
+                    return Err(DistributedError::Protocol(
+                        "a worker sent something else than a panel".to_string(),
+                    ))
+                }
+                Ok(Err(e)) => return Err(e),
+                Err(_) => {
+                    return Err(DistributedError::Protocol(format!(
+                        "the workers disconnected before sending panel {} of {:?}",
+                        index, operand
+                    )))
+                }
+            }
+        }
+    }
+}
+
+/// Serves one distributed multiplication as a worker
+///
+/// The coordinator and the other workers of the grid all connect to `listener`, so
+/// its address is the one to give to `summa`.
+pub fn serve_worker(listener: &TcpListener) -> Result<(), DistributedError> {
+    let (stream, _) = listener.accept()?;
+    stream.set_nodelay(true)?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let setup = Setup::read(&mut reader)?;
+
+    match setup.element_type {
+        ElementType::I32 => run_worker::<i32>(listener, stream, reader, setup),
+        ElementType::I64 => run_worker::<i64>(listener, stream, reader, setup),
+        ElementType::F32 => run_worker::<f32>(listener, stream, reader, setup),
+        ElementType::F64 => run_worker::<f64>(listener, stream, reader, setup),
+    }
+}
+
+fn run_worker<T: Element>(
+    listener: &TcpListener,
+    coordinator: TcpStream,
+    mut coordinator_reader: BufReader<TcpStream>,
+    setup: Setup,
+) -> Result<(), DistributedError> {
+    let start = Instant::now();
+    let layout = Layout::new(&setup);
+    let position = layout.position(setup.rank);
+
+    // the panels this worker owns
+    let mut pending = HashMap::new();
+    let owned: usize = (0..layout.panels())
+        .flat_map(|index| [(Operand::A, index), (Operand::B, index)])
+        .filter(|&(operand, index)| layout.owner(operand, position, index) == setup.rank)
+        .count();
+    for _ in 0..owned {
+        match Message::read(&mut coordinator_reader)? {
+            Some(Message::Panel {
+                operand,
+                index,
+                block,
+            }) => {
+                pending.insert((operand, index), block);
+            }
+            _ => {
+                return Err(DistributedError::Protocol(
+                    "expected a panel from the coordinator".to_string(),
+                ))
+            }
+        }
+    }
+
+    // connect to the workers of the same row and column: each worker opens the
+    // connections to the workers of higher rank and accepts the others
+    let row_peers: Vec<usize> = (0..layout.grid.1)
+        .map(|col| layout.rank((position.0, col)))
+        .filter(|&rank| rank != setup.rank)
+        .collect();
+    let col_peers: Vec<usize> = (0..layout.grid.0)
+        .map(|row| layout.rank((row, position.1)))
+        .filter(|&rank| rank != setup.rank)
+        .collect();
+    let peers: Vec<usize> = row_peers.iter().chain(&col_peers).copied().collect();
+
+    let mut streams = Vec::with_capacity(peers.len());
+    let mut readers = Vec::with_capacity(peers.len());
+    let mut writers = HashMap::with_capacity(peers.len());
+    for &rank in peers.iter().filter(|&&rank| rank > setup.rank) {
+        let stream = TcpStream::connect(&setup.workers[rank])?;
+        stream.set_nodelay(true)?;
+        let mut writer = BufWriter::new(stream.try_clone()?);
+        Message::<T>::Hello { rank: setup.rank }.write(&mut writer)?;
+        writer.flush()?;
+
+        readers.push(BufReader::new(stream.try_clone()?));
+        writers.insert(rank, writer);
+        streams.push(stream);
+    }
+    for _ in peers.iter().filter(|&&rank| rank < setup.rank) {
+        let (stream, _) = listener.accept()?;
+        stream.set_nodelay(true)?;
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let rank = match Message::<T>::read(&mut reader)? {
+            Some(Message::Hello { rank })
+                if rank < setup.rank && peers.contains(&rank) && !writers.contains_key(&rank) =>
+            {
+                rank
+            }
+            _ => {
+                return Err(DistributedError::Protocol(
+                    "expected a hello from a worker of the same row or column".to_string(),
+                ))
+            }
+        };
+
+        readers.push(reader);
+        writers.insert(rank, BufWriter::new(stream.try_clone()?));
+        streams.push(stream);
+    }
+
+    // reading on separate threads keeps the broadcasts of two workers to each other
+    // from blocking both of them once the socket buffers are full
+    let (sender, messages) = mpsc::channel();
+    let reader_threads: Vec<_> = readers
+        .into_iter()
+        .map(|mut reader| {
+            let sender = sender.clone();
+            thread::spawn(move || loop {
+                match Message::read(&mut reader) {
+                    Ok(Some(message)) => {
+                        if sender.send(Ok(message)).is_err() {
+                            break;
+                        }
+                    }
+                    Ok(None) => break,
+                    Err(e) => {
+                        let _ = sender.send(Err(e));
+                        break;
+                    }
+                }
+            })
+        })
+        .collect();
+    drop(sender);
+
+    let mut peers = Peers {
+        writers,
+        messages,
+        pending,
+    };
+
+    let (rows, cols) = (layout.rows(position.0), layout.cols(position.1));
+    let mut c: Vec<Vec<T>> = vec![vec![T::zero(); cols.len()]; rows.len()];
+    let mut compute = Duration::ZERO;
+    let mut communication = start.elapsed();
+
+    for index in 0..layout.panels() {
+        let exchange_start = Instant::now();
+
+        let a_panel = peers.receive(&layout, position, Operand::A, index)?;
+        if layout.owner(Operand::A, position, index) == setup.rank {
+            peers.broadcast(&row_peers, Operand::A, index, &a_panel)?;
+        }
+        let b_panel = peers.receive(&layout, position, Operand::B, index)?;
+        if layout.owner(Operand::B, position, index) == setup.rank {
+            peers.broadcast(&col_peers, Operand::B, index, &b_panel)?;
+        }
+
+        let compute_start = Instant::now();
+        communication += compute_start - exchange_start;
+
+        if !rows.is_empty() && !cols.is_empty() {
+            gemm(
+                Transpose::No,
+                Transpose::No,
+                T::one(),
+                &a_panel,
+                &b_panel,
+                T::one(),
+                &mut c,
+            )?;
+        }
+
+        compute += compute_start.elapsed();
+    }
+
+    // every panel this worker needs was received, so nothing is left to read
+    for stream in &streams {
+        stream.shutdown(Shutdown::Both)?;
+    }
+    for reader_thread in reader_threads {
+        reader_thread.join().unwrap();
+    }
+
+    let mut writer = BufWriter::new(coordinator);
+    Message::Result {
+        compute,
+        communication,
+        block: c,
+    }
+    .write(&mut writer)?;
+    writer.flush()?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matrix_multiplication::{
+        generate::{Distribution, MatrixGenerator},
+        matrix_multiplication_sequential_ikj,
+    };
+
+    /// Starts `count` workers on threads of this process
+    fn start_workers(count: usize) -> (Vec<SocketAddr>, Vec<thread::JoinHandle<()>>) {
+        (0..count)
+            .map(|_| {
+                let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+                let address = listener.local_addr().unwrap();
+                let handle = thread::spawn(move || serve_worker(&listener).unwrap());
+                (address, handle)
+            })
+            .unzip()
+    }
+
+    #[test]
+    fn test_summa_matches_ikj() {
+        let mut generator = MatrixGenerator::new(Some(37), Distribution::default());
+
+        for (workers, (m, k, n), panel_width) in [
+            (1, (5, 7, 3), 2),
+            (3, (9, 4, 6), 3),
+            (4, (10, 13, 11), 4),
+            (6, (2, 8, 2), 1),
+        ] {
+            let a: Vec<Vec<i64>> = generator.generate(m, k);
+            let b: Vec<Vec<i64>> = generator.generate(k, n);
+            let (addresses, handles) = start_workers(workers);
+
+            let (c, report) = summa(&a, &b, &addresses, panel_width).unwrap();
+
+            assert_eq!(c, matrix_multiplication_sequential_ikj(&a, &b).unwrap());
+            assert_eq!(report.grid.0 * report.grid.1, workers);
+            assert_eq!(report.workers.len(), workers);
+            for handle in handles {
+                handle.join().unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn test_summa_errors() {
+        let a = vec![vec![1.0, 2.0]];
+
+        assert!(matches!(
+            summa(&a, &a, &[], 1),
+            Err(DistributedError::Operands(
+                SanitizeError::NotConformable { .. }
+            ))
+        ));
+        assert!(matches!(
+            summa(&a, &[vec![1.0], vec![2.0]], &[], 1),
+            Err(DistributedError::NoWorkers)
+        ));
+    }
+
+    #[test]
+    fn test_layout() {
+        let layout = Layout {
+            grid: (2, 3),
+            dimensions: (5, 7, 4),
+            panel_width: 3,
+        };
+
+        assert_eq!(layout.panels(), 3);
+        assert_eq!(layout.panel(2), 6..7);
+        assert_eq!((layout.rows(0), layout.rows(1)), (0..2, 2..5));
+        assert_eq!(
+            (layout.cols(0), layout.cols(1), layout.cols(2)),
+            (0..1, 1..2, 2..4)
+        );
+        assert_eq!(layout.position(4), (1, 1));
+        assert_eq!(layout.owner(Operand::A, (1, 1), 2), 5);
+        assert_eq!(layout.owner(Operand::B, (1, 1), 2), 1);
+        assert_eq!(layout.panel_shape(Operand::A, (1, 2), 2), (3, 1));
+        assert_eq!(layout.panel_shape(Operand::B, (1, 2), 0), (3, 2));
+        assert!(has_shape(&vec![vec![1, 2]; 3], (3, 2)));
+        assert!(!has_shape(&vec![vec![1, 2]; 3], (2, 3)));
+        assert!(has_shape::<i32>(&[], (3, 0)));
+    }
+}
@@ -0,0 +1,468 @@
+use std::io::{self, Read, Write};
+use std::time::Duration;
+
+use super::DistributedError;
+use crate::matrix_multiplication::{
+    element::{Element, ElementType},
+    io::binary::{element_type_code, element_type_from_code, read_binary, write_binary},
+};
+
+const SETUP: u8 = 1;
+const HELLO: u8 = 2;
+const PANEL: u8 = 3;
+const RESULT: u8 = 4;
+
+/// The longest worker address accepted in a setup message
+const MAX_ADDRESS_LENGTH: usize = 1024;
+
+/// The most workers accepted in a setup message
+const MAX_WORKERS: usize = 1 << 16;
+
+/// The most elements accepted in each of A, B and C in a setup message
+const MAX_ELEMENTS: usize = 1 << 32;
+
+fn write_u64<W: Write>(writer: &mut W, value: u64) -> io::Result<()> {
+    writer.write_all(&value.to_le_bytes())
+}
+
+fn read_u64<R: Read>(reader: &mut R) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+fn write_usize<W: Write>(writer: &mut W, value: usize) -> io::Result<()> {
+    write_u64(writer, value as u64)
+}
+
+fn read_usize<R: Read>(reader: &mut R) -> Result<usize, DistributedError> {
+    let value = read_u64(reader)?;
+    usize::try_from(value)
+        .map_err(|_| DistributedError::Protocol(format!("value {} is too large", value)))
+}
+
+fn write_duration<W: Write>(writer: &mut W, duration: Duration) -> io::Result<()> {
+    write_u64(writer, duration.as_nanos() as u64)
+}
+
+fn read_duration<R: Read>(reader: &mut R) -> io::Result<Duration> {
+    read_u64(reader).map(Duration::from_nanos)
+}
+
+fn read_byte<R: Read>(reader: &mut R) -> io::Result<u8> {
+    let mut byte = [0];
+    reader.read_exact(&mut byte)?;
+    Ok(byte[0])
+}
+
+/// Reads the tag of the next message, or `None` if the stream ended cleanly before it
+fn read_tag<R: Read>(reader: &mut R) -> io::Result<Option<u8>> {
+    let mut byte = [0];
+    loop {
+        match reader.read(&mut byte) {
+            Ok(0) => return Ok(None),
+            Ok(_) => return Ok(Some(byte[0])),
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// The job sent by the coordinator to each worker, before the panels the worker owns
+pub(super) struct Setup {
+    /// The position of the worker in the row-major order of the grid
+    pub rank: usize,
+    /// The shape of the grid of workers, as `(rows, columns)`
+    pub grid: (usize, usize),
+    /// The dimensions `(m, k, n)` of the product of an `m` x `k` and a `k` x `n` matrix
+    pub dimensions: (usize, usize, usize),
+    /// The number of columns of A, and rows of B, in every panel but the last
+    pub panel_width: usize,
+    pub element_type: ElementType,
+    /// The address of every worker, by rank
+    pub workers: Vec<String>,
+}
+
+impl Setup {
+    pub fn write<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(&[SETUP])?;
+        for value in [
+            self.rank,
+            self.grid.0,
+            self.grid.1,
+            self.dimensions.0,
+            self.dimensions.1,
+            self.dimensions.2,
+            self.panel_width,
+        ] {
+            write_usize(writer, value)?;
+        }
+        writer.write_all(&[element_type_code(self.element_type)])?;
+
+        write_usize(writer, self.workers.len())?;
+        for address in &self.workers {
+            write_usize(writer, address.len())?;
+            writer.write_all(address.as_bytes())?;
+        }
+
+        Ok(())
+    }
+
+    pub fn read<R: Read>(reader: &mut R) -> Result<Setup, DistributedError> {
+        match read_tag(reader)? {
+            Some(SETUP) => (),
+            Some(tag) => {
+                return Err(DistributedError::Protocol(format!(
+                    "expected a setup message, got tag {}",
+                    tag
+                )))
+            }
+            None => {
+                return Err(DistributedError::Protocol(
+                    "connection closed before the setup message".to_string(),
+                ))
+            }
+        }
+
+        let mut fields = [0; 7];
+        for field in fields.iter_mut() {
+            *field = read_usize(reader)?;
+        }
+        let [rank, grid_rows, grid_cols, m, k, n, panel_width] = fields;
+
+        let code = read_byte(reader)?;
+        let element_type = element_type_from_code(code).ok_or_else(|| {
+            DistributedError::Protocol(format!("unknown element type code {}", code))
+        })?;
+
+        let count = read_usize(reader)?;
+        if count > MAX_WORKERS {
+            return Err(DistributedError::Protocol(format!(
+                "{} workers are too many",
+                count
+            )));
+        }
+        if grid_rows.checked_mul(grid_cols) != Some(count) || rank >= count {
+            return Err(DistributedError::Protocol(format!(
+                "rank {} of {} workers doesn't fit a {}x{} grid",
+                rank, count, grid_rows, grid_cols
+            )));
+        }
+        check_dimensions((grid_rows, grid_cols), (m, k, n), panel_width)?;
+        let workers = (0..count)
+            .map(|_| {
+                let length = read_usize(reader)?;
+                if length > MAX_ADDRESS_LENGTH {
+                    return Err(DistributedError::Protocol(format!(
+                        "worker address of {} bytes is too long",
+                        length
+                    )));
+                }
+                let mut bytes = vec![0; length];
+                reader.read_exact(&mut bytes)?;
+                String::from_utf8(bytes).map_err(|_| {
+                    DistributedError::Protocol("worker address is not UTF-8".to_string())
+                })
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Setup {
+            rank,
+            grid: (grid_rows, grid_cols),
+            dimensions: (m, k, n),
+            panel_width,
+            element_type,
+            workers,
+        })
+    }
+}
+
+/// Checks that the product of an `m` x `k` and a `k` x `n` matrix has at most
+/// `MAX_ELEMENTS` elements in each matrix, and that cutting it into the blocks of `grid`
+/// and the panels of `panel_width` doesn't overflow
+///
+/// A zero dimension counts as one, since the rows of a matrix without columns are still
+/// allocated.
+fn check_dimensions(
+    grid: (usize, usize),
+    dimensions: (usize, usize, usize),
+    panel_width: usize,
+) -> Result<(), DistributedError> {
+    let (m, k, n) = dimensions;
+    let (rows, inner, cols) = (m.max(1), k.max(1), n.max(1));
+    let elements = [
+        rows.checked_mul(inner),
+        inner.checked_mul(cols),
+        rows.checked_mul(cols),
+    ];
+    if elements
+        .iter()
+        .any(|count| count.is_none_or(|count| count > MAX_ELEMENTS))
+    {
+        return Err(DistributedError::Protocol(format!(
+            "the product of a {}x{} and a {}x{} matrix is too large",
+            m, k, k, n
+        )));
+    }
+    if m.checked_mul(grid.0).is_none()
+        || n.checked_mul(grid.1).is_none()
+        || k.checked_add(panel_width).is_none()
+    {
+        return Err(DistributedError::Protocol(format!(
+            "a {}x{} matrix doesn't fit a {}x{} grid with panels of width {}",
+            m, n, grid.0, grid.1, panel_width
+        )));
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// The operand a panel is taken from
+pub(super) enum Operand {
+    /// A block of rows of a panel of columns of A
+    A,
+    /// A block of columns of a panel of rows of B
+    B,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// The messages following the setup, which is message 1 below
+///
+/// Every message is a one-byte tag followed by its fields. Integers are little-endian
+/// `u64`s, strings are a length followed by UTF-8 bytes, durations are nanoseconds and
+/// blocks use the binary matrix format of `io::binary`, header included:
+///
+/// | tag | message | fields                                                        |
+/// |-----|---------|---------------------------------------------------------------|
+/// | 1   | setup   | rank, grid rows, grid columns, m, k, n, panel width, element type (one byte), number of workers, worker addresses |
+/// | 2   | hello   | rank of the connecting worker                                 |
+/// | 3   | panel   | operand (one byte, 0 for A and 1 for B), panel index, block   |
+/// | 4   | result  | compute time, communication time, block of C                 |
+pub(super) enum Message<T> {
+    /// Sent by a worker on each connection it opens to another worker
+    Hello { rank: usize },
+    /// A panel of an operand, sent by the coordinator to its owner and by the owner to
+    /// the workers that need it
+    Panel {
+        operand: Operand,
+        index: usize,
+        block: Vec<Vec<T>>,
+    },
+    /// The block of C computed by a worker, with the time it spent on each activity
+    Result {
+        compute: Duration,
+        communication: Duration,
+        block: Vec<Vec<T>>,
+    },
+}
+
+impl<T: Element> Message<T> {
+    pub fn write<W: Write>(&self, writer: &mut W) -> Result<(), DistributedError> {
+        match self {
+            Message::Hello { rank } => {
+                writer.write_all(&[HELLO])?;
+                write_usize(writer, *rank)?;
+            }
+            Message::Panel {
+                operand,
+                index,
+                block,
+            } => write_panel(writer, *operand, *index, block)?,
+            Message::Result {
+                compute,
+                communication,
+                block,
+            } => {
+                writer.write_all(&[RESULT])?;
+                write_duration(writer, *compute)?;
+                write_duration(writer, *communication)?;
+                write_binary(writer, block)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Reads the next message, or `None` if the stream ended cleanly before it
+    pub fn read<R: Read>(reader: &mut R) -> Result<Option<Message<T>>, DistributedError> {
+        let message = match read_tag(reader)? {
+            None => return Ok(None),
+            Some(HELLO) => Message::Hello {
+                rank: read_usize(reader)?,
+            },
+            Some(PANEL) => {
+                let operand = match read_byte(reader)? {
+                    0 => Operand::A,
+                    1 => Operand::B,
+                    code => {
+                        return Err(DistributedError::Protocol(format!(
+                            "unknown operand code {}",
+                            code
+                        )))
+                    }
+                };
+                Message::Panel {
+                    operand,
+                    index: read_usize(reader)?,
+                    block: read_binary(&mut *reader)?,
+                }
+            }
+            Some(RESULT) => Message::Result {
+                compute: read_duration(reader)?,
+                communication: read_duration(reader)?,
+                block: read_binary(&mut *reader)?,
+            },
+            Some(tag) => {
+                return Err(DistributedError::Protocol(format!(
+                    "unexpected message tag {}",
+                    tag
+                )))
+            }
+        };
+
+        Ok(Some(message))
+    }
+}
+
+/// Writes a panel message holding `block`, without taking ownership of it
+pub(super) fn write_panel<T: Element, W: Write>(
+    writer: &mut W,
+    operand: Operand,
+    index: usize,
+    block: &[Vec<T>],
+) -> Result<(), DistributedError> {
+    let operand = match operand {
+        Operand::A => 0,
+        Operand::B => 1,
+    };
+    writer.write_all(&[PANEL, operand])?;
+    write_usize(writer, index)?;
+    write_binary(writer, block)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_setup_round_trip() {
+        let setup = Setup {
+            rank: 3,
+            grid: (2, 2),
+            dimensions: (5, 6, 7),
+            panel_width: 2,
+            element_type: ElementType::F32,
+            workers: ["127.0.0.1:1", "127.0.0.1:2", "[::1]:3", "host:4"]
+                .map(String::from)
+                .to_vec(),
+        };
+        let mut bytes = Vec::new();
+        setup.write(&mut bytes).unwrap();
+
+        assert_eq!(bytes[0], SETUP);
+        assert_eq!(&bytes[1..9], &3_u64.to_le_bytes());
+        assert_eq!(Setup::read(&mut &bytes[..]).unwrap(), setup);
+        assert!(Setup::read(&mut &bytes[..bytes.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn test_setup_errors() {
+        let mut setup = Setup {
+            rank: 0,
+            grid: (1 << 32, 1 << 32),
+            dimensions: (1, 1, 1),
+            panel_width: 1,
+            element_type: ElementType::F64,
+            workers: vec!["host:1".to_string()],
+        };
+        let mut bytes = Vec::new();
+        setup.write(&mut bytes).unwrap();
+
+        assert!(matches!(
+            Setup::read(&mut &bytes[..]),
+            Err(DistributedError::Protocol(_))
+        ));
+
+        setup.grid = (MAX_WORKERS + 1, 1);
+        setup.workers = vec![String::new(); MAX_WORKERS + 1];
+        bytes.clear();
+        setup.write(&mut bytes).unwrap();
+
+        match Setup::read(&mut &bytes[..]) {
+            Err(DistributedError::Protocol(message)) => assert!(message.contains("too many")),
+            result => panic!("unexpected {:?}", result),
+        }
+
+        setup.grid = (1, 1);
+        setup.workers = vec!["host:1".to_string()];
+        for (dimensions, panel_width) in [
+            ((1 << 40, 1, 1 << 40), 1),
+            ((1, 1 << 40, 1), 1),
+            ((usize::MAX, 0, 0), 1),
+            ((1, 1, 1), usize::MAX),
+        ] {
+            setup.dimensions = dimensions;
+            setup.panel_width = panel_width;
+            bytes.clear();
+            setup.write(&mut bytes).unwrap();
+
+            assert!(
+                matches!(
+                    Setup::read(&mut &bytes[..]),
+                    Err(DistributedError::Protocol(_))
+                ),
+                "{:?} with panels of width {}",
+                dimensions,
+                panel_width
+            );
+        }
+    }
+
+    #[test]
+    fn test_messages_round_trip() {
+        let messages = vec![
+            Message::Hello { rank: 2 },
+            Message::Panel {
+                operand: Operand::B,
+                index: 4,
+                block: vec![vec![1_i64, -2], vec![3, 4]],
+            },
+            Message::Result {
+                compute: Duration::from_millis(3),
+                communication: Duration::from_nanos(17),
+                block: vec![vec![5], vec![6]],
+            },
+        ];
+        let mut bytes = Vec::new();
+        for message in &messages {
+            message.write(&mut bytes).unwrap();
+        }
+
+        let mut reader = &bytes[..];
+        for message in messages {
+            assert_eq!(Message::read(&mut reader).unwrap(), Some(message));
+        }
+        assert_eq!(Message::<i64>::read(&mut reader).unwrap(), None);
+    }
+
+    #[test]
+    fn test_message_errors() {
+        let mut bytes = Vec::new();
+        Message::Panel {
+            operand: Operand::A,
+            index: 0,
+            block: vec![vec![1_i32]],
+        }
+        .write(&mut bytes)
+        .unwrap();
+
+        assert!(Message::<i64>::read(&mut &bytes[..]).is_err());
+        assert!(Message::<i32>::read(&mut &bytes[..bytes.len() - 1]).is_err());
+        assert!(Message::<i32>::read(&mut &[9_u8][..]).is_err());
+        assert!(Setup::read(&mut &bytes[..]).is_err());
+    }
+}
@@ -0,0 +1,258 @@
+use std::fmt::{Debug, Display};
+use std::io::{self, Read, Write};
+use std::ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub, SubAssign};
+use std::str::FromStr;
+
+use clap::ValueEnum;
+use rand::Rng;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+/// Enum to represent the types implementing `Element`
+pub enum ElementType {
+    I32,
+    I64,
+    F32,
+    F64,
+}
+
+impl ElementType {
+    /// Whether the type is an integer type
+    pub fn is_integer(self) -> bool {
+        matches!(self, ElementType::I32 | ElementType::I64)
+    }
+
+    /// The smallest and largest finite values of the type
+    pub fn bounds(self) -> (f64, f64) {
+        match self {
+            ElementType::I32 => (i32::MIN as f64, i32::MAX as f64),
+            ElementType::I64 => (i64::MIN as f64, i64::MAX as f64),
+            ElementType::F32 => (f32::MIN as f64, f32::MAX as f64),
+            ElementType::F64 => (f64::MIN, f64::MAX),
+        }
+    }
+}
+
+/// Trait for the numeric types the matrix kernels can operate on
+pub trait Element:
+    Copy
+    + Default
+    + PartialEq
+    + PartialOrd
+    + Debug
+    + Display
+    + FromStr
+    + Send
+    + Sync
+    + 'static
+    + Add<Output = Self>
+    + Sub<Output = Self>
+    + Mul<Output = Self>
+    + Neg<Output = Self>
+    + AddAssign
+    + SubAssign
+    + MulAssign
+{
+    /// The `ElementType` corresponding to `Self`
+    const TYPE: ElementType;
+
+    /// The additive identity
+    fn zero() -> Self;
+
+    /// The multiplicative identity
+    fn one() -> Self;
+
+    /// Compares two values, exactly for integers and within `tolerance` for floats
+    ///
+    /// For floats the tolerance is relative to the larger magnitude of the two values,
+    /// and absolute when both values are smaller than one.
+    fn approx_eq(self, other: Self, tolerance: f64) -> bool;
+
+    /// Adds `other`, wrapping around at the bounds of the type for integers
+    fn wrapping_add(self, other: Self) -> Self;
+
+    /// Multiplies by `other`, wrapping around at the bounds of the type for integers
+    fn wrapping_mul(self, other: Self) -> Self;
+
+    /// Converts from `f64`, rounding to the nearest value for integers
+    fn from_f64(value: f64) -> Self;
+
+    /// Converts to `f64`
+    fn to_f64(self) -> f64;
+
+    /// Draws a value uniformly from the closed range `[low, high]`
+    ///
+    /// Integers are drawn from the integers contained in the range.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range is empty.
+    fn sample_uniform<R: Rng + ?Sized>(rng: &mut R, low: f64, high: f64) -> Self;
+
+    /// Writes the value as little-endian bytes
+    fn write_le<W: Write>(self, writer: &mut W) -> io::Result<()>;
+
+    /// Reads a value written by `write_le`
+    fn read_le<R: Read>(reader: &mut R) -> io::Result<Self>;
+}
+
+/// Trait for the floating-point `Element` types, which support division
+pub trait Float: Element + Div<Output = Self> + DivAssign {
+    /// The difference between `1.0` and the next larger representable value
+    const EPSILON: Self;
+
+    /// The absolute value
+    fn abs(self) -> Self;
+}
+
+macro_rules! impl_little_endian {
+    () => {
+        fn write_le<W: Write>(self, writer: &mut W) -> io::Result<()> {
+            writer.write_all(&self.to_le_bytes())
+        }
+
+        fn read_le<R: Read>(reader: &mut R) -> io::Result<Self> {
+            let mut bytes = [0; std::mem::size_of::<Self>()];
+            reader.read_exact(&mut bytes)?;
+            Ok(Self::from_le_bytes(bytes))
+        }
+    };
+}
+
+macro_rules! impl_integer_element {
+    ($($t:ty => $element_type:ident),*) => {
+        $(
+            impl Element for $t {
+                const TYPE: ElementType = ElementType::$element_type;
+
+                fn zero() -> Self {
+                    0
+                }
+
+                fn one() -> Self {
+                    1
+                }
+
+                fn approx_eq(self, other: Self, _tolerance: f64) -> bool {
+                    self == other
+                }
+
+                fn wrapping_add(self, other: Self) -> Self {
+                    <$t>::wrapping_add(self, other)
+                }
+
+                fn wrapping_mul(self, other: Self) -> Self {
+                    <$t>::wrapping_mul(self, other)
+                }
+
+                fn from_f64(value: f64) -> Self {
+                    value.round() as Self
+                }
+
+                fn to_f64(self) -> f64 {
+                    self as f64
+                }
+
+                fn sample_uniform<R: Rng + ?Sized>(rng: &mut R, low: f64, high: f64) -> Self {
+                    rng.gen_range(low.ceil() as Self..=high.floor() as Self)
+                }
+
+                impl_little_endian!();
+            }
+        )*
+    };
+}
+
+macro_rules! impl_float_element {
+    ($($t:ty => $element_type:ident),*) => {
+        $(
+            impl Element for $t {
+                const TYPE: ElementType = ElementType::$element_type;
+
+                fn zero() -> Self {
+                    0.0
+                }
+
+                fn one() -> Self {
+                    1.0
+                }
+
+                fn approx_eq(self, other: Self, tolerance: f64) -> bool {
+                    let (x, y) = (self as f64, other as f64);
+                    let scale = x.abs().max(y.abs()).max(1.0);
+                    (x - y).abs() <= tolerance * scale
+                }
+
+                fn wrapping_add(self, other: Self) -> Self {
+                    self + other
+                }
+
+                fn wrapping_mul(self, other: Self) -> Self {
+                    self * other
+                }
+
+                fn from_f64(value: f64) -> Self {
+                    value as Self
+                }
+
+                fn to_f64(self) -> f64 {
+                    self as f64
+                }
+
+                fn sample_uniform<R: Rng + ?Sized>(rng: &mut R, low: f64, high: f64) -> Self {
+                    rng.gen_range(low as Self..=high as Self)
+                }
+
+                impl_little_endian!();
+            }
+
+            impl Float for $t {
+                const EPSILON: Self = <$t>::EPSILON;
+
+                fn abs(self) -> Self {
+                    <$t>::abs(self)
+                }
+            }
+        )*
+    };
+}
+
+impl_integer_element!(i32 => I32, i64 => I64);
+impl_float_element!(f32 => F32, f64 => F64);
+
+#[cfg(test)]
+mod tests {
+    use super::Element;
+
+    #[test]
+    fn test_integer_approx_eq_is_exact() {
+        assert!(3_i32.approx_eq(3, 0.5));
+        assert!(!3_i32.approx_eq(4, 10.0));
+    }
+
+    #[test]
+    fn test_float_approx_eq_uses_relative_tolerance() {
+        assert!(1.0_f64.approx_eq(1.0 + 1e-12, 1e-9));
+        assert!(1e6_f64.approx_eq(1e6 + 1e-4, 1e-9));
+        assert!(!1.0_f64.approx_eq(1.001, 1e-9));
+    }
+
+    #[test]
+    fn test_integer_from_f64_rounds() {
+        assert_eq!(i32::from_f64(2.6), 3);
+        assert_eq!(i32::from_f64(-2.6), -3);
+        assert_eq!(i64::from_f64(0.4), 0);
+    }
+
+    #[test]
+    fn test_little_endian_round_trip() {
+        let mut bytes = Vec::new();
+        (-2_i32).write_le(&mut bytes).unwrap();
+        1.5_f64.write_le(&mut bytes).unwrap();
+
+        assert_eq!(&bytes[..4], &[0xfe, 0xff, 0xff, 0xff]);
+
+        let mut reader = &bytes[..];
+        assert_eq!(i32::read_le(&mut reader).unwrap(), -2);
+        assert_eq!(f64::read_le(&mut reader).unwrap(), 1.5);
+    }
+}
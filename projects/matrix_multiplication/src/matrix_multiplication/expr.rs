@@ -0,0 +1,371 @@
+use std::borrow::Cow;
+use std::ops::{Add, Mul, Neg, Sub};
+
+use super::{
+    element::Element,
+    gemm::{gemm, Transpose},
+    generate::generate_zero_matrix,
+    matrix::Matrix,
+};
+
+#[derive(Debug, Clone)]
+/// One term of an `Expr`
+enum Term<'a, T: Element> {
+    /// `alpha * A`
+    Scaled {
+        alpha: T,
+        matrix: Cow<'a, Matrix<T>>,
+    },
+    /// `alpha * A * B`, evaluated by one call to `gemm`
+    Product {
+        alpha: T,
+        a: Cow<'a, Matrix<T>>,
+        b: Cow<'a, Matrix<T>>,
+    },
+}
+
+impl<T: Element> Term<'_, T> {
+    fn scale(&mut self, scalar: T) {
+        match self {
+            Term::Scaled { alpha, .. } | Term::Product { alpha, .. } => *alpha *= scalar,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+/// Lazy matrix expression, built with the usual operators and evaluated in one pass
+///
+/// An expression is kept as a sum of terms, each either a scaled matrix or a scaled
+/// product of two matrices. Evaluating it computes all the scaled matrices in a single
+/// pass over the output and then adds every product with `gemm`, so
+/// `a.lazy() * &b * alpha + c.lazy() * beta` allocates nothing but its result and
+/// makes exactly one `gemm` call. A product whose operand is itself a sum is the only
+/// case that evaluates that operand into a temporary matrix.
+///
+/// The operators panic on shape mismatches, like those of `Matrix`.
+pub struct Expr<'a, T: Element> {
+    terms: Vec<Term<'a, T>>,
+    rows: usize,
+    cols: usize,
+}
+
+impl<T: Element> Matrix<T> {
+    /// Starts a lazy expression from this matrix
+    pub fn lazy(&self) -> Expr<'_, T> {
+        Expr::from(self)
+    }
+}
+
+impl<'a, T: Element> From<&'a Matrix<T>> for Expr<'a, T> {
+    fn from(matrix: &'a Matrix<T>) -> Self {
+        Expr {
+            rows: matrix.rows(),
+            cols: matrix.cols(),
+            terms: vec![Term::Scaled {
+                alpha: T::one(),
+                matrix: Cow::Borrowed(matrix),
+            }],
+        }
+    }
+}
+
+impl<'a, T: Element> Expr<'a, T> {
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    /// Evaluates the expression into a new matrix
+    pub fn eval(self) -> Matrix<T> {
+        let mut result = generate_zero_matrix(self.rows, self.cols);
+        self.evaluate(None, &mut result);
+        Matrix::new(result)
+    }
+
+    /// Evaluates the expression into `output`, overwriting its content without reading it
+    ///
+    /// # Panics
+    ///
+    /// Panics if `output` doesn't have the shape of the expression
+    pub fn eval_into(self, output: &mut Matrix<T>) {
+        self.check_output(output);
+        self.evaluate(None, output.rows_mut());
+    }
+
+    /// Computes `target = beta * target + self` in place
+    ///
+    /// With a single product term this is one `gemm` call with `beta`, the usual way to
+    /// update a matrix with `alpha * A * B`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `target` doesn't have the shape of the expression
+    pub fn update(self, beta: T, target: &mut Matrix<T>) {
+        self.check_output(target);
+        let beta = (beta != T::zero()).then_some(beta);
+        self.evaluate(beta, target.rows_mut());
+    }
+
+    fn check_output(&self, output: &Matrix<T>) {
+        assert!(
+            (output.rows(), output.cols()) == (self.rows, self.cols),
+            "Cannot evaluate a {}x{} expression into a {}x{} matrix",
+            self.rows,
+            self.cols,
+            output.rows(),
+            output.cols()
+        );
+    }
+
+    /// Computes `output = beta * output + self`, not reading `output` if `beta` is `None`
+    fn evaluate(self, beta: Option<T>, output: &mut [Vec<T>]) {
+        let (scaled, products): (Vec<_>, Vec<_>) = self
+            .terms
+            .into_iter()
+            .partition(|term| matches!(term, Term::Scaled { .. }));
+
+        // the factor of the previous content of `output` for the next step
+        let mut beta = beta;
+
+        if !scaled.is_empty() {
+            let scaled: Vec<(T, &Matrix<T>)> = scaled
+                .iter()
+                .map(|term| match term {
+                    Term::Scaled { alpha, matrix } => (*alpha, matrix.as_ref()),
+                    Term::Product { .. } => unreachable!(),
+                })
+                .collect();
+
+            for (i, output_row) in output.iter_mut().enumerate() {
+                for (j, value) in output_row.iter_mut().enumerate() {
+                    let mut sum = beta.map_or(T::zero(), |beta| beta * *value);
+                    for &(alpha, matrix) in &scaled {
+                        sum += alpha * matrix[(i, j)];
+                    }
+                    *value = sum;
+                }
+            }
+
+            beta = Some(T::one());
+        }
+
+        for term in products {
+            if let Term::Product { alpha, a, b } = term {
+                let beta_gemm = beta.unwrap_or(T::zero());
+                gemm(
+                    Transpose::No,
+                    Transpose::No,
+                    alpha,
+                    &a,
+                    &b,
+                    beta_gemm,
+                    output,
+                )
+                .unwrap();
+                beta = Some(T::one());
+            }
+        }
+    }
+
+    /// Returns the matrix `alpha * matrix` this expression consists of, evaluating the
+    /// expression first if it is anything else
+    fn into_factor(mut self) -> (T, Cow<'a, Matrix<T>>) {
+        match (self.terms.len(), self.terms.pop()) {
+            (1, Some(Term::Scaled { alpha, matrix })) => (alpha, matrix),
+            (_, term) => {
+                self.terms.extend(term);
+                (T::one(), Cow::Owned(self.eval()))
+            }
+        }
+    }
+
+    fn check_same_shape(&self, other: &Expr<'a, T>, operation: &str) {
+        assert!(
+            self.rows == other.rows && self.cols == other.cols,
+            "Cannot {} a {}x{} matrix and a {}x{} matrix",
+            operation,
+            self.rows,
+            self.cols,
+            other.rows,
+            other.cols
+        );
+    }
+}
+
+impl<'a, T: Element> Add for Expr<'a, T> {
+    type Output = Expr<'a, T>;
+
+    fn add(mut self, other: Expr<'a, T>) -> Expr<'a, T> {
+        self.check_same_shape(&other, "add");
+        self.terms.extend(other.terms);
+        self
+    }
+}
+
+impl<'a, T: Element> Sub for Expr<'a, T> {
+    type Output = Expr<'a, T>;
+
+    fn sub(self, other: Expr<'a, T>) -> Expr<'a, T> {
+        self.check_same_shape(&other, "subtract");
+        self + -other
+    }
+}
+
+impl<T: Element> Neg for Expr<'_, T> {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        self * -T::one()
+    }
+}
+
+impl<T: Element> Mul<T> for Expr<'_, T> {
+    type Output = Self;
+
+    fn mul(mut self, scalar: T) -> Self {
+        for term in &mut self.terms {
+            term.scale(scalar);
+        }
+        self
+    }
+}
+
+impl<'a, T: Element> Mul for Expr<'a, T> {
+    type Output = Expr<'a, T>;
+
+    /// Builds the product node, scaled by the factors of both operands
+    ///
+    /// # Panics
+    ///
+    /// Panics if the number of columns of `self` differs from the number of rows of
+    /// `other`.
+    fn mul(self, other: Expr<'a, T>) -> Expr<'a, T> {
+        assert!(
+            self.cols == other.rows,
+            "Cannot multiply: A has {} columns but B has {} rows",
+            self.cols,
+            other.rows
+        );
+
+        let (rows, cols) = (self.rows, other.cols);
+        let (alpha_a, a) = self.into_factor();
+        let (alpha_b, b) = other.into_factor();
+
+        Expr {
+            terms: vec![Term::Product {
+                alpha: alpha_a * alpha_b,
+                a,
+                b,
+            }],
+            rows,
+            cols,
+        }
+    }
+}
+
+impl<'a, T: Element> Add<&'a Matrix<T>> for Expr<'a, T> {
+    type Output = Expr<'a, T>;
+
+    fn add(self, other: &'a Matrix<T>) -> Expr<'a, T> {
+        self + other.lazy()
+    }
+}
+
+impl<'a, T: Element> Sub<&'a Matrix<T>> for Expr<'a, T> {
+    type Output = Expr<'a, T>;
+
+    fn sub(self, other: &'a Matrix<T>) -> Expr<'a, T> {
+        self - other.lazy()
+    }
+}
+
+impl<'a, T: Element> Mul<&'a Matrix<T>> for Expr<'a, T> {
+    type Output = Expr<'a, T>;
+
+    fn mul(self, other: &'a Matrix<T>) -> Expr<'a, T> {
+        self * other.lazy()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_matrices() -> (Matrix<i64>, Matrix<i64>, Matrix<i64>) {
+        (
+            Matrix::new(vec![vec![1, 2, 3], vec![4, 5, 6]]),
+            Matrix::new(vec![vec![1, -1], vec![0, 2], vec![3, 1]]),
+            Matrix::new(vec![vec![2, 0], vec![-1, 5]]),
+        )
+    }
+
+    #[test]
+    fn test_gemm_expression() {
+        let (a, b, c) = get_matrices();
+        let expression = a.lazy() * &b * 3 + c.lazy() * 2;
+
+        assert!(matches!(
+            expression.terms.as_slice(),
+            [
+                Term::Product { alpha: 3, .. },
+                Term::Scaled { alpha: 2, .. }
+            ]
+        ));
+        assert_eq!(expression.eval(), &(&a * &b) * 3 + &(&c * 2));
+    }
+
+    #[test]
+    fn test_sums_and_scales() {
+        let (a, b, c) = get_matrices();
+        let c_twice = &c * 2;
+        let expression = -(c.lazy() - &c_twice) + (a.lazy() * 2) * (b.lazy() * 5) - &c;
+
+        assert_eq!(expression.terms.len(), 4);
+        assert_eq!(expression.eval(), &(&a * &b) * 10);
+    }
+
+    #[test]
+    fn test_product_of_sums() {
+        let (a, b, c) = get_matrices();
+        let ones = Matrix::new(vec![vec![1, 1]; 3]);
+        let b_plus = b.lazy() + &ones;
+
+        let expected = &(&(&a * &b) + &(&a * &ones)) * &c;
+        assert_eq!((a.lazy() * b_plus * &c).eval(), expected);
+    }
+
+    #[test]
+    fn test_update_in_place() {
+        let (a, b, c) = get_matrices();
+        let mut target = c.clone();
+
+        (a.lazy() * &b * 2).update(3, &mut target);
+        assert_eq!(target, &(&(&a * &b) * 2) + &(&c * 3));
+
+        let mut output = Matrix::new(vec![vec![f64::NAN; 2]; 2]);
+        let identity = Matrix::identity(2);
+        (identity.lazy() * 2.0 + &identity).eval_into(&mut output);
+        assert_eq!(output, &identity * 3.0);
+
+        let mut output = Matrix::new(vec![vec![f64::NAN; 2]; 2]);
+        (identity.lazy() * &identity).update(0.0, &mut output);
+        assert_eq!(output, identity);
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot add")]
+    fn test_shape_mismatch() {
+        let (a, b, _) = get_matrices();
+        let _ = a.lazy() + &b;
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot evaluate")]
+    fn test_output_mismatch() {
+        let (a, _, _) = get_matrices();
+        a.lazy().eval_into(&mut Matrix::zeros(1, 3));
+    }
+}
@@ -0,0 +1,239 @@
+use super::{
+    element::Element,
+    packed::{packed_gemm, BlockSizes, Operand},
+    sanitize::{is_matrix_rectangular, SanitizeError, SanitizeResult},
+    simd::SimdLevel,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Enum to represent how `gemm` reads an operand, like the `TRANS` flags of BLAS
+pub enum Transpose {
+    /// Use the matrix as is
+    No,
+    /// Use the transpose of the matrix, without materialising it
+    Yes,
+}
+
+/// Computes `C = alpha * op(A) * op(B) + beta * C` into the caller's `c`
+///
+/// Like BLAS, `c` is not read when `beta` is zero and the product is not computed when
+/// `alpha` is zero. No matrix is allocated besides the packing buffers of the packed
+/// GEMM, so iterative algorithms can reuse `c` across calls.
+///
+/// # Arguments
+///
+/// * `trans_a` - Whether to use `A` or its transpose
+/// * `trans_b` - Whether to use `B` or its transpose
+/// * `alpha` - The scale of the product
+/// * `a` - The first matrix
+/// * `b` - The second matrix
+/// * `beta` - The scale of the previous content of `c`
+/// * `c` - The output, with as many rows as `op(A)` and as many columns as `op(B)`
+///
+/// # Returns
+///
+/// `Ok`, or the error of the operands or the output having incompatible shapes
+pub fn gemm<T: Element>(
+    trans_a: Transpose,
+    trans_b: Transpose,
+    alpha: T,
+    a: &[Vec<T>],
+    b: &[Vec<T>],
+    beta: T,
+    c: &mut [Vec<T>],
+) -> Result<(), SanitizeError> {
+    gemm_parallel(trans_a, trans_b, alpha, a, b, beta, c, 1)
+}
+
+/// Computes `C = alpha * op(A) * op(B) + beta * C` on `threads` threads
+///
+/// See `gemm`.
+#[allow(clippy::too_many_arguments)]
+pub fn gemm_parallel<T: Element>(
+    trans_a: Transpose,
+    trans_b: Transpose,
+    alpha: T,
+    a: &[Vec<T>],
+    b: &[Vec<T>],
+    beta: T,
+    c: &mut [Vec<T>],
+    threads: usize,
+) -> Result<(), SanitizeError> {
+    for (matrix, name) in [(a, "A"), (b, "B"), (&*c, "C")] {
+        if let SanitizeResult::NotOk(e) = is_matrix_rectangular(matrix, name) {
+            return Err(e);
+        }
+    }
+
+    let a = Operand {
+        matrix: a,
+        transpose: trans_a,
+    };
+    let b = Operand {
+        matrix: b,
+        transpose: trans_b,
+    };
+
+    if a.cols() != b.rows() {
+        return Err(SanitizeError::NotConformable {
+            a_cols: a.cols(),
+            b_rows: b.rows(),
+        });
+    }
+    if (c.len(), c[0].len()) != (a.rows(), b.cols()) {
+        return Err(SanitizeError::OutputSizeMismatch {
+            expected: (a.rows(), b.cols()),
+            actual: (c.len(), c[0].len()),
+        });
+    }
+
+    if beta == T::zero() {
+        for row in c.iter_mut() {
+            row.fill(T::zero());
+        }
+    } else if beta != T::one() {
+        for value in c.iter_mut().flatten() {
+            *value *= beta;
+        }
+    }
+
+    if alpha != T::zero() {
+        packed_gemm(
+            a,
+            b,
+            alpha,
+            c,
+            threads,
+            BlockSizes::default(),
+            SimdLevel::detect(),
+        );
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matrix_multiplication::{matrix::Matrix, matrix_multiplication_sequential_ikj};
+
+    fn get_a() -> Vec<Vec<i32>> {
+        vec![vec![1, 2, 3], vec![4, 5, 6]]
+    }
+
+    fn get_b() -> Vec<Vec<i32>> {
+        vec![vec![1, -1], vec![0, 2], vec![3, 1]]
+    }
+
+    fn transpose(matrix: &[Vec<i32>]) -> Vec<Vec<i32>> {
+        Matrix::new(matrix.to_vec()).transpose().into_inner()
+    }
+
+    #[test]
+    fn test_gemm_alpha_beta() {
+        let (a, b) = (get_a(), get_b());
+        let product = matrix_multiplication_sequential_ikj(&a, &b).unwrap();
+        let mut c = vec![vec![1, 1], vec![1, 1]];
+
+        gemm(Transpose::No, Transpose::No, 2, &a, &b, 3, &mut c).unwrap();
+
+        let expected: Vec<Vec<i32>> = product
+            .iter()
+            .map(|row| row.iter().map(|&value| 2 * value + 3).collect())
+            .collect();
+        assert_eq!(c, expected);
+    }
+
+    #[test]
+    fn test_gemm_transposed_operands() {
+        let (a, b) = (get_a(), get_b());
+        let expected = matrix_multiplication_sequential_ikj(&a, &b).unwrap();
+        let (a_t, b_t) = (transpose(&a), transpose(&b));
+
+        for (trans_a, a) in [(Transpose::No, &a), (Transpose::Yes, &a_t)] {
+            for (trans_b, b) in [(Transpose::No, &b), (Transpose::Yes, &b_t)] {
+                for threads in [1, 2] {
+                    let mut c = vec![vec![0; 2]; 2];
+                    gemm_parallel(trans_a, trans_b, 1, a, b, 0, &mut c, threads).unwrap();
+                    assert_eq!(c, expected, "{:?} {:?}", trans_a, trans_b);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_gemm_beta_zero_ignores_output() {
+        let mut c = vec![vec![f64::NAN]];
+
+        gemm(
+            Transpose::No,
+            Transpose::No,
+            1.0,
+            &[vec![2.0]],
+            &[vec![3.0]],
+            0.0,
+            &mut c,
+        )
+        .unwrap();
+
+        assert_eq!(c, vec![vec![6.0]]);
+    }
+
+    #[test]
+    fn test_gemm_alpha_zero_only_scales() {
+        let mut c = vec![vec![1.0, 2.0]];
+
+        gemm(
+            Transpose::No,
+            Transpose::No,
+            0.0,
+            &[vec![f64::NAN]],
+            &[vec![1.0, 1.0]],
+            2.0,
+            &mut c,
+        )
+        .unwrap();
+
+        assert_eq!(c, vec![vec![2.0, 4.0]]);
+    }
+
+    #[test]
+    fn test_gemm_invalid_shapes() {
+        let (a, b) = (get_a(), get_b());
+
+        assert_eq!(
+            gemm(
+                Transpose::No,
+                Transpose::Yes,
+                1,
+                &a,
+                &b,
+                0,
+                &mut vec![vec![0; 2]; 2]
+            ),
+            Err(SanitizeError::NotConformable {
+                a_cols: 3,
+                b_rows: 2
+            })
+        );
+        assert_eq!(
+            gemm(
+                Transpose::No,
+                Transpose::No,
+                1,
+                &a,
+                &b,
+                0,
+                &mut vec![vec![0; 3]; 2]
+            ),
+            Err(SanitizeError::OutputSizeMismatch {
+                expected: (2, 2),
+                actual: (2, 3)
+            })
+        );
+        assert_eq!(
+            gemm(Transpose::No, Transpose::No, 1, &a, &b, 0, &mut []),
+            Err(SanitizeError::EmptyMatrix("C".to_string()))
+        );
+    }
+}
@@ -1,31 +1,470 @@
+use std::fmt;
+use std::str::FromStr;
 
-/// Generates a square matrix of size `size` filled with zeros or random values between -10 and 10
-/// 
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use rand_distr::Normal;
+
+use super::element::{Element, ElementType};
+
+/// Range of the values drawn by the distributions that don't take one explicitly
+const DEFAULT_LOW: f64 = -10.0;
+const DEFAULT_HIGH: f64 = 10.0;
+
+#[derive(Debug, Clone, PartialEq)]
+/// Enum to represent the distributions matrices can be generated from
+///
+/// The textual form accepted by `from_str` and produced by `fmt` is the name of the
+/// distribution followed by its colon-separated parameters, e.g. `uniform:-10:10`,
+/// `normal:0:1`, `sparse:0.05` or `banded:2`. Parameters may be omitted to use defaults.
+pub enum Distribution {
+    /// Values drawn uniformly from `[low, high]`
+    Uniform { low: f64, high: f64 },
+    /// Values drawn from a normal distribution, rounded for integers
+    Normal { mean: f64, std_dev: f64 },
+    /// Ones on the main diagonal, zeros elsewhere
+    Identity,
+    /// Each cell is non-zero with probability `density`
+    Sparse { density: f64 },
+    /// Non-zero only within `bandwidth` cells of the main diagonal
+    Banded { bandwidth: usize },
+    /// Uniform values mirrored across the main diagonal, square only
+    Symmetric,
+    /// Uniform values with each diagonal cell larger than the rest of its row, square only
+    DiagonallyDominant,
+}
+
+impl Default for Distribution {
+    fn default() -> Self {
+        Distribution::Uniform {
+            low: DEFAULT_LOW,
+            high: DEFAULT_HIGH,
+        }
+    }
+}
+
+impl fmt::Display for Distribution {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Distribution::Uniform { low, high } => write!(f, "uniform:{}:{}", low, high),
+            Distribution::Normal { mean, std_dev } => write!(f, "normal:{}:{}", mean, std_dev),
+            Distribution::Identity => write!(f, "identity"),
+            Distribution::Sparse { density } => write!(f, "sparse:{}", density),
+            Distribution::Banded { bandwidth } => write!(f, "banded:{}", bandwidth),
+            Distribution::Symmetric => write!(f, "symmetric"),
+            Distribution::DiagonallyDominant => write!(f, "diagonally-dominant"),
+        }
+    }
+}
+
+fn parse_parameter<T: FromStr>(
+    parameter: Option<&str>,
+    default: T,
+    name: &str,
+) -> Result<T, String> {
+    match parameter {
+        None => Ok(default),
+        Some(value) => value
+            .parse()
+            .map_err(|_| format!("invalid {} `{}`", name, value)),
+    }
+}
+
+impl FromStr for Distribution {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split(':');
+        let name = parts.next().unwrap_or_default();
+        let parameters: Vec<&str> = parts.collect();
+
+        let max_parameters = match name {
+            "uniform" | "normal" => 2,
+            "sparse" | "banded" => 1,
+            "identity" | "symmetric" | "diagonally-dominant" => 0,
+            _ => return Err(format!("unknown distribution `{}`", name)),
+        };
+        if parameters.len() > max_parameters {
+            return Err(format!("too many parameters in `{}`", s));
+        }
+
+        let first = parameters.first().copied();
+        let second = parameters.get(1).copied();
+
+        let distribution = match name {
+            "uniform" => {
+                let low = parse_parameter(first, DEFAULT_LOW, "low bound")?;
+                let high = parse_parameter(second, DEFAULT_HIGH, "high bound")?;
+                if low.is_nan() || high.is_nan() || low > high {
+                    return Err(format!("empty range [{}, {}]", low, high));
+                }
+                Distribution::Uniform { low, high }
+            }
+            "normal" => {
+                let mean = parse_parameter(first, 0.0, "mean")?;
+                let std_dev: f64 = parse_parameter(second, 1.0, "standard deviation")?;
+                if std_dev.is_nan() || std_dev < 0.0 {
+                    return Err(format!("invalid standard deviation `{}`", std_dev));
+                }
+                Distribution::Normal { mean, std_dev }
+            }
+            "sparse" => {
+                let density = parse_parameter(first, 0.05, "density")?;
+                if !(0.0..=1.0).contains(&density) {
+                    return Err(format!("density `{}` is not in [0, 1]", density));
+                }
+                Distribution::Sparse { density }
+            }
+            "banded" => Distribution::Banded {
+                bandwidth: parse_parameter(first, 1, "bandwidth")?,
+            },
+            "identity" => Distribution::Identity,
+            "symmetric" => Distribution::Symmetric,
+            "diagonally-dominant" => Distribution::DiagonallyDominant,
+            _ => unreachable!(),
+        };
+
+        Ok(distribution)
+    }
+}
+
+impl Distribution {
+    /// Checks that values of type `element` can be drawn from the distribution
+    ///
+    /// A uniform range must lie within the values of the type, and contain an integer for
+    /// integer types.
+    pub fn check_element(&self, element: ElementType) -> Result<(), String> {
+        if let Distribution::Uniform { low, high } = *self {
+            let (min, max) = element.bounds();
+            if low < min || high > max {
+                return Err(format!(
+                    "range [{}, {}] exceeds the values of {:?}",
+                    low, high, element
+                ));
+            }
+            if element.is_integer() && low.ceil() > high.floor() {
+                return Err(format!("range [{}, {}] contains no integer", low, high));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Generates matrices from a `Distribution` with a seedable random number generator
+///
+/// Two generators created with the same seed and distribution produce the same sequence
+/// of matrices.
+pub struct MatrixGenerator {
+    rng: StdRng,
+    distribution: Distribution,
+}
+
+impl MatrixGenerator {
+    /// Create a new `MatrixGenerator`
+    ///
+    /// # Arguments
+    ///
+    /// * `seed` - The seed of the random number generator, or `None` to seed it from the OS
+    /// * `distribution` - The distribution the values are drawn from
+    pub fn new(seed: Option<u64>, distribution: Distribution) -> MatrixGenerator {
+        let rng = match seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
+
+        MatrixGenerator { rng, distribution }
+    }
+
+    /// Generates a matrix of `rows` rows and `cols` columns
+    ///
+    /// # Panics
+    ///
+    /// Panics if the distribution is `Symmetric` or `DiagonallyDominant` and the
+    /// matrix is not square.
+    ///
+    /// # Returns
+    ///
+    /// A `rows` x `cols` matrix as a `Vec<Vec<T>>`
+    pub fn generate<T: Element>(&mut self, rows: usize, cols: usize) -> Vec<Vec<T>> {
+        let rng = &mut self.rng;
+
+        match self.distribution {
+            Distribution::Uniform { low, high } => {
+                fill(rows, cols, |_, _| T::sample_uniform(rng, low, high))
+            }
+            Distribution::Normal { mean, std_dev } => {
+                let normal = Normal::new(mean, std_dev).unwrap();
+                fill(rows, cols, |_, _| T::from_f64(rng.sample(normal)))
+            }
+            Distribution::Identity => {
+                fill(rows, cols, |i, j| if i == j { T::one() } else { T::zero() })
+            }
+            Distribution::Sparse { density } => fill(rows, cols, |_, _| {
+                if rng.gen_bool(density) {
+                    sample_non_zero(rng)
+                } else {
+                    T::zero()
+                }
+            }),
+            Distribution::Banded { bandwidth } => fill(rows, cols, |i, j| {
+                if i.abs_diff(j) <= bandwidth {
+                    T::sample_uniform(rng, DEFAULT_LOW, DEFAULT_HIGH)
+                } else {
+                    T::zero()
+                }
+            }),
+            Distribution::Symmetric => {
+                assert_eq!(rows, cols, "Symmetric matrices must be square");
+
+                let upper = fill(rows, cols, |i, j| {
+                    if i <= j {
+                        T::sample_uniform(rng, DEFAULT_LOW, DEFAULT_HIGH)
+                    } else {
+                        T::zero()
+                    }
+                });
+                fill(
+                    rows,
+                    cols,
+                    |i, j| if i <= j { upper[i][j] } else { upper[j][i] },
+                )
+            }
+            Distribution::DiagonallyDominant => {
+                assert_eq!(rows, cols, "Diagonally dominant matrices must be square");
+
+                let mut matrix = fill(rows, cols, |i, j| {
+                    if i == j {
+                        T::zero()
+                    } else {
+                        T::sample_uniform(rng, DEFAULT_LOW, DEFAULT_HIGH)
+                    }
+                });
+                for (i, row) in matrix.iter_mut().enumerate() {
+                    let off_diagonal: f64 = row.iter().map(|x| x.to_f64().abs()).sum();
+                    let margin = T::sample_uniform(rng, 1.0, DEFAULT_HIGH).to_f64();
+                    row[i] = T::from_f64(off_diagonal + margin);
+                }
+                matrix
+            }
+        }
+    }
+}
+
+/// Draws a uniform value in `[-10, 10]` that is not zero
+fn sample_non_zero<T: Element, R: Rng + ?Sized>(rng: &mut R) -> T {
+    loop {
+        let value = T::sample_uniform(rng, DEFAULT_LOW, DEFAULT_HIGH);
+        if value != T::zero() {
+            return value;
+        }
+    }
+}
+
+fn fill<T: Element>(
+    rows: usize,
+    cols: usize,
+    mut value: impl FnMut(usize, usize) -> T,
+) -> Vec<Vec<T>> {
+    (0..rows)
+        .map(|i| (0..cols).map(|j| value(i, j)).collect())
+        .collect()
+}
+
+/// Generates a matrix of `rows` rows and `cols` columns filled with zeros
+///
 /// # Arguments
-/// 
-/// * `size` - The size of the matrix
-/// * `random_values` - If true, the matrix will be filled with random values between -10 and 10
-/// 
+///
+/// * `rows` - The number of rows
+/// * `cols` - The number of columns
+///
 /// # Returns
-/// 
-/// A square matrix of size `size` as a `Vec<Vec<i32>>`
-/// ```
-pub fn generate_square_matrix_of_size(size: usize, random_values: bool) -> Vec<Vec<i32>> {
-    let mut matrix = Vec::with_capacity(size);
+///
+/// A `rows` x `cols` matrix as a `Vec<Vec<T>>`
+pub fn generate_zero_matrix<T: Element>(rows: usize, cols: usize) -> Vec<Vec<T>> {
+    vec![vec![T::zero(); cols]; rows]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn generate<T: Element>(distribution: Distribution, rows: usize, cols: usize) -> Vec<Vec<T>> {
+        MatrixGenerator::new(Some(42), distribution).generate(rows, cols)
+    }
+
+    #[test]
+    fn test_same_seed_same_matrices() {
+        let mut first = MatrixGenerator::new(Some(7), Distribution::default());
+        let mut second = MatrixGenerator::new(Some(7), Distribution::default());
+
+        for _ in 0..3 {
+            assert_eq!(first.generate::<i32>(8, 5), second.generate::<i32>(8, 5));
+        }
+    }
 
-    for _ in 0..size {
-        let mut row = Vec::with_capacity(size);
-        for _ in 0..size {
-            if random_values {
-                // random between -10 and 10
-                row.push(rand::random::<i32>() % 20 - 10);
-                //row.push(rand::random::<i32>());
-            } else {
-                row.push(0);
+    #[test]
+    fn test_uniform_stays_in_range() {
+        let matrix: Vec<Vec<i32>> = generate(Distribution::default(), 64, 64);
+        let values = matrix.iter().flatten();
+
+        assert!(values.clone().all(|x| (-10..=10).contains(x)));
+        assert_eq!(*values.clone().min().unwrap(), -10);
+        assert_eq!(*values.max().unwrap(), 10);
+    }
+
+    #[test]
+    fn test_rectangular_shape() {
+        let matrix: Vec<Vec<f64>> = generate(Distribution::default(), 3, 7);
+
+        assert_eq!(matrix.len(), 3);
+        assert!(matrix.iter().all(|row| row.len() == 7));
+    }
+
+    #[test]
+    fn test_normal() {
+        let matrix: Vec<Vec<f64>> = generate(
+            Distribution::Normal {
+                mean: 5.0,
+                std_dev: 0.5,
+            },
+            64,
+            64,
+        );
+        let mean = matrix.iter().flatten().sum::<f64>() / (64.0 * 64.0);
+
+        assert!((mean - 5.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn test_identity() {
+        let matrix: Vec<Vec<i32>> = generate(Distribution::Identity, 2, 3);
+
+        assert_eq!(matrix, vec![vec![1, 0, 0], vec![0, 1, 0]]);
+    }
+
+    #[test]
+    fn test_sparse_density() {
+        let matrix: Vec<Vec<i32>> = generate(Distribution::Sparse { density: 0.1 }, 100, 100);
+        let non_zeros = matrix.iter().flatten().filter(|&&x| x != 0).count();
+
+        assert!((800..1200).contains(&non_zeros));
+    }
+
+    #[test]
+    fn test_banded() {
+        let matrix: Vec<Vec<i32>> = generate(Distribution::Banded { bandwidth: 1 }, 6, 6);
+
+        for (i, row) in matrix.iter().enumerate() {
+            for (j, &x) in row.iter().enumerate() {
+                if i.abs_diff(j) > 1 {
+                    assert_eq!(x, 0);
+                }
             }
         }
-        matrix.push(row);
     }
 
-    matrix
+    #[test]
+    fn test_symmetric() {
+        let matrix: Vec<Vec<i32>> = generate(Distribution::Symmetric, 6, 6);
+
+        for (i, row) in matrix.iter().enumerate() {
+            for (j, &x) in row.iter().enumerate() {
+                assert_eq!(x, matrix[j][i]);
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_symmetric_must_be_square() {
+        generate::<i32>(Distribution::Symmetric, 2, 3);
+    }
+
+    #[test]
+    fn test_diagonally_dominant() {
+        let matrix: Vec<Vec<f64>> = generate(Distribution::DiagonallyDominant, 8, 8);
+
+        for (i, row) in matrix.iter().enumerate() {
+            let off_diagonal: f64 = row
+                .iter()
+                .enumerate()
+                .filter(|&(j, _)| j != i)
+                .map(|(_, x)| x.abs())
+                .sum();
+            assert!(row[i].abs() > off_diagonal);
+        }
+    }
+
+    #[test]
+    fn test_parse_distribution() {
+        assert_eq!("uniform".parse(), Ok(Distribution::default()));
+        assert_eq!(
+            "uniform:0:1".parse(),
+            Ok(Distribution::Uniform {
+                low: 0.0,
+                high: 1.0
+            })
+        );
+        assert_eq!(
+            "normal:2".parse(),
+            Ok(Distribution::Normal {
+                mean: 2.0,
+                std_dev: 1.0
+            })
+        );
+        assert_eq!(
+            "sparse:0.5".parse(),
+            Ok(Distribution::Sparse { density: 0.5 })
+        );
+        assert_eq!(
+            "banded:3".parse(),
+            Ok(Distribution::Banded { bandwidth: 3 })
+        );
+        assert_eq!(
+            "diagonally-dominant".parse(),
+            Ok(Distribution::DiagonallyDominant)
+        );
+
+        assert!("uniform:1:0".parse::<Distribution>().is_err());
+        assert!("sparse:2".parse::<Distribution>().is_err());
+        assert!("identity:1".parse::<Distribution>().is_err());
+        assert!("banded:1:2".parse::<Distribution>().is_err());
+        assert!("gaussian".parse::<Distribution>().is_err());
+        assert!("uniform:nan:1".parse::<Distribution>().is_err());
+    }
+
+    #[test]
+    fn test_check_element() {
+        let between_integers: Distribution = "uniform:0.2:0.8".parse().unwrap();
+        assert!(between_integers.check_element(ElementType::I32).is_err());
+        assert!(between_integers.check_element(ElementType::F64).is_ok());
+
+        let one_integer: Distribution = "uniform:0.2:1".parse().unwrap();
+        assert_eq!(one_integer.check_element(ElementType::I64), Ok(()));
+        let matrix: Vec<Vec<i64>> = generate(one_integer, 2, 2);
+        assert_eq!(matrix, vec![vec![1, 1], vec![1, 1]]);
+
+        let too_large: Distribution = "uniform:0:1e10".parse().unwrap();
+        assert!(too_large.check_element(ElementType::I32).is_err());
+        assert!(too_large.check_element(ElementType::I64).is_ok());
+        assert!("uniform:-1e300:0"
+            .parse::<Distribution>()
+            .unwrap()
+            .check_element(ElementType::F32)
+            .is_err());
+        assert_eq!(
+            Distribution::Identity.check_element(ElementType::I32),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_display_round_trips() {
+        let distribution = Distribution::Normal {
+            mean: -1.5,
+            std_dev: 2.0,
+        };
+
+        assert_eq!(distribution.to_string().parse(), Ok(distribution));
+    }
 }
@@ -0,0 +1,303 @@
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+use super::{element::Element, sparse::CsrMatrix};
+
+pub mod binary;
+pub mod matrix_market;
+pub mod text;
+
+#[derive(Debug)]
+/// Enum to represent the errors that can occur while reading or writing a matrix
+pub enum MatrixIoError {
+    /// The underlying reader or writer failed
+    Io(io::Error),
+    /// The content is malformed, with 1-based line and column of the offending token
+    Parse {
+        line: usize,
+        column: usize,
+        message: String,
+    },
+    /// The content is well formed but can't be represented, e.g. a wrong element type
+    Unsupported(String),
+}
+
+impl fmt::Display for MatrixIoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MatrixIoError::Io(e) => write!(f, "{}", e),
+            MatrixIoError::Parse {
+                line,
+                column,
+                message,
+            } => write!(f, "line {}, column {}: {}", line, column, message),
+            MatrixIoError::Unsupported(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl Error for MatrixIoError {}
+
+impl From<io::Error> for MatrixIoError {
+    fn from(e: io::Error) -> Self {
+        MatrixIoError::Io(e)
+    }
+}
+
+impl MatrixIoError {
+    pub(crate) fn parse(line: usize, column: usize, message: impl Into<String>) -> Self {
+        MatrixIoError::Parse {
+            line,
+            column,
+            message: message.into(),
+        }
+    }
+}
+
+/// The most bytes a matrix read into memory may take, its rows included
+///
+/// Files describing a larger matrix are rejected before anything is allocated, rather
+/// than aborting or getting the process killed once memory runs out.
+pub const MAX_MATRIX_BYTES: usize = 1 << 36;
+
+/// Checks that a `rows` x `cols` matrix of `T` read into memory takes at most
+/// `MAX_MATRIX_BYTES`
+pub(crate) fn check_matrix_size<T>(rows: usize, cols: usize) -> Result<(), MatrixIoError> {
+    let size = rows
+        .checked_mul(cols)
+        .and_then(|count| count.checked_mul(size_of::<T>()))
+        .and_then(|size| size.checked_add(rows.checked_mul(size_of::<Vec<T>>())?));
+
+    match size {
+        Some(size) if size <= MAX_MATRIX_BYTES => Ok(()),
+        _ => Err(MatrixIoError::Unsupported(format!(
+            "a {}x{} matrix is too large to read into memory",
+            rows, cols
+        ))),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Enum to represent the supported file formats
+pub enum MatrixFormat {
+    /// One row per line, values separated by commas and/or whitespace
+    Text,
+    /// NIST Matrix Market exchange format, `array` or `coordinate`
+    MatrixMarket,
+    /// Little-endian binary format with a fixed-size header, see `binary`
+    Binary,
+}
+
+impl MatrixFormat {
+    /// Guesses the format from the extension of `path`
+    ///
+    /// `.csv`, `.tsv` and `.txt` are text, `.mtx` is Matrix Market and `.bin` is binary.
+    pub fn from_path(path: &Path) -> Result<MatrixFormat, MatrixIoError> {
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(|extension| extension.to_ascii_lowercase());
+
+        match extension.as_deref() {
+            Some("csv") | Some("tsv") | Some("txt") => Ok(MatrixFormat::Text),
+            Some("mtx") => Ok(MatrixFormat::MatrixMarket),
+            Some("bin") => Ok(MatrixFormat::Binary),
+            _ => Err(MatrixIoError::Unsupported(format!(
+                "cannot tell the format of {} from its extension",
+                path.display()
+            ))),
+        }
+    }
+}
+
+/// Reads a matrix from `path`, choosing the format from its extension
+pub fn read_matrix<T: Element>(path: &Path) -> Result<Vec<Vec<T>>, MatrixIoError> {
+    let format = MatrixFormat::from_path(path)?;
+    let reader = BufReader::new(File::open(path)?);
+
+    match format {
+        MatrixFormat::Text => text::read_text(reader),
+        MatrixFormat::MatrixMarket => matrix_market::read_matrix_market(reader),
+        MatrixFormat::Binary => binary::read_binary(reader),
+    }
+}
+
+/// Writes `matrix` to `path`, choosing the format from its extension
+///
+/// Text files ending in `.csv` are comma-separated, those ending in `.tsv` are
+/// tab-separated and other text files are space-separated.
+pub fn write_matrix<T: Element>(path: &Path, matrix: &[Vec<T>]) -> Result<(), MatrixIoError> {
+    let format = MatrixFormat::from_path(path)?;
+    let mut writer = BufWriter::new(File::create(path)?);
+
+    match format {
+        MatrixFormat::Text => {
+            let has_extension = |name: &str| {
+                path.extension()
+                    .is_some_and(|e| e.eq_ignore_ascii_case(name))
+            };
+            let separator = if has_extension("csv") {
+                ','
+            } else if has_extension("tsv") {
+                '\t'
+            } else {
+                ' '
+            };
+            text::write_text(&mut writer, matrix, separator)?
+        }
+        MatrixFormat::MatrixMarket => matrix_market::write_matrix_market(&mut writer, matrix)?,
+        MatrixFormat::Binary => binary::write_binary(&mut writer, matrix)?,
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+/// Reads a sparse matrix from `path`, choosing the format from its extension
+///
+/// Matrix Market files are read entry by entry, other formats are read densely and
+/// then compressed.
+pub fn read_sparse_matrix<T: Element>(path: &Path) -> Result<CsrMatrix<T>, MatrixIoError> {
+    match MatrixFormat::from_path(path)? {
+        MatrixFormat::MatrixMarket => {
+            matrix_market::read_matrix_market_sparse(BufReader::new(File::open(path)?))
+        }
+        MatrixFormat::Text | MatrixFormat::Binary => {
+            Ok(CsrMatrix::from_dense(&read_matrix::<T>(path)?))
+        }
+    }
+}
+
+/// Writes a sparse matrix to `path`, choosing the format from its extension
+///
+/// Matrix Market files use the `coordinate` format, other formats are written densely.
+pub fn write_sparse_matrix<T: Element>(
+    path: &Path,
+    matrix: &CsrMatrix<T>,
+) -> Result<(), MatrixIoError> {
+    match MatrixFormat::from_path(path)? {
+        MatrixFormat::MatrixMarket => {
+            let mut writer = BufWriter::new(File::create(path)?);
+            matrix_market::write_matrix_market_sparse(&mut writer, matrix)?;
+            writer.flush()?;
+            Ok(())
+        }
+        MatrixFormat::Text | MatrixFormat::Binary => write_matrix(path, &matrix.to_dense()),
+    }
+}
+
+/// Splits `line` into tokens separated by commas and/or whitespace
+///
+/// # Returns
+///
+/// An iterator over the tokens and their 1-based column, counted in characters
+pub(crate) fn tokens(line: &str) -> impl Iterator<Item = (usize, &str)> {
+    let is_separator = |c: char| c == ',' || c.is_whitespace();
+    let mut rest = line.char_indices().peekable();
+    let mut column = 0;
+
+    std::iter::from_fn(move || {
+        while let Some(&(_, c)) = rest.peek() {
+            if !is_separator(c) {
+                break;
+            }
+            rest.next();
+            column += 1;
+        }
+
+        let (start, _) = *rest.peek()?;
+        let token_column = column + 1;
+        let mut end = line.len();
+        while let Some(&(index, c)) = rest.peek() {
+            if is_separator(c) {
+                end = index;
+                break;
+            }
+            rest.next();
+            column += 1;
+        }
+
+        Some((token_column, &line[start..end]))
+    })
+}
+
+/// Parses `token`, reporting failures at `line` and `column`
+pub(crate) fn parse_token<T: FromStr>(
+    token: &str,
+    line: usize,
+    column: usize,
+    what: &str,
+) -> Result<T, MatrixIoError> {
+    token
+        .parse()
+        .map_err(|_| MatrixIoError::parse(line, column, format!("invalid {} `{}`", what, token)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tokens() {
+        let tokens: Vec<(usize, &str)> = tokens("1, 2,,3\t 4  ").collect();
+
+        assert_eq!(tokens, vec![(1, "1"), (4, "2"), (7, "3"), (10, "4")]);
+    }
+
+    #[test]
+    fn test_parse_token_reports_position() {
+        let error = parse_token::<i32>("x1", 3, 5, "value").unwrap_err();
+
+        assert_eq!(error.to_string(), "line 3, column 5: invalid value `x1`");
+    }
+
+    #[test]
+    fn test_check_matrix_size() {
+        let row = size_of::<Vec<u8>>();
+
+        assert!(check_matrix_size::<u8>(1, MAX_MATRIX_BYTES - row).is_ok());
+        assert!(check_matrix_size::<u8>(1, MAX_MATRIX_BYTES - row + 1).is_err());
+        assert!(check_matrix_size::<f64>(1 << 16, 1 << 16).is_ok());
+        assert!(check_matrix_size::<f64>(1 << 17, 1 << 16).is_err());
+        assert!(check_matrix_size::<i32>(MAX_MATRIX_BYTES / row + 1, 0).is_err());
+        assert!(check_matrix_size::<i32>(usize::MAX, usize::MAX).is_err());
+    }
+
+    #[test]
+    fn test_format_from_path() {
+        assert_eq!(
+            MatrixFormat::from_path(Path::new("a.CSV")).unwrap(),
+            MatrixFormat::Text
+        );
+        assert_eq!(
+            MatrixFormat::from_path(Path::new("dir/a.mtx")).unwrap(),
+            MatrixFormat::MatrixMarket
+        );
+        assert_eq!(
+            MatrixFormat::from_path(Path::new("a.bin")).unwrap(),
+            MatrixFormat::Binary
+        );
+        assert!(MatrixFormat::from_path(Path::new("a")).is_err());
+    }
+
+    #[test]
+    fn test_write_and_read_every_format() {
+        let matrix = vec![vec![1.5, -2.0, 0.0], vec![0.0, 4.25, 6.0]];
+        let directory = std::env::temp_dir().join(format!("matrix_io_{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+
+        for name in ["m.csv", "m.tsv", "m.txt", "m.mtx", "m.bin"] {
+            let path = directory.join(name);
+            write_matrix(&path, &matrix).unwrap();
+            assert_eq!(read_matrix::<f64>(&path).unwrap(), matrix, "{}", name);
+        }
+        let tsv = std::fs::read_to_string(directory.join("m.tsv")).unwrap();
+        assert_eq!(tsv.lines().next(), Some("1.5\t-2\t0"));
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,241 @@
+use std::io::{Read, Write};
+
+use super::{check_matrix_size, MatrixIoError};
+use crate::matrix_multiplication::element::{Element, ElementType};
+
+/// The first four bytes of every binary matrix file
+pub const MAGIC: &[u8; 4] = b"MTRX";
+/// The version of the format written by `write_binary`
+pub const VERSION: u8 = 1;
+/// The size of the header in bytes
+pub const HEADER_SIZE: usize = 24;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// The header of a binary matrix file
+///
+/// A file is a 24-byte header followed by the elements in row-major order, all
+/// little-endian:
+///
+/// | offset | size | content                                  |
+/// |--------|------|------------------------------------------|
+/// | 0      | 4    | magic `MTRX`                             |
+/// | 4      | 1    | format version, currently 1              |
+/// | 5      | 1    | element type: 0 `i32`, 1 `i64`, 2 `f32`, 3 `f64` |
+/// | 6      | 2    | reserved, zero                           |
+/// | 8      | 8    | number of rows as `u64`                  |
+/// | 16     | 8    | number of columns as `u64`               |
+pub struct BinaryHeader {
+    pub element_type: ElementType,
+    pub rows: usize,
+    pub cols: usize,
+}
+
+pub(crate) fn element_type_code(element_type: ElementType) -> u8 {
+    match element_type {
+        ElementType::I32 => 0,
+        ElementType::I64 => 1,
+        ElementType::F32 => 2,
+        ElementType::F64 => 3,
+    }
+}
+
+pub(crate) fn element_type_from_code(code: u8) -> Option<ElementType> {
+    match code {
+        0 => Some(ElementType::I32),
+        1 => Some(ElementType::I64),
+        2 => Some(ElementType::F32),
+        3 => Some(ElementType::F64),
+        _ => None,
+    }
+}
+
+impl BinaryHeader {
+    /// Reads and validates a header
+    pub fn read<R: Read>(reader: &mut R) -> Result<BinaryHeader, MatrixIoError> {
+        let mut bytes = [0; HEADER_SIZE];
+        reader.read_exact(&mut bytes)?;
+
+        if &bytes[0..4] != MAGIC {
+            return Err(MatrixIoError::Unsupported(
+                "not a binary matrix file: bad magic".to_string(),
+            ));
+        }
+        if bytes[4] != VERSION {
+            return Err(MatrixIoError::Unsupported(format!(
+                "unsupported binary matrix version {}",
+                bytes[4]
+            )));
+        }
+        let element_type = element_type_from_code(bytes[5]).ok_or_else(|| {
+            MatrixIoError::Unsupported(format!("unknown element type code {}", bytes[5]))
+        })?;
+
+        let dimension = |offset: usize| {
+            let value = u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap());
+            usize::try_from(value).map_err(|_| {
+                MatrixIoError::Unsupported(format!("dimension {} is too large", value))
+            })
+        };
+
+        let (rows, cols) = (dimension(8)?, dimension(16)?);
+        if (rows == 0) != (cols == 0) {
+            return Err(MatrixIoError::Unsupported(format!(
+                "a {}x{} matrix has a dimension of zero but not the other",
+                rows, cols
+            )));
+        }
+
+        Ok(BinaryHeader {
+            element_type,
+            rows,
+            cols,
+        })
+    }
+
+    /// Writes the header
+    pub fn write<W: Write>(&self, writer: &mut W) -> Result<(), MatrixIoError> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&[VERSION, element_type_code(self.element_type), 0, 0])?;
+        writer.write_all(&(self.rows as u64).to_le_bytes())?;
+        writer.write_all(&(self.cols as u64).to_le_bytes())?;
+
+        Ok(())
+    }
+}
+
+/// Reads a binary matrix whose elements have type `T`
+///
+/// # Returns
+///
+/// The matrix, or `MatrixIoError::Unsupported` if the file holds another element type or
+/// a matrix larger than `MAX_MATRIX_BYTES`
+pub fn read_binary<T: Element, R: Read>(mut reader: R) -> Result<Vec<Vec<T>>, MatrixIoError> {
+    let header = BinaryHeader::read(&mut reader)?;
+
+    if header.element_type != T::TYPE {
+        return Err(MatrixIoError::Unsupported(format!(
+            "file holds {:?} elements, expected {:?}",
+            header.element_type,
+            T::TYPE
+        )));
+    }
+    check_matrix_size::<T>(header.rows, header.cols)?;
+
+    (0..header.rows)
+        .map(|_| {
+            (0..header.cols)
+                .map(|_| T::read_le(&mut reader).map_err(MatrixIoError::from))
+                .collect()
+        })
+        .collect()
+}
+
+/// Writes `matrix` in the binary format
+///
+/// A matrix without columns is written as a 0x0 matrix, since the header can't describe
+/// rows without elements.
+///
+/// # Returns
+///
+/// Nothing, or `MatrixIoError::Unsupported` if the rows of `matrix` have different
+/// lengths
+pub fn write_binary<T: Element, W: Write>(
+    writer: &mut W,
+    matrix: &[Vec<T>],
+) -> Result<(), MatrixIoError> {
+    let cols = matrix.first().map_or(0, |row| row.len());
+    if let Some((i, row)) = matrix.iter().enumerate().find(|(_, row)| row.len() != cols) {
+        return Err(MatrixIoError::Unsupported(format!(
+            "row {} has {} values, expected {}",
+            i + 1,
+            row.len(),
+            cols
+        )));
+    }
+
+    let header = BinaryHeader {
+        element_type: T::TYPE,
+        rows: if cols == 0 { 0 } else { matrix.len() },
+        cols,
+    };
+    header.write(writer)?;
+
+    for row in matrix {
+        for &value in row {
+            value.write_le(writer)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_binary_layout() {
+        let mut output = Vec::new();
+        write_binary(&mut output, &[vec![1_i32, 2]]).unwrap();
+
+        assert_eq!(output.len(), HEADER_SIZE + 8);
+        assert_eq!(&output[0..8], b"MTRX\x01\x00\x00\x00");
+        assert_eq!(&output[8..16], &1_u64.to_le_bytes());
+        assert_eq!(&output[16..24], &2_u64.to_le_bytes());
+        assert_eq!(&output[24..], &[1, 0, 0, 0, 2, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_binary_round_trip() {
+        let matrix = vec![vec![1.5_f32, -2.0], vec![3.0, 4.0], vec![0.0, 1e-3]];
+        let mut output = Vec::new();
+        write_binary(&mut output, &matrix).unwrap();
+
+        assert_eq!(read_binary::<f32, _>(&output[..]).unwrap(), matrix);
+    }
+
+    #[test]
+    fn test_binary_errors() {
+        let mut output = Vec::new();
+        write_binary(&mut output, &[vec![1_i64]]).unwrap();
+
+        assert!(read_binary::<i32, _>(&output[..]).is_err());
+        assert!(read_binary::<i64, _>(&output[..output.len() - 1]).is_err());
+        assert!(read_binary::<i64, _>(&b"NOPE"[..]).is_err());
+
+        let header = |rows: u64, cols: u64| {
+            let mut bytes = b"MTRX\x01\x01\x00\x00".to_vec();
+            bytes.extend_from_slice(&rows.to_le_bytes());
+            bytes.extend_from_slice(&cols.to_le_bytes());
+            bytes
+        };
+        for (rows, cols) in [(1 << 32, 1 << 32), (1 << 40, 1), (3, 0), (0, 3)] {
+            assert!(
+                matches!(
+                    read_binary::<i64, _>(&header(rows, cols)[..]),
+                    Err(MatrixIoError::Unsupported(_))
+                ),
+                "{}x{}",
+                rows,
+                cols
+            );
+        }
+        assert_eq!(
+            read_binary::<i64, _>(&header(0, 0)[..]).unwrap(),
+            Vec::<Vec<i64>>::new()
+        );
+
+        let ragged = [vec![1_i32, 2], vec![3]];
+        assert!(matches!(
+            write_binary(&mut Vec::new(), &ragged),
+            Err(MatrixIoError::Unsupported(_))
+        ));
+        let mut output = Vec::new();
+        write_binary(&mut output, &[Vec::<i32>::new(), Vec::new()]).unwrap();
+        assert_eq!(output.len(), HEADER_SIZE);
+        assert_eq!(
+            read_binary::<i32, _>(&output[..]).unwrap(),
+            Vec::<Vec<i32>>::new()
+        );
+    }
+}
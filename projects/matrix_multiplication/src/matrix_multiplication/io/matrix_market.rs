@@ -0,0 +1,605 @@
+use std::io::{BufRead, Write};
+
+use super::{check_matrix_size, parse_token, tokens, MatrixIoError};
+use crate::matrix_multiplication::{
+    element::Element, generate::generate_zero_matrix, sparse::CsrMatrix,
+};
+
+/// The most entries preallocated before reading them, since the size line can't be trusted
+const MAX_PREALLOCATED_ENTRIES: usize = 1 << 16;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Layout {
+    Array,
+    Coordinate,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Field {
+    Integer,
+    Real,
+    Pattern,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Symmetry {
+    General,
+    Symmetric,
+    SkewSymmetric,
+}
+
+#[derive(Debug)]
+struct Header {
+    layout: Layout,
+    field: Field,
+    symmetry: Symmetry,
+}
+
+/// The entries of a Matrix Market file, as stored in the file
+enum Entries<T> {
+    /// Every stored value in column-major order
+    Array(Vec<T>),
+    /// The stored `(row, column, value)` triplets, 0-based
+    Coordinate(Vec<(usize, usize, T)>),
+}
+
+/// Lines of a Matrix Market file with their 1-based number, skipping comments and blanks
+struct Lines<R> {
+    lines: std::io::Lines<R>,
+    line_number: usize,
+}
+
+impl<R: BufRead> Lines<R> {
+    fn next_line(&mut self) -> Result<Option<(usize, String)>, MatrixIoError> {
+        for line in self.lines.by_ref() {
+            let line = line?;
+            self.line_number += 1;
+
+            if !line.trim().is_empty() && !line.starts_with('%') {
+                return Ok(Some((self.line_number, line)));
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+fn parse_header(line: &str) -> Result<Header, MatrixIoError> {
+    let words: Vec<(usize, &str)> = tokens(line).collect();
+
+    match words.first() {
+        Some((_, banner)) if banner.eq_ignore_ascii_case("%%MatrixMarket") => (),
+        _ => {
+            return Err(MatrixIoError::parse(
+                1,
+                1,
+                "missing `%%MatrixMarket` header",
+            ))
+        }
+    }
+
+    if words.len() != 5 {
+        return Err(MatrixIoError::parse(
+            1,
+            1,
+            "expected `%%MatrixMarket matrix <format> <field> <symmetry>`",
+        ));
+    }
+
+    let word = |index: usize| (words[index].0, words[index].1.to_ascii_lowercase());
+    let unsupported = |(column, value): (usize, String), what: &str| {
+        MatrixIoError::parse(1, column, format!("unsupported {} `{}`", what, value))
+    };
+
+    if word(1).1 != "matrix" {
+        return Err(unsupported(word(1), "object"));
+    }
+
+    let layout = match word(2).1.as_str() {
+        "array" => Layout::Array,
+        "coordinate" => Layout::Coordinate,
+        _ => return Err(unsupported(word(2), "format")),
+    };
+
+    let field = match word(3).1.as_str() {
+        "integer" => Field::Integer,
+        "real" | "double" => Field::Real,
+        "pattern" if layout == Layout::Coordinate => Field::Pattern,
+        _ => return Err(unsupported(word(3), "field")),
+    };
+
+    let symmetry = match word(4).1.as_str() {
+        "general" => Symmetry::General,
+        "symmetric" => Symmetry::Symmetric,
+        "skew-symmetric" => Symmetry::SkewSymmetric,
+        _ => return Err(unsupported(word(4), "symmetry")),
+    };
+
+    Ok(Header {
+        layout,
+        field,
+        symmetry,
+    })
+}
+
+/// Splits `line` into exactly `expected.len()` tokens, named by `expected` in errors
+fn expect_words<'a>(
+    line_number: usize,
+    line: &'a str,
+    expected: &[&str],
+) -> Result<Vec<(usize, &'a str)>, MatrixIoError> {
+    let words: Vec<(usize, &str)> = tokens(line).collect();
+
+    if words.len() != expected.len() {
+        let column = words.get(expected.len()).map_or(1, |&(column, _)| column);
+        return Err(MatrixIoError::parse(
+            line_number,
+            column,
+            format!("expected {}", expected.join(", ")),
+        ));
+    }
+
+    Ok(words)
+}
+
+/// Parses the tokens of `line` as exactly `expected.len()` values named by `expected`
+fn parse_line<T: std::str::FromStr>(
+    line_number: usize,
+    line: &str,
+    expected: &[&str],
+) -> Result<Vec<T>, MatrixIoError> {
+    expect_words(line_number, line, expected)?
+        .iter()
+        .zip(expected)
+        .map(|(&(column, word), what)| parse_token(word, line_number, column, what))
+        .collect()
+}
+
+/// Reads the header, size line and entries of a Matrix Market file
+///
+/// # Returns
+///
+/// The header, the number of rows and columns, and the entries as stored
+fn read_entries<T: Element, R: BufRead>(
+    reader: R,
+) -> Result<(Header, usize, usize, Entries<T>), MatrixIoError> {
+    let mut lines = reader.lines();
+    let header = match lines.next() {
+        Some(line) => parse_header(&line?)?,
+        None => return Err(MatrixIoError::parse(1, 1, "empty file")),
+    };
+    let mut lines = Lines {
+        lines,
+        line_number: 1,
+    };
+
+    let (size_line_number, size_line) = lines
+        .next_line()?
+        .ok_or_else(|| MatrixIoError::parse(2, 1, "missing size line"))?;
+
+    let (rows, cols, expected_entries) = match header.layout {
+        Layout::Array => {
+            let size: Vec<usize> = parse_line(size_line_number, &size_line, &["rows", "columns"])?;
+            let stored = match header.symmetry {
+                Symmetry::General => size[0].checked_mul(size[1]),
+                Symmetry::Symmetric => size[0]
+                    .checked_mul(size[0].saturating_add(1))
+                    .map(|twice| twice / 2),
+                Symmetry::SkewSymmetric => size[0]
+                    .checked_mul(size[0].saturating_sub(1))
+                    .map(|twice| twice / 2),
+            };
+            let stored = stored
+                .ok_or_else(|| MatrixIoError::parse(size_line_number, 1, "matrix too large"))?;
+            (size[0], size[1], stored)
+        }
+        Layout::Coordinate => {
+            let size: Vec<usize> = parse_line(
+                size_line_number,
+                &size_line,
+                &["rows", "columns", "entries"],
+            )?;
+            (size[0], size[1], size[2])
+        }
+    };
+
+    if header.symmetry != Symmetry::General && rows != cols {
+        return Err(MatrixIoError::parse(
+            size_line_number,
+            1,
+            "symmetric matrices must be square",
+        ));
+    }
+
+    let mut last_line_number = size_line_number;
+    let entries = match header.layout {
+        Layout::Array => {
+            let mut values = Vec::with_capacity(expected_entries.min(MAX_PREALLOCATED_ENTRIES));
+            while let Some((line_number, line)) = lines.next_line()? {
+                for (column, word) in tokens(&line) {
+                    if values.len() == expected_entries {
+                        return Err(MatrixIoError::parse(
+                            line_number,
+                            column,
+                            format!("expected {} values", expected_entries),
+                        ));
+                    }
+                    values.push(parse_token(word, line_number, column, "value")?);
+                }
+                last_line_number = line_number;
+            }
+            if values.len() != expected_entries {
+                return Err(MatrixIoError::parse(
+                    last_line_number + 1,
+                    1,
+                    format!(
+                        "expected {} values, found {}",
+                        expected_entries,
+                        values.len()
+                    ),
+                ));
+            }
+            Entries::Array(values)
+        }
+        Layout::Coordinate => {
+            let mut triplets = Vec::with_capacity(expected_entries.min(MAX_PREALLOCATED_ENTRIES));
+            let names: &[&str] = match header.field {
+                Field::Pattern => &["row", "column"],
+                Field::Integer | Field::Real => &["row", "column", "value"],
+            };
+            while let Some((line_number, line)) = lines.next_line()? {
+                if triplets.len() == expected_entries {
+                    return Err(MatrixIoError::parse(
+                        line_number,
+                        1,
+                        format!("expected {} entries", expected_entries),
+                    ));
+                }
+
+                let words = expect_words(line_number, &line, names)?;
+                let row: usize = parse_token(words[0].1, line_number, words[0].0, "row")?;
+                let col: usize = parse_token(words[1].1, line_number, words[1].0, "column")?;
+                if row == 0 || row > rows {
+                    return Err(MatrixIoError::parse(
+                        line_number,
+                        words[0].0,
+                        format!("row {} out of range 1..={}", row, rows),
+                    ));
+                }
+                if col == 0 || col > cols {
+                    return Err(MatrixIoError::parse(
+                        line_number,
+                        words[1].0,
+                        format!("column {} out of range 1..={}", col, cols),
+                    ));
+                }
+
+                let value = match header.field {
+                    Field::Pattern => T::one(),
+                    Field::Integer | Field::Real => {
+                        parse_token(words[2].1, line_number, words[2].0, "value")?
+                    }
+                };
+
+                triplets.push((row - 1, col - 1, value));
+                last_line_number = line_number;
+            }
+            if triplets.len() != expected_entries {
+                return Err(MatrixIoError::parse(
+                    last_line_number + 1,
+                    1,
+                    format!(
+                        "expected {} entries, found {}",
+                        expected_entries,
+                        triplets.len()
+                    ),
+                ));
+            }
+            Entries::Coordinate(triplets)
+        }
+    };
+
+    Ok((header, rows, cols, entries))
+}
+
+/// Calls `emit` with every `(row, column, value)` cell described by `entries`
+///
+/// Cells mirrored by symmetry are emitted too, so each cell is emitted once for every
+/// entry describing it, which is more than once only for duplicate coordinate entries.
+fn for_each_cell<T: Element>(
+    header: &Header,
+    rows: usize,
+    cols: usize,
+    entries: Entries<T>,
+    mut emit: impl FnMut(usize, usize, T),
+) {
+    let mut mirror = |i: usize, j: usize, value: T| {
+        emit(i, j, value);
+        if i != j {
+            match header.symmetry {
+                Symmetry::General => (),
+                Symmetry::Symmetric => emit(j, i, value),
+                Symmetry::SkewSymmetric => emit(j, i, -value),
+            }
+        }
+    };
+
+    match entries {
+        Entries::Array(values) => {
+            let mut values = values.into_iter();
+            for j in 0..cols {
+                let first_row = match header.symmetry {
+                    Symmetry::General => 0,
+                    Symmetry::Symmetric => j,
+                    Symmetry::SkewSymmetric => j + 1,
+                };
+                for i in first_row..rows {
+                    // `read_entries` checked that exactly this many values are stored
+                    mirror(i, j, values.next().unwrap());
+                }
+            }
+        }
+        Entries::Coordinate(triplets) => {
+            for (i, j, value) in triplets {
+                mirror(i, j, value);
+            }
+        }
+    }
+}
+
+/// Reads a Matrix Market file into a dense matrix
+///
+/// Both the `array` and `coordinate` formats are supported, with `integer`, `real`
+/// and `pattern` fields and `general`, `symmetric` and `skew-symmetric` symmetry.
+/// Entries missing from coordinate files are zero, pattern entries are one, and
+/// duplicate coordinate entries are summed, as by `read_matrix_market_sparse`.
+pub fn read_matrix_market<T: Element, R: BufRead>(reader: R) -> Result<Vec<Vec<T>>, MatrixIoError> {
+    let (header, rows, cols, entries) = read_entries(reader)?;
+    check_matrix_size::<T>(rows, cols)?;
+    let mut matrix = generate_zero_matrix(rows, cols);
+
+    for_each_cell(&header, rows, cols, entries, |i, j, value| {
+        matrix[i][j] += value
+    });
+
+    Ok(matrix)
+}
+
+/// Reads a Matrix Market file as a sparse matrix
+///
+/// Accepts the same files as `read_matrix_market`, without ever building the dense
+/// matrix. Zero values are not stored, and duplicate coordinate entries are summed.
+pub fn read_matrix_market_sparse<T: Element, R: BufRead>(
+    reader: R,
+) -> Result<CsrMatrix<T>, MatrixIoError> {
+    let (header, rows, cols, entries) = read_entries(reader)?;
+    // the row offsets are the only part whose size doesn't depend on the entries
+    check_matrix_size::<usize>(1, rows.saturating_add(1))?;
+    let mut triplets = Vec::new();
+
+    for_each_cell(&header, rows, cols, entries, |i, j, value| {
+        if value != T::zero() {
+            triplets.push((i, j, value));
+        }
+    });
+
+    Ok(CsrMatrix::from_triplets(rows, cols, triplets))
+}
+
+/// Writes `matrix` in the Matrix Market `array general` format
+///
+/// The field is `integer` for integer elements and `real` otherwise.
+pub fn write_matrix_market<T: Element, W: Write>(
+    writer: &mut W,
+    matrix: &[Vec<T>],
+) -> Result<(), MatrixIoError> {
+    let field = if T::TYPE.is_integer() {
+        "integer"
+    } else {
+        "real"
+    };
+    let cols = matrix.first().map_or(0, |row| row.len());
+
+    writeln!(writer, "%%MatrixMarket matrix array {} general", field)?;
+    writeln!(writer, "{} {}", matrix.len(), cols)?;
+    for j in 0..cols {
+        for row in matrix {
+            writeln!(writer, "{}", row[j])?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Writes `matrix` in the Matrix Market `coordinate general` format
+///
+/// The field is `integer` for integer elements and `real` otherwise.
+pub fn write_matrix_market_sparse<T: Element, W: Write>(
+    writer: &mut W,
+    matrix: &CsrMatrix<T>,
+) -> Result<(), MatrixIoError> {
+    let field = if T::TYPE.is_integer() {
+        "integer"
+    } else {
+        "real"
+    };
+
+    writeln!(writer, "%%MatrixMarket matrix coordinate {} general", field)?;
+    writeln!(
+        writer,
+        "{} {} {}",
+        matrix.rows(),
+        matrix.cols(),
+        matrix.nnz()
+    )?;
+    for (i, j, value) in matrix.triplets() {
+        writeln!(writer, "{} {} {}", i + 1, j + 1, value)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matrix_multiplication::io::MAX_MATRIX_BYTES;
+
+    fn read(input: &str) -> Result<Vec<Vec<i32>>, MatrixIoError> {
+        read_matrix_market(input.as_bytes())
+    }
+
+    #[test]
+    fn test_read_array() {
+        let input =
+            "%%MatrixMarket matrix array integer general\n% comment\n2 3\n1\n4\n2\n5\n3\n6\n";
+
+        assert_eq!(read(input).unwrap(), vec![vec![1, 2, 3], vec![4, 5, 6]]);
+    }
+
+    #[test]
+    fn test_read_array_symmetric() {
+        let input = "%%MatrixMarket matrix array integer symmetric\n2 2\n1\n2\n3\n";
+
+        assert_eq!(read(input).unwrap(), vec![vec![1, 2], vec![2, 3]]);
+    }
+
+    #[test]
+    fn test_read_coordinate() {
+        let input = "%%MatrixMarket matrix coordinate integer general\n2 3 2\n1 3 7\n2 1 -1\n";
+
+        assert_eq!(read(input).unwrap(), vec![vec![0, 0, 7], vec![-1, 0, 0]]);
+    }
+
+    #[test]
+    fn test_read_coordinate_duplicates() {
+        let input =
+            "%%MatrixMarket matrix coordinate integer general\n2 2 3\n1 1 5\n2 2 1\n1 1 7\n";
+
+        assert_eq!(read(input).unwrap(), vec![vec![12, 0], vec![0, 1]]);
+        assert_eq!(
+            read_matrix_market_sparse::<i32, _>(input.as_bytes())
+                .unwrap()
+                .to_dense(),
+            read(input).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_read_coordinate_skew_symmetric_pattern() {
+        let skew = "%%MatrixMarket matrix coordinate integer skew-symmetric\n2 2 1\n2 1 4\n";
+        let pattern = "%%MatrixMarket matrix coordinate pattern general\n2 2 1\n1 2\n";
+
+        assert_eq!(read(skew).unwrap(), vec![vec![0, -4], vec![4, 0]]);
+        assert_eq!(read(pattern).unwrap(), vec![vec![0, 1], vec![0, 0]]);
+    }
+
+    #[test]
+    fn test_read_oversized() {
+        let overflowing =
+            "%%MatrixMarket matrix array integer general\n100000000000 100000000000\n1\n";
+        assert!(matches!(
+            read_matrix_market::<i32, _>(overflowing.as_bytes()),
+            Err(MatrixIoError::Parse { line: 2, .. })
+        ));
+        let symmetric = "%%MatrixMarket matrix array integer symmetric\n10000000000 10000000000\n";
+        assert!(read_matrix_market::<i32, _>(symmetric.as_bytes()).is_err());
+
+        // a valid file, whose dense matrix can't be allocated
+        let huge = "%%MatrixMarket matrix coordinate integer general\n3000000 3000000 1\n1 1 5\n";
+        assert!(matches!(
+            read_matrix_market::<i32, _>(huge.as_bytes()),
+            Err(MatrixIoError::Unsupported(_))
+        ));
+        let just_too_large = format!(
+            "%%MatrixMarket matrix coordinate integer general\n1 {} 0\n",
+            (MAX_MATRIX_BYTES - size_of::<Vec<i32>>()) / size_of::<i32>() + 1
+        );
+        assert!(matches!(
+            read_matrix_market::<i32, _>(just_too_large.as_bytes()),
+            Err(MatrixIoError::Unsupported(_))
+        ));
+        let sparse = read_matrix_market_sparse::<i32, _>(huge.as_bytes()).unwrap();
+        assert_eq!(sparse.rows(), 3000000);
+        let too_many_rows =
+            "%%MatrixMarket matrix coordinate integer general\n100000000000000000 1 0\n";
+        assert!(read_matrix_market_sparse::<i32, _>(too_many_rows.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_read_sparse() {
+        let coordinate =
+            "%%MatrixMarket matrix coordinate integer symmetric\n3 3 2\n2 1 5\n3 3 1\n";
+        let array = "%%MatrixMarket matrix array integer general\n2 2\n0\n3\n0\n0\n";
+
+        let sparse = read_matrix_market_sparse::<i32, _>(coordinate.as_bytes()).unwrap();
+        assert_eq!(sparse.nnz(), 3);
+        assert_eq!(sparse.to_dense(), read(coordinate).unwrap());
+
+        let sparse = read_matrix_market_sparse::<i32, _>(array.as_bytes()).unwrap();
+        assert_eq!(sparse.nnz(), 1);
+        assert_eq!(sparse.to_dense(), vec![vec![0, 0], vec![3, 0]]);
+    }
+
+    #[test]
+    fn test_write_sparse_round_trip() {
+        let matrix = CsrMatrix::from_dense(&[vec![0.5, 0.0], vec![0.0, -2.0]]);
+        let mut output = Vec::new();
+        write_matrix_market_sparse(&mut output, &matrix).unwrap();
+
+        assert_eq!(
+            String::from_utf8(output.clone()).unwrap(),
+            "%%MatrixMarket matrix coordinate real general\n2 2 2\n1 1 0.5\n2 2 -2\n"
+        );
+        assert_eq!(
+            read_matrix_market_sparse::<f64, _>(&output[..]).unwrap(),
+            matrix
+        );
+    }
+
+    #[test]
+    fn test_parse_errors_have_positions() {
+        let cases = [
+            (
+                "%%MatrixMarket matrix array complex general\n",
+                "line 1, column 29: unsupported field `complex`",
+            ),
+            (
+                "%%MatrixMarket matrix coordinate real general\n2 2 1\n1 x 1.0\n",
+                "line 3, column 3: invalid column `x`",
+            ),
+            (
+                "%%MatrixMarket matrix coordinate real general\n2 2 1\n3 1 1.0\n",
+                "line 3, column 1: row 3 out of range 1..=2",
+            ),
+            (
+                "%%MatrixMarket matrix coordinate real general\n2 2 2\n1 1 1.0\n",
+                "line 4, column 1: expected 2 entries, found 1",
+            ),
+            (
+                "%%MatrixMarket matrix array real general\n1 1\n1\n2\n",
+                "line 4, column 1: expected 1 values",
+            ),
+            ("1 2\n", "line 1, column 1: missing `%%MatrixMarket` header"),
+        ];
+
+        for (input, message) in cases {
+            assert_eq!(
+                read_matrix_market::<f64, _>(input.as_bytes())
+                    .unwrap_err()
+                    .to_string(),
+                message
+            );
+        }
+    }
+
+    #[test]
+    fn test_write_matrix_market() {
+        let mut output = Vec::new();
+        write_matrix_market(&mut output, &[vec![1, 2], vec![3, 4]]).unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "%%MatrixMarket matrix array integer general\n2 2\n1\n3\n2\n4\n"
+        );
+    }
+}
@@ -0,0 +1,109 @@
+use std::io::{BufRead, Write};
+
+use super::{parse_token, tokens, MatrixIoError};
+use crate::matrix_multiplication::element::Element;
+
+/// Reads a matrix written one row per line, values separated by commas and/or whitespace
+///
+/// Blank lines and lines starting with `#` are ignored.
+///
+/// # Returns
+///
+/// The matrix, or a `MatrixIoError::Parse` pointing at the first invalid value or at
+/// the first row whose length differs from the first row
+pub fn read_text<T: Element, R: BufRead>(reader: R) -> Result<Vec<Vec<T>>, MatrixIoError> {
+    let mut matrix: Vec<Vec<T>> = Vec::new();
+
+    for (index, line) in reader.lines().enumerate() {
+        let line = line?;
+        let line_number = index + 1;
+
+        if line.trim().is_empty() || line.trim_start().starts_with('#') {
+            continue;
+        }
+
+        let row = tokens(&line)
+            .map(|(column, token)| parse_token(token, line_number, column, "value"))
+            .collect::<Result<Vec<T>, _>>()?;
+
+        if let Some(first) = matrix.first() {
+            if row.len() != first.len() {
+                return Err(MatrixIoError::parse(
+                    line_number,
+                    1,
+                    format!("expected {} values, found {}", first.len(), row.len()),
+                ));
+            }
+        }
+
+        matrix.push(row);
+    }
+
+    Ok(matrix)
+}
+
+/// Writes `matrix` one row per line, with values separated by `separator`
+pub fn write_text<T: Element, W: Write>(
+    writer: &mut W,
+    matrix: &[Vec<T>],
+    separator: char,
+) -> Result<(), MatrixIoError> {
+    for row in matrix {
+        for (j, value) in row.iter().enumerate() {
+            if j > 0 {
+                write!(writer, "{}", separator)?;
+            }
+            write!(writer, "{}", value)?;
+        }
+        writeln!(writer)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_text() {
+        let input = "# a comment\n1, 2,3\n\n4 5\t6\n";
+
+        assert_eq!(
+            read_text::<i32, _>(input.as_bytes()).unwrap(),
+            vec![vec![1, 2, 3], vec![4, 5, 6]]
+        );
+    }
+
+    #[test]
+    fn test_read_text_invalid_value() {
+        let input = "1,2\n3,x\n";
+
+        assert_eq!(
+            read_text::<i32, _>(input.as_bytes())
+                .unwrap_err()
+                .to_string(),
+            "line 2, column 3: invalid value `x`"
+        );
+    }
+
+    #[test]
+    fn test_read_text_ragged_rows() {
+        let input = "1 2\n3\n";
+
+        assert_eq!(
+            read_text::<i32, _>(input.as_bytes())
+                .unwrap_err()
+                .to_string(),
+            "line 2, column 1: expected 2 values, found 1"
+        );
+    }
+
+    #[test]
+    fn test_write_text() {
+        let mut output = Vec::new();
+        write_text(&mut output, &[vec![1, -2], vec![3, 4]], ',').unwrap();
+
+        assert_eq!(String::from_utf8(output).unwrap(), "1,-2\n3,4\n");
+    }
+}
@@ -0,0 +1,439 @@
+use std::cmp::Ordering;
+
+use super::{
+    element::{Element, Float},
+    matrix::Matrix,
+    matrix_multiplication_parallel_i_loop, matrix_multiplication_sequential_ikj,
+    sanitize::{is_matrix_square, SanitizeError, SanitizeResult},
+};
+
+/// Block size used by `solve`, `determinant` and `inverse`
+pub const DEFAULT_BLOCK_SIZE: usize = 64;
+
+#[derive(Debug, Clone, PartialEq)]
+/// LU decomposition with partial pivoting, `P * A = L * U`
+///
+/// `L` is unit lower triangular and `U` is upper triangular. Both are stored in a single
+/// matrix, with the unit diagonal of `L` left implicit.
+pub struct LuDecomposition<T> {
+    lu: Vec<Vec<T>>,
+    /// `permutation[i]` is the row of `A` that was moved to row `i`
+    permutation: Vec<usize>,
+    /// Whether an odd number of row swaps was made
+    odd_swaps: bool,
+}
+
+impl<T: Float> LuDecomposition<T> {
+    /// The number of rows and columns of the decomposed matrix
+    pub fn size(&self) -> usize {
+        self.lu.len()
+    }
+
+    /// The row permutation `P`, as the row of `A` moved to each row
+    pub fn permutation(&self) -> &[usize] {
+        &self.permutation
+    }
+
+    /// The unit lower triangular factor `L`
+    pub fn lower(&self) -> Vec<Vec<T>> {
+        self.triangle(|i, j| match j.cmp(&i) {
+            Ordering::Less => Some(self.lu[i][j]),
+            Ordering::Equal => Some(T::one()),
+            Ordering::Greater => None,
+        })
+    }
+
+    /// The upper triangular factor `U`
+    pub fn upper(&self) -> Vec<Vec<T>> {
+        self.triangle(|i, j| (j >= i).then(|| self.lu[i][j]))
+    }
+
+    fn triangle(&self, cell: impl Fn(usize, usize) -> Option<T>) -> Vec<Vec<T>> {
+        let n = self.size();
+
+        (0..n)
+            .map(|i| (0..n).map(|j| cell(i, j).unwrap_or_else(T::zero)).collect())
+            .collect()
+    }
+
+    /// Computes the determinant from the diagonal of `U` and the parity of `P`
+    pub fn determinant(&self) -> T {
+        let product = (0..self.size()).fold(T::one(), |product, i| product * self.lu[i][i]);
+
+        match self.odd_swaps {
+            true => -product,
+            false => product,
+        }
+    }
+
+    /// Solves `A * x = b`
+    ///
+    /// # Panics
+    ///
+    /// Panics if `b` doesn't have one element per row of `A`.
+    pub fn solve(&self, b: &[T]) -> Vec<T> {
+        let columns: Vec<Vec<T>> = b.iter().map(|&value| vec![value]).collect();
+
+        self.solve_matrix(&columns)
+            .into_iter()
+            .map(|row| row[0])
+            .collect()
+    }
+
+    /// Solves `A * X = B` for every column of `B` at once
+    ///
+    /// # Panics
+    ///
+    /// Panics if `b` doesn't have one row per row of `A`.
+    pub fn solve_matrix(&self, b: &[Vec<T>]) -> Vec<Vec<T>> {
+        let n = self.size();
+        assert_eq!(b.len(), n, "Right-hand side must have one row per row of A");
+
+        let mut x: Vec<Vec<T>> = self.permutation.iter().map(|&i| b[i].clone()).collect();
+
+        // forward substitution with the unit lower triangle
+        for i in 0..n {
+            let (solved, rest) = x.split_at_mut(i);
+            for (k, solved_row) in solved.iter().enumerate() {
+                subtract_scaled(&mut rest[0], self.lu[i][k], solved_row);
+            }
+        }
+
+        // backward substitution with the upper triangle
+        for i in (0..n).rev() {
+            let (rest, solved) = x.split_at_mut(i + 1);
+            let row = &mut rest[i];
+            for (k, solved_row) in solved.iter().enumerate() {
+                subtract_scaled(row, self.lu[i][i + 1 + k], solved_row);
+            }
+            for value in row.iter_mut() {
+                *value /= self.lu[i][i];
+            }
+        }
+
+        x
+    }
+
+    /// Computes the inverse of `A`
+    pub fn inverse(&self) -> Vec<Vec<T>> {
+        self.solve_matrix(&Matrix::identity(self.size()))
+    }
+}
+
+/// Subtracts `factor * source` from `target`, element by element
+fn subtract_scaled<T: Element>(target: &mut [T], factor: T, source: &[T]) {
+    for (value, &other) in target.iter_mut().zip(source) {
+        *value -= factor * other;
+    }
+}
+
+/// Decomposes `a` one column at a time
+///
+/// # Returns
+///
+/// The decomposition, `SanitizeError::SingularMatrix` if a pivot vanishes, or the error
+/// of `a` not being square
+pub fn lu_decompose<T: Float>(a: &[Vec<T>]) -> Result<LuDecomposition<T>, SanitizeError> {
+    decompose(a, a.len().max(1), matrix_multiplication_sequential_ikj)
+}
+
+/// Decomposes `a` by panels of `block_size` columns
+///
+/// Each panel is decomposed one column at a time, then the rest of the matrix is updated
+/// at once with the sequential GEMM kernel.
+///
+/// # Panics
+///
+/// Panics if `block_size` is zero.
+pub fn lu_decompose_blocked<T: Float>(
+    a: &[Vec<T>],
+    block_size: usize,
+) -> Result<LuDecomposition<T>, SanitizeError> {
+    decompose(a, block_size, matrix_multiplication_sequential_ikj)
+}
+
+/// Decomposes `a` by panels of `block_size` columns, updating the rest of the matrix
+/// with the parallel GEMM kernel on `threads` threads
+///
+/// # Panics
+///
+/// Panics if `block_size` is zero.
+pub fn lu_decompose_parallel<T: Float>(
+    a: &[Vec<T>],
+    block_size: usize,
+    threads: usize,
+) -> Result<LuDecomposition<T>, SanitizeError> {
+    decompose(a, block_size, |l, u| {
+        matrix_multiplication_parallel_i_loop(l, u, threads)
+    })
+}
+
+/// Right-looking blocked LU decomposition, using `multiply` for the trailing updates
+fn decompose<T: Float>(
+    a: &[Vec<T>],
+    block_size: usize,
+    multiply: impl Fn(&[Vec<T>], &[Vec<T>]) -> Option<Vec<Vec<T>>>,
+) -> Result<LuDecomposition<T>, SanitizeError> {
+    assert!(block_size > 0, "Block size must be positive");
+
+    if let SanitizeResult::NotOk(e) = is_matrix_square(a, "A") {
+        return Err(e);
+    }
+
+    let n = a.len();
+    let mut lu = a.to_vec();
+    let mut permutation: Vec<usize> = (0..n).collect();
+    let mut odd_swaps = false;
+
+    // a pivot this small relative to the largest cell of its row of A is rounding noise
+    let tolerance = T::EPSILON * T::from_f64(n as f64);
+    let row_scales: Vec<T> = a
+        .iter()
+        .map(|row| {
+            row.iter()
+                .fold(T::zero(), |largest, &value| match value.abs() > largest {
+                    true => value.abs(),
+                    false => largest,
+                })
+        })
+        .collect();
+
+    for start in (0..n).step_by(block_size) {
+        let end = (start + block_size).min(n);
+
+        // decompose the panel, swapping whole rows so the rest of the matrix follows
+        for k in start..end {
+            let pivot = (k..n).fold(k, |pivot, i| match lu[i][k].abs() > lu[pivot][k].abs() {
+                true => i,
+                false => pivot,
+            });
+            let threshold = row_scales[permutation[pivot]] * tolerance;
+            if lu[pivot][k].abs().partial_cmp(&threshold) != Some(Ordering::Greater) {
+                return Err(SanitizeError::SingularMatrix("A".to_string()));
+            }
+
+            if pivot != k {
+                lu.swap(pivot, k);
+                permutation.swap(pivot, k);
+                odd_swaps = !odd_swaps;
+            }
+
+            let (top, bottom) = lu.split_at_mut(k + 1);
+            let pivot_row = &top[k];
+            for row in bottom {
+                row[k] /= pivot_row[k];
+                let factor = row[k];
+                subtract_scaled(&mut row[k + 1..end], factor, &pivot_row[k + 1..end]);
+            }
+        }
+
+        if end == n {
+            break;
+        }
+
+        // U12 = L11^-1 * A12
+        for i in start..end {
+            let (top, bottom) = lu.split_at_mut(i);
+            let row = &mut bottom[0];
+            for (k, solved_row) in top.iter().enumerate().skip(start) {
+                let factor = row[k];
+                subtract_scaled(&mut row[end..], factor, &solved_row[end..]);
+            }
+        }
+
+        // A22 -= L21 * U12
+        let l21: Vec<Vec<T>> = lu[end..]
+            .iter()
+            .map(|row| row[start..end].to_vec())
+            .collect();
+        let u12: Vec<Vec<T>> = lu[start..end]
+            .iter()
+            .map(|row| row[end..].to_vec())
+            .collect();
+        // both blocks are non-empty and conformable, so the kernel always returns a result
+        let update = multiply(&l21, &u12).unwrap();
+
+        for (row, update_row) in lu[end..].iter_mut().zip(update) {
+            for (value, delta) in row[end..].iter_mut().zip(update_row) {
+                *value -= delta;
+            }
+        }
+    }
+
+    Ok(LuDecomposition {
+        lu,
+        permutation,
+        odd_swaps,
+    })
+}
+
+/// Solves `A * x = b`
+///
+/// # Returns
+///
+/// The solution, `SanitizeError::SingularMatrix` if `a` has no inverse, or the error of
+/// the operands having incompatible shapes
+pub fn solve<T: Float>(a: &[Vec<T>], b: &[T]) -> Result<Vec<T>, SanitizeError> {
+    if b.len() != a.len() {
+        return Err(SanitizeError::NotConformable {
+            a_cols: a.len(),
+            b_rows: b.len(),
+        });
+    }
+
+    Ok(lu_decompose_blocked(a, DEFAULT_BLOCK_SIZE)?.solve(b))
+}
+
+/// Computes the determinant of `a`, which is zero when `a` is singular
+pub fn determinant<T: Float>(a: &[Vec<T>]) -> Result<T, SanitizeError> {
+    match lu_decompose_blocked(a, DEFAULT_BLOCK_SIZE) {
+        Ok(lu) => Ok(lu.determinant()),
+        Err(SanitizeError::SingularMatrix(_)) => Ok(T::zero()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Computes the inverse of `a`
+pub fn inverse<T: Float>(a: &[Vec<T>]) -> Result<Vec<Vec<T>>, SanitizeError> {
+    Ok(lu_decompose_blocked(a, DEFAULT_BLOCK_SIZE)?.inverse())
+}
+
+impl<T: Float> Matrix<T> {
+    /// Computes the LU decomposition, see `lu_decompose_blocked`
+    pub fn lu(&self) -> Result<LuDecomposition<T>, SanitizeError> {
+        lu_decompose_blocked(self, DEFAULT_BLOCK_SIZE)
+    }
+
+    /// Solves `self * x = b`, see `solve`
+    pub fn solve(&self, b: &[T]) -> Result<Vec<T>, SanitizeError> {
+        solve(self, b)
+    }
+
+    /// Computes the determinant, see `determinant`
+    pub fn determinant(&self) -> Result<T, SanitizeError> {
+        determinant(self)
+    }
+
+    /// Computes the inverse, see `inverse`
+    pub fn inverse(&self) -> Result<Matrix<T>, SanitizeError> {
+        inverse(self).map(Matrix::new)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matrix_multiplication::generate::{Distribution, MatrixGenerator};
+
+    fn get_random(n: usize) -> Vec<Vec<f64>> {
+        MatrixGenerator::new(Some(31), Distribution::default()).generate(n, n)
+    }
+
+    fn assert_matrices_close(a: &[Vec<f64>], b: &[Vec<f64>]) {
+        for (row_a, row_b) in a.iter().zip(b) {
+            for (&x, &y) in row_a.iter().zip(row_b) {
+                assert!(x.approx_eq(y, 1e-9), "{} != {}", x, y);
+            }
+        }
+    }
+
+    fn assert_reconstructs(a: &[Vec<f64>], lu: &LuDecomposition<f64>) {
+        let permuted: Vec<Vec<f64>> = lu.permutation().iter().map(|&i| a[i].clone()).collect();
+        let product = matrix_multiplication_sequential_ikj(&lu.lower(), &lu.upper()).unwrap();
+
+        assert_matrices_close(&permuted, &product);
+    }
+
+    #[test]
+    fn test_decompositions_reconstruct_matrix() {
+        let a = get_random(50);
+
+        assert_reconstructs(&a, &lu_decompose(&a).unwrap());
+        for block_size in [1, 7, 16, 64] {
+            assert_reconstructs(&a, &lu_decompose_blocked(&a, block_size).unwrap());
+            assert_reconstructs(&a, &lu_decompose_parallel(&a, block_size, 3).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_solve() {
+        let a = vec![
+            vec![2.0, 1.0, -1.0],
+            vec![-3.0, -1.0, 2.0],
+            vec![-2.0, 1.0, 2.0],
+        ];
+
+        let x = solve(&a, &[8.0, -11.0, -3.0]).unwrap();
+
+        assert_matrices_close(&[x], &[vec![2.0, 3.0, -1.0]]);
+    }
+
+    #[test]
+    fn test_determinant() {
+        assert_eq!(determinant(&[vec![4.0, 3.0], vec![6.0, 3.0]]), Ok(-6.0));
+        assert_eq!(determinant(&[vec![0.0, 1.0], vec![1.0, 0.0]]), Ok(-1.0));
+        assert_eq!(determinant(&[vec![1.0_f32, 2.0], vec![2.0, 4.0]]), Ok(0.0));
+    }
+
+    #[test]
+    fn test_inverse() {
+        let a = Matrix::new(get_random(20));
+
+        let product = &a * &a.inverse().unwrap();
+
+        assert_matrices_close(&product, &Matrix::identity(20));
+    }
+
+    #[test]
+    fn test_singular_matrix() {
+        let a = vec![
+            vec![1.0, 2.0, 3.0],
+            vec![4.0, 5.0, 6.0],
+            vec![7.0, 8.0, 9.0],
+        ];
+
+        assert_eq!(
+            lu_decompose(&a),
+            Err(SanitizeError::SingularMatrix("A".to_string()))
+        );
+        assert_eq!(
+            lu_decompose_blocked(&a, 2),
+            Err(SanitizeError::SingularMatrix("A".to_string()))
+        );
+        assert!(inverse(&a).is_err());
+    }
+
+    #[test]
+    fn test_mixed_magnitudes() {
+        let a = vec![
+            vec![1e10, 0.0, 0.0],
+            vec![0.0, 1.0, 0.0],
+            vec![0.0, 0.0, 1e-7],
+        ];
+        let b = vec![vec![1e20, 0.0], vec![0.0, 1.0]];
+
+        assert_reconstructs(&a, &lu_decompose(&a).unwrap());
+        assert_reconstructs(&a, &lu_decompose_blocked(&a, 2).unwrap());
+        assert!(determinant(&a).unwrap().approx_eq(1e3, 1e-9));
+        assert_eq!(determinant(&b), Ok(1e20));
+    }
+
+    #[test]
+    fn test_invalid_operands() {
+        assert_eq!(
+            lu_decompose::<f64>(&[]),
+            Err(SanitizeError::EmptyMatrix("A".to_string()))
+        );
+        assert_eq!(
+            determinant(&[vec![1.0, 2.0]]),
+            Err(SanitizeError::NotSquareMatrix("A".to_string()))
+        );
+        assert_eq!(
+            solve(&[vec![1.0]], &[1.0, 2.0]),
+            Err(SanitizeError::NotConformable {
+                a_cols: 1,
+                b_rows: 2
+            })
+        );
+    }
+}
@@ -0,0 +1,535 @@
+use std::ops::{Add, Deref, Index, IndexMut, Mul, Neg, Sub};
+
+use super::{
+    element::Element,
+    generate::generate_zero_matrix,
+    matrix_multiplication_sequential_ikj,
+    sanitize::{sanitize_product, SanitizeResult},
+};
+
+/// Side length below which the recursive transposes swap cells directly
+const TRANSPOSE_LEAF_SIZE: usize = 16;
+
+#[derive(Debug, Clone, PartialEq)]
+/// Dense row-major matrix with the usual arithmetic operators
+///
+/// Dereferences to its rows, so it can be passed to every kernel taking `&[Vec<T>]`.
+/// The rows always have the same length.
+pub struct Matrix<T>(Vec<Vec<T>>);
+
+impl<T: Element> Matrix<T> {
+    /// Create new `Matrix` from its rows
+    ///
+    /// # Panics
+    ///
+    /// Panics if the rows don't all have the same length.
+    pub fn new(rows: Vec<Vec<T>>) -> Self {
+        if let Some(first) = rows.first() {
+            assert!(
+                rows.iter().all(|row| row.len() == first.len()),
+                "Rows must all have the same length"
+            );
+        }
+
+        Matrix(rows)
+    }
+
+    /// Create new `rows` x `cols` matrix filled with zeros
+    pub fn zeros(rows: usize, cols: usize) -> Self {
+        Matrix(generate_zero_matrix(rows, cols))
+    }
+
+    /// Create new `n` x `n` identity matrix
+    pub fn identity(n: usize) -> Self {
+        let mut matrix = Matrix::zeros(n, n);
+        for i in 0..n {
+            matrix[(i, i)] = T::one();
+        }
+
+        matrix
+    }
+
+    /// The number of rows
+    pub fn rows(&self) -> usize {
+        self.0.len()
+    }
+
+    /// The number of columns
+    pub fn cols(&self) -> usize {
+        self.0.first().map_or(0, |row| row.len())
+    }
+
+    /// Whether the matrix has as many rows as columns
+    pub fn is_square(&self) -> bool {
+        self.rows() == self.cols()
+    }
+
+    /// Consumes the matrix, returning its rows
+    pub fn into_inner(self) -> Vec<Vec<T>> {
+        self.0
+    }
+
+    /// Returns the rows for kernels writing into the matrix, which must keep their lengths
+    pub(crate) fn rows_mut(&mut self) -> &mut [Vec<T>] {
+        &mut self.0
+    }
+
+    /// Multiplies every cell by `scalar`
+    pub fn scale(mut self, scalar: T) -> Self {
+        for value in self.0.iter_mut().flatten() {
+            *value *= scalar;
+        }
+
+        self
+    }
+
+    /// Computes the product of the matrix with the column vector `x`
+    ///
+    /// # Panics
+    ///
+    /// Panics if `x` doesn't have one element per column.
+    pub fn mul_vec(&self, x: &[T]) -> Vec<T> {
+        assert_eq!(
+            x.len(),
+            self.cols(),
+            "Vector length must equal the number of columns"
+        );
+
+        self.0
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .zip(x)
+                    .fold(T::zero(), |sum, (&a, &b)| sum + a * b)
+            })
+            .collect()
+    }
+
+    /// Computes the sum of the diagonal
+    ///
+    /// # Panics
+    ///
+    /// Panics if the matrix isn't square.
+    pub fn trace(&self) -> T {
+        assert!(
+            self.is_square(),
+            "Trace is only defined for square matrices"
+        );
+
+        (0..self.rows()).fold(T::zero(), |sum, i| sum + self[(i, i)])
+    }
+
+    /// Computes the Frobenius norm, the square root of the sum of squared cells
+    pub fn frobenius_norm(&self) -> f64 {
+        self.0
+            .iter()
+            .flatten()
+            .map(|&value| value.to_f64() * value.to_f64())
+            .sum::<f64>()
+            .sqrt()
+    }
+
+    /// Computes the transpose into a new matrix
+    ///
+    /// The matrix is split recursively along its longer side, so the copy is
+    /// cache-oblivious.
+    pub fn transpose(&self) -> Self {
+        let (rows, cols) = (self.rows(), self.cols());
+        let mut transposed = Matrix::zeros(cols, rows);
+
+        transpose_into(&self.0, &mut transposed.0, (0, 0), (rows, cols));
+
+        transposed
+    }
+
+    /// Transposes the matrix in place
+    ///
+    /// Diagonal blocks are transposed recursively and the blocks on either side of the
+    /// diagonal are swapped recursively, so the transpose is cache-oblivious and needs
+    /// no extra memory.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the matrix isn't square.
+    pub fn transpose_in_place(&mut self) {
+        assert!(
+            self.is_square(),
+            "In-place transpose is only supported for square matrices"
+        );
+
+        let n = self.rows();
+        transpose_diagonal_block(&mut self.0, 0, n);
+    }
+}
+
+/// Writes the transpose of the `size` block of `source` starting at `origin` into `target`
+fn transpose_into<T: Copy>(
+    source: &[Vec<T>],
+    target: &mut [Vec<T>],
+    origin: (usize, usize),
+    size: (usize, usize),
+) {
+    let ((row, col), (rows, cols)) = (origin, size);
+
+    if rows <= TRANSPOSE_LEAF_SIZE && cols <= TRANSPOSE_LEAF_SIZE {
+        for (i, source_row) in source.iter().enumerate().skip(row).take(rows) {
+            for (j, &value) in source_row.iter().enumerate().skip(col).take(cols) {
+                target[j][i] = value;
+            }
+        }
+    } else if rows >= cols {
+        let half = rows / 2;
+        transpose_into(source, target, (row, col), (half, cols));
+        transpose_into(source, target, (row + half, col), (rows - half, cols));
+    } else {
+        let half = cols / 2;
+        transpose_into(source, target, (row, col), (rows, half));
+        transpose_into(source, target, (row, col + half), (rows, cols - half));
+    }
+}
+
+/// Transposes in place the `size` x `size` block on the diagonal starting at `start`
+fn transpose_diagonal_block<T: Copy>(matrix: &mut [Vec<T>], start: usize, size: usize) {
+    if size <= TRANSPOSE_LEAF_SIZE {
+        for i in start..start + size {
+            for j in i + 1..start + size {
+                swap_mirrored(matrix, i, j);
+            }
+        }
+        return;
+    }
+
+    let half = size / 2;
+    transpose_diagonal_block(matrix, start, half);
+    transpose_diagonal_block(matrix, start + half, size - half);
+    swap_off_diagonal_block(matrix, (start + half, start), (size - half, half));
+}
+
+/// Swaps the `size` block below the diagonal starting at `origin` with its mirror image
+fn swap_off_diagonal_block<T: Copy>(
+    matrix: &mut [Vec<T>],
+    origin: (usize, usize),
+    size: (usize, usize),
+) {
+    let ((row, col), (rows, cols)) = (origin, size);
+
+    if rows <= TRANSPOSE_LEAF_SIZE && cols <= TRANSPOSE_LEAF_SIZE {
+        for i in row..row + rows {
+            for j in col..col + cols {
+                swap_mirrored(matrix, i, j);
+            }
+        }
+    } else if rows >= cols {
+        let half = rows / 2;
+        swap_off_diagonal_block(matrix, (row, col), (half, cols));
+        swap_off_diagonal_block(matrix, (row + half, col), (rows - half, cols));
+    } else {
+        let half = cols / 2;
+        swap_off_diagonal_block(matrix, (row, col), (rows, half));
+        swap_off_diagonal_block(matrix, (row, col + half), (rows, cols - half));
+    }
+}
+
+/// Swaps `matrix[i][j]` with `matrix[j][i]`, for `i != j`
+fn swap_mirrored<T>(matrix: &mut [Vec<T>], i: usize, j: usize) {
+    let (low, high) = (i.min(j), i.max(j));
+    let (top, bottom) = matrix.split_at_mut(high);
+    std::mem::swap(&mut top[low][high], &mut bottom[0][low]);
+}
+
+impl<T> Deref for Matrix<T> {
+    type Target = [Vec<T>];
+
+    fn deref(&self) -> &[Vec<T>] {
+        &self.0
+    }
+}
+
+impl<T: Element> From<Vec<Vec<T>>> for Matrix<T> {
+    fn from(rows: Vec<Vec<T>>) -> Self {
+        Matrix::new(rows)
+    }
+}
+
+impl<T> Index<(usize, usize)> for Matrix<T> {
+    type Output = T;
+
+    fn index(&self, (i, j): (usize, usize)) -> &T {
+        &self.0[i][j]
+    }
+}
+
+impl<T> IndexMut<(usize, usize)> for Matrix<T> {
+    fn index_mut(&mut self, (i, j): (usize, usize)) -> &mut T {
+        &mut self.0[i][j]
+    }
+}
+
+/// Combines `a` and `b` cell by cell, panicking with `operation` if their shapes differ
+fn zip_cells<T: Element>(
+    mut a: Matrix<T>,
+    b: &Matrix<T>,
+    operation: &str,
+    combine: impl Fn(&mut T, T),
+) -> Matrix<T> {
+    assert!(
+        a.rows() == b.rows() && a.cols() == b.cols(),
+        "Cannot {} a {}x{} matrix and a {}x{} matrix",
+        operation,
+        a.rows(),
+        a.cols(),
+        b.rows(),
+        b.cols()
+    );
+
+    for (row_a, row_b) in a.0.iter_mut().zip(&b.0) {
+        for (x, &y) in row_a.iter_mut().zip(row_b) {
+            combine(x, y);
+        }
+    }
+
+    a
+}
+
+impl<T: Element> Add<&Matrix<T>> for Matrix<T> {
+    type Output = Matrix<T>;
+
+    fn add(self, other: &Matrix<T>) -> Matrix<T> {
+        zip_cells(self, other, "add", |x, y| *x += y)
+    }
+}
+
+impl<T: Element> Add for &Matrix<T> {
+    type Output = Matrix<T>;
+
+    fn add(self, other: &Matrix<T>) -> Matrix<T> {
+        self.clone() + other
+    }
+}
+
+impl<T: Element> Add for Matrix<T> {
+    type Output = Matrix<T>;
+
+    fn add(self, other: Matrix<T>) -> Matrix<T> {
+        self + &other
+    }
+}
+
+impl<T: Element> Sub<&Matrix<T>> for Matrix<T> {
+    type Output = Matrix<T>;
+
+    fn sub(self, other: &Matrix<T>) -> Matrix<T> {
+        zip_cells(self, other, "subtract", |x, y| *x -= y)
+    }
+}
+
+impl<T: Element> Sub for &Matrix<T> {
+    type Output = Matrix<T>;
+
+    fn sub(self, other: &Matrix<T>) -> Matrix<T> {
+        self.clone() - other
+    }
+}
+
+impl<T: Element> Sub for Matrix<T> {
+    type Output = Matrix<T>;
+
+    fn sub(self, other: Matrix<T>) -> Matrix<T> {
+        self - &other
+    }
+}
+
+impl<T: Element> Neg for Matrix<T> {
+    type Output = Matrix<T>;
+
+    fn neg(mut self) -> Matrix<T> {
+        for value in self.0.iter_mut().flatten() {
+            *value = -*value;
+        }
+
+        self
+    }
+}
+
+impl<T: Element> Neg for &Matrix<T> {
+    type Output = Matrix<T>;
+
+    fn neg(self) -> Matrix<T> {
+        -self.clone()
+    }
+}
+
+impl<T: Element> Mul for &Matrix<T> {
+    type Output = Matrix<T>;
+
+    /// Computes the matrix product with the sequential ikj kernel
+    ///
+    /// The product is a zero matrix when a dimension is zero. A matrix without rows has
+    /// no columns either, so it can be multiplied by a matrix with any number of rows.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the number of columns of `self` differs from the number of rows of
+    /// `other`.
+    fn mul(self, other: &Matrix<T>) -> Matrix<T> {
+        if self.rows() == 0 || self.cols() == 0 || other.cols() == 0 {
+            assert!(
+                self.rows() == 0 || self.cols() == other.rows(),
+                "Cannot multiply a {}x{} matrix by a {}x{} matrix",
+                self.rows(),
+                self.cols(),
+                other.rows(),
+                other.cols()
+            );
+            return Matrix::zeros(self.rows(), other.cols());
+        }
+        if let SanitizeResult::NotOk(error) = sanitize_product(self, other) {
+            panic!("Cannot multiply: {}", error);
+        }
+
+        Matrix(matrix_multiplication_sequential_ikj(self, other).unwrap())
+    }
+}
+
+impl<T: Element> Mul for Matrix<T> {
+    type Output = Matrix<T>;
+
+    fn mul(self, other: Matrix<T>) -> Matrix<T> {
+        &self * &other
+    }
+}
+
+impl<T: Element> Mul<T> for Matrix<T> {
+    type Output = Matrix<T>;
+
+    fn mul(self, scalar: T) -> Matrix<T> {
+        self.scale(scalar)
+    }
+}
+
+impl<T: Element> Mul<T> for &Matrix<T> {
+    type Output = Matrix<T>;
+
+    fn mul(self, scalar: T) -> Matrix<T> {
+        self.clone().scale(scalar)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_2x3() -> Matrix<i32> {
+        Matrix::new(vec![vec![1, 2, 3], vec![4, 5, 6]])
+    }
+
+    fn get_numbered(rows: usize, cols: usize) -> Matrix<i64> {
+        Matrix::new(
+            (0..rows)
+                .map(|i| (0..cols).map(|j| (i * cols + j) as i64).collect())
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_add_sub_neg() {
+        let a = get_2x3();
+        let b = Matrix::new(vec![vec![6, 5, 4], vec![3, 2, 1]]);
+
+        assert_eq!((&a + &b).into_inner(), vec![vec![7; 3]; 2]);
+        assert_eq!(
+            (&a - &b).into_inner(),
+            vec![vec![-5, -3, -1], vec![1, 3, 5]]
+        );
+        assert_eq!(&a + &(-&a), Matrix::zeros(2, 3));
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot add")]
+    fn test_add_shape_mismatch() {
+        let _ = &get_2x3() + &Matrix::zeros(3, 2);
+    }
+
+    #[test]
+    fn test_mul() {
+        let a = get_2x3();
+
+        assert_eq!(
+            (&a * &a.transpose()).into_inner(),
+            vec![vec![14, 32], vec![32, 77]]
+        );
+        assert_eq!(&a * &Matrix::identity(3), a);
+        assert_eq!((&a * 2).into_inner(), vec![vec![2, 4, 6], vec![8, 10, 12]]);
+    }
+
+    #[test]
+    fn test_owned_operators() {
+        let a = get_2x3();
+
+        assert_eq!(a.clone() + a.clone(), &a * 2);
+        assert_eq!(a.clone() - a.clone(), Matrix::zeros(2, 3));
+        assert_eq!(a.clone() * Matrix::identity(3), a);
+    }
+
+    #[test]
+    fn test_mul_zero_dimensions() {
+        let empty = Matrix::<i32>::zeros(0, 0);
+
+        assert_eq!(&Matrix::zeros(3, 0) * &empty, Matrix::zeros(3, 0));
+        assert_eq!(&empty * &empty, empty);
+        assert_eq!(&empty * &get_2x3(), empty);
+        assert_eq!(&get_2x3() * &Matrix::zeros(3, 0), Matrix::zeros(2, 0));
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot multiply")]
+    fn test_mul_zero_dimensions_not_conformable() {
+        let _ = &Matrix::<i32>::zeros(3, 0) * &get_2x3();
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot multiply")]
+    fn test_mul_not_conformable() {
+        let _ = &get_2x3() * &get_2x3();
+    }
+
+    #[test]
+    fn test_mul_vec() {
+        assert_eq!(get_2x3().mul_vec(&[1, 0, -1]), vec![-2, -2]);
+    }
+
+    #[test]
+    fn test_trace_and_norm() {
+        let a = Matrix::new(vec![vec![3.0, 1.0], vec![-1.0, 1.0]]);
+
+        assert_eq!(a.trace(), 4.0);
+        assert_eq!(a.frobenius_norm(), 12.0_f64.sqrt());
+        assert_eq!(Matrix::<f32>::identity(5).trace(), 5.0);
+    }
+
+    #[test]
+    fn test_transpose() {
+        for (rows, cols) in [(2, 3), (1, 40), (37, 19), (64, 64)] {
+            let a = get_numbered(rows, cols);
+            let transposed = a.transpose();
+
+            assert_eq!((transposed.rows(), transposed.cols()), (cols, rows));
+            for i in 0..rows {
+                for j in 0..cols {
+                    assert_eq!(transposed[(j, i)], a[(i, j)]);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_transpose_in_place() {
+        for n in [1, 2, 16, 17, 50] {
+            let a = get_numbered(n, n);
+            let mut b = a.clone();
+            b.transpose_in_place();
+
+            assert_eq!(b, a.transpose());
+        }
+    }
+}
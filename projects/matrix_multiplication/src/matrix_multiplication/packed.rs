@@ -0,0 +1,570 @@
+use std::ops::Range;
+use std::sync::{mpsc, Arc};
+
+use log::error;
+
+use super::{
+    element::Element,
+    gemm::Transpose,
+    generate::generate_zero_matrix,
+    sanitize::{sanitize_product, SanitizeResult},
+    simd::{packed_kernel, PackedKernel, SimdLevel, MR},
+    types::{MatrixRowMutPtr, SharedPtr},
+};
+use crate::thread_pool::ThreadPool;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Sizes of the blocks the packed GEMM splits the operands into
+///
+/// A `kc` x `NR` panel of packed B should fit in L1, an `mc` x `kc` block of packed A
+/// in L2 and a `kc` x `nc` block of packed B in L3.
+pub struct BlockSizes {
+    /// Rows of A packed at once, rounded up to a multiple of `MR`
+    pub mc: usize,
+    /// Depth of the packed panels, the slice of the inner dimension handled at once
+    pub kc: usize,
+    /// Columns of B packed at once, rounded up to a multiple of `NR`
+    pub nc: usize,
+}
+
+impl Default for BlockSizes {
+    fn default() -> Self {
+        BlockSizes {
+            mc: 128,
+            kc: 256,
+            nc: 4096,
+        }
+    }
+}
+
+/// An operand of the packed GEMM, read through `transpose`
+#[derive(Clone, Copy)]
+pub(crate) struct Operand<'a, T> {
+    pub matrix: &'a [Vec<T>],
+    pub transpose: Transpose,
+}
+
+impl<T> Operand<'_, T> {
+    /// The number of rows after transposition
+    pub fn rows(&self) -> usize {
+        match self.transpose {
+            Transpose::No => self.matrix.len(),
+            Transpose::Yes => self.matrix[0].len(),
+        }
+    }
+
+    /// The number of columns after transposition
+    pub fn cols(&self) -> usize {
+        match self.transpose {
+            Transpose::No => self.matrix[0].len(),
+            Transpose::Yes => self.matrix.len(),
+        }
+    }
+}
+
+/// The block of `C` computed by one job, for one slice of the inner dimension
+struct Block {
+    rows: Range<usize>,
+    cols: Range<usize>,
+    depth: Range<usize>,
+}
+
+/// Multiplies the matrices with the packed GEMM, using the default block sizes and the
+/// best SIMD instructions of the running CPU
+///
+/// See `matrix_multiplication_packed_with`.
+pub fn matrix_multiplication_packed<T: Element>(
+    a: &[Vec<T>],
+    b: &[Vec<T>],
+    threads: usize,
+) -> Option<Vec<Vec<T>>> {
+    matrix_multiplication_packed_with(a, b, threads, BlockSizes::default(), SimdLevel::detect())
+}
+
+/// Multiplies the matrices with a GotoBLAS/BLIS style packed GEMM
+///
+/// For every `nc` wide block of columns and every `kc` slice of the inner dimension, the
+/// `kc` x `nc` block of B is packed into contiguous `NR` wide panels shared by all jobs.
+/// Each job then packs an `mc` x `kc` block of A into `MR` tall panels and runs the
+/// `MR` x `NR` micro-kernel of `level` over an `mc` x `nc` block of `C`, so the packing
+/// of B and the `ic` loop run in parallel on the `ThreadPool`.
+///
+/// # Arguments
+///
+/// * `a` - The first matrix
+/// * `b` - The second matrix
+/// * `threads` - The number of threads to use
+/// * `block_sizes` - The cache block sizes
+/// * `level` - The instruction set of the micro-kernel
+///
+/// # Returns
+///
+/// The product of the matrices, or `None` if they can't be multiplied
+///
+/// # Panics
+///
+/// Panics if a block size is zero or if the running CPU doesn't support `level`.
+pub fn matrix_multiplication_packed_with<T: Element>(
+    a: &[Vec<T>],
+    b: &[Vec<T>],
+    threads: usize,
+    block_sizes: BlockSizes,
+    level: SimdLevel,
+) -> Option<Vec<Vec<T>>> {
+    match sanitize_product(a, b) {
+        SanitizeResult::Ok => (),
+        SanitizeResult::NotOk(error) => {
+            error!("Error: {:?}", error);
+            return None;
+        }
+    };
+
+    let mut c = generate_zero_matrix(a.len(), b[0].len());
+
+    packed_gemm(
+        Operand {
+            matrix: a,
+            transpose: Transpose::No,
+        },
+        Operand {
+            matrix: b,
+            transpose: Transpose::No,
+        },
+        T::one(),
+        &mut c,
+        threads,
+        block_sizes,
+        level,
+    );
+
+    Some(c)
+}
+
+/// Adds `alpha * op(a) * op(b)` to `c` with the packed GEMM
+///
+/// With one thread the blocks are computed on the calling thread, otherwise they are
+/// computed by the jobs of a `ThreadPool`.
+///
+/// # Panics
+///
+/// Panics if a block size is zero or if the running CPU doesn't support `level`. The
+/// caller must have checked that the shapes of the operands and of `c` agree.
+pub(crate) fn packed_gemm<T: Element>(
+    a: Operand<T>,
+    b: Operand<T>,
+    alpha: T,
+    c: &mut [Vec<T>],
+    threads: usize,
+    block_sizes: BlockSizes,
+    level: SimdLevel,
+) {
+    assert!(
+        block_sizes.mc > 0 && block_sizes.kc > 0 && block_sizes.nc > 0,
+        "Block sizes must be positive"
+    );
+    assert!(
+        level.is_available(),
+        "{} is not supported by this CPU",
+        level
+    );
+
+    let (rows, inner, cols) = (a.rows(), b.rows(), b.cols());
+    let (nr, kernel) = packed_kernel::<T>(level);
+    let mc = block_sizes.mc.div_ceil(MR) * MR;
+    let nc = block_sizes.nc.div_ceil(nr) * nr;
+
+    // the blocks of `c` sharing the packed `depth` x `cols` block of B
+    let blocks = |cols: Range<usize>, depth: Range<usize>| {
+        (0..rows).step_by(mc).map(move |ic| Block {
+            rows: ic..(ic + mc).min(rows),
+            cols: cols.clone(),
+            depth: depth.clone(),
+        })
+    };
+    let slices = (0..cols).step_by(nc).flat_map(|jc| {
+        (0..inner).step_by(block_sizes.kc).map(move |pc| {
+            (
+                jc..(jc + nc).min(cols),
+                pc..(pc + block_sizes.kc).min(inner),
+            )
+        })
+    });
+
+    if threads <= 1 {
+        for (cols, depth) in slices {
+            let b_packed = pack_b(b, depth.clone(), cols.clone(), nr);
+
+            for block in blocks(cols, depth) {
+                let mut c_rows = row_pointers(&mut c[block.rows.clone()]);
+                // SAFETY: the blocks are computed one after the other
+                unsafe { compute_block(a, &b_packed, alpha, &mut c_rows, &block, nr, kernel) };
+            }
+        }
+        return;
+    }
+
+    let pool = ThreadPool::new(threads);
+
+    for (cols, depth) in slices {
+        let b_packed = Arc::new(pack_b_parallel(
+            &pool,
+            b,
+            depth.clone(),
+            cols.clone(),
+            nr,
+            threads,
+        ));
+        let (sender, receiver) = mpsc::channel();
+        let mut jobs = 0;
+
+        for block in blocks(cols, depth) {
+            let a_matrix = SharedPtr::new(a.matrix);
+            let a_transpose = a.transpose;
+            let b_packed = Arc::clone(&b_packed);
+            let mut c_rows = row_pointers(&mut c[block.rows.clone()]);
+            let sender = sender.clone();
+
+            // SAFETY: `a` and `c` outlive the job, which is waited for below, and
+            // the jobs write disjoint blocks of `c`
+            pool.execute(move || unsafe {
+                let a = Operand {
+                    matrix: a_matrix.get(),
+                    transpose: a_transpose,
+                };
+                compute_block(a, &b_packed, alpha, &mut c_rows, &block, nr, kernel);
+                sender.send(()).unwrap();
+            });
+            jobs += 1;
+        }
+
+        // the next slice of the inner dimension adds to the same blocks of `c`, and the
+        // next block of B is packed by the same threads
+        drop(sender);
+        for _ in 0..jobs {
+            receiver.recv().unwrap();
+        }
+    }
+
+    ThreadPool::terminate(pool);
+}
+
+/// Returns the number of elements of the buffers `packed_gemm` holds at once for a
+/// `rows` x `inner` by `inner` x `cols` product, or `None` if it overflows
+///
+/// These are the panels of a `kc` x `nc` block of B and, for each of the `threads`, a
+/// block of A and a micro-kernel tile, all padded to whole `MR` x `NR` panels.
+pub(crate) fn packing_buffer_len<T: Element>(
+    rows: usize,
+    inner: usize,
+    cols: usize,
+    threads: usize,
+    block_sizes: BlockSizes,
+    level: SimdLevel,
+) -> Option<usize> {
+    let (nr, _) = packed_kernel::<T>(level);
+    let kc = block_sizes.kc.min(inner);
+    let mc = block_sizes.mc.div_ceil(MR) * MR;
+    let nc = block_sizes.nc.div_ceil(nr) * nr;
+
+    let b_packed = cols.min(nc).div_ceil(nr) * nr * kc;
+    let a_packed = rows.min(mc).div_ceil(MR) * MR * kc;
+    let per_thread = a_packed.checked_add(MR * nr)?;
+
+    per_thread
+        .checked_mul(threads.max(1))?
+        .checked_add(b_packed)
+}
+
+fn row_pointers<T>(rows: &mut [Vec<T>]) -> Vec<MatrixRowMutPtr<T>> {
+    rows.iter_mut()
+        .map(|row| MatrixRowMutPtr(row.as_mut_ptr()))
+        .collect()
+}
+
+/// Packs the block `depth` x `cols` of `b` into `nr` wide panels, padded with zeros
+///
+/// Panel `q` holds columns `cols.start + q * nr..cols.start + (q + 1) * nr`, stored `nr`
+/// values per row.
+fn pack_b<T: Element>(b: Operand<T>, depth: Range<usize>, cols: Range<usize>, nr: usize) -> Vec<T> {
+    let mut packed = vec![T::zero(); cols.len().div_ceil(nr) * depth.len() * nr];
+    pack_b_into(b, depth, cols, nr, &mut packed);
+    packed
+}
+
+/// Packs the block `depth` x `cols` of `b` like `pack_b`, with one job per thread each
+/// packing a share of the panels
+fn pack_b_parallel<T: Element>(
+    pool: &ThreadPool,
+    b: Operand<T>,
+    depth: Range<usize>,
+    cols: Range<usize>,
+    nr: usize,
+    threads: usize,
+) -> Vec<T> {
+    let kc = depth.len();
+    let panels_per_job = cols.len().div_ceil(nr).div_ceil(threads);
+    let mut packed = vec![T::zero(); cols.len().div_ceil(nr) * kc * nr];
+    let (sender, receiver) = mpsc::channel();
+    let mut jobs = 0;
+
+    for (index, chunk) in packed.chunks_mut(panels_per_job * kc * nr).enumerate() {
+        let start = cols.start + index * panels_per_job * nr;
+        let job_cols = start..(start + panels_per_job * nr).min(cols.end);
+        let (chunk, len) = (MatrixRowMutPtr(chunk.as_mut_ptr()), chunk.len());
+        let b_matrix = SharedPtr::new(b.matrix);
+        let b_transpose = b.transpose;
+        let depth = depth.clone();
+        let sender = sender.clone();
+
+        // SAFETY: `b` and `packed` outlive the job, which is waited for below, and the
+        // jobs write disjoint chunks of `packed`
+        pool.execute(move || unsafe {
+            // moves the whole `Send` wrapper into the job rather than its raw pointer
+            let chunk = chunk;
+            let b = Operand {
+                matrix: b_matrix.get(),
+                transpose: b_transpose,
+            };
+            let packed = std::slice::from_raw_parts_mut(chunk.0, len);
+            pack_b_into(b, depth, job_cols, nr, packed);
+            sender.send(()).unwrap();
+        });
+        jobs += 1;
+    }
+
+    drop(sender);
+    for _ in 0..jobs {
+        receiver.recv().unwrap();
+    }
+
+    packed
+}
+
+fn pack_b_into<T: Element>(
+    b: Operand<T>,
+    depth: Range<usize>,
+    cols: Range<usize>,
+    nr: usize,
+    packed: &mut [T],
+) {
+    let kc = depth.len();
+
+    match b.transpose {
+        Transpose::No => {
+            for (p, b_row) in b.matrix[depth].iter().enumerate() {
+                for (j, &value) in b_row[cols.clone()].iter().enumerate() {
+                    packed[(j / nr) * kc * nr + p * nr + j % nr] = value;
+                }
+            }
+        }
+        Transpose::Yes => {
+            for (j, b_col) in b.matrix[cols].iter().enumerate() {
+                let panel = &mut packed[(j / nr) * kc * nr..];
+                for (p, &value) in b_col[depth.clone()].iter().enumerate() {
+                    panel[p * nr + j % nr] = value;
+                }
+            }
+        }
+    }
+}
+
+/// Packs the block `rows` x `depth` of `a` into `MR` tall panels, padded with zeros
+///
+/// Panel `p` holds rows `p * MR..(p + 1) * MR` of the block, stored `MR` values per
+/// column.
+fn pack_a<T: Element>(a: Operand<T>, rows: Range<usize>, depth: Range<usize>) -> Vec<T> {
+    let kc = depth.len();
+    let mut packed = vec![T::zero(); rows.len().div_ceil(MR) * kc * MR];
+
+    match a.transpose {
+        Transpose::No => {
+            for (i, a_row) in a.matrix[rows].iter().enumerate() {
+                let panel = &mut packed[(i / MR) * kc * MR..];
+                for (p, &value) in a_row[depth.clone()].iter().enumerate() {
+                    panel[p * MR + i % MR] = value;
+                }
+            }
+        }
+        Transpose::Yes => {
+            for (p, a_col) in a.matrix[depth].iter().enumerate() {
+                for (i, &value) in a_col[rows.clone()].iter().enumerate() {
+                    packed[(i / MR) * kc * MR + p * MR + i % MR] = value;
+                }
+            }
+        }
+    }
+
+    packed
+}
+
+/// Adds `alpha` times the product of the `block` of A with the packed panels of B to
+/// `c_rows`
+///
+/// # Safety
+///
+/// `c_rows` must point to the rows `block.rows` of `C`, of which no other thread may
+/// access the columns `block.cols`, and `kernel` must be supported by the running CPU.
+unsafe fn compute_block<T: Element>(
+    a: Operand<T>,
+    b_packed: &[T],
+    alpha: T,
+    c_rows: &mut [MatrixRowMutPtr<T>],
+    block: &Block,
+    nr: usize,
+    kernel: PackedKernel<T>,
+) {
+    let kc = block.depth.len();
+    let a_packed = pack_a(a, block.rows.clone(), block.depth.clone());
+    let mut tile = vec![T::zero(); MR * nr];
+
+    for jr in block.cols.clone().step_by(nr) {
+        let b_panel = &b_packed[((jr - block.cols.start) / nr) * kc * nr..][..kc * nr];
+        let width = nr.min(block.cols.end - jr);
+
+        for (a_panel, c_panel) in a_packed.chunks_exact(kc * MR).zip(c_rows.chunks_mut(MR)) {
+            kernel(kc, a_panel.as_ptr(), b_panel.as_ptr(), tile.as_mut_ptr());
+
+            for (c_row, tile_row) in c_panel.iter_mut().zip(tile.chunks_exact(nr)) {
+                for (offset, &value) in tile_row[..width].iter().enumerate() {
+                    *c_row.add(jr + offset) += alpha * value;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matrix_multiplication::{
+        generate::{Distribution, MatrixGenerator},
+        matrix_multiplication_sequential_ikj,
+    };
+
+    fn get_operands<T: Element>(rows: usize, inner: usize, cols: usize) -> [Vec<Vec<T>>; 2] {
+        let mut generator = MatrixGenerator::new(Some(34), Distribution::default());
+
+        [
+            generator.generate(rows, inner),
+            generator.generate(inner, cols),
+        ]
+    }
+
+    fn assert_close(actual: &[Vec<f64>], expected: &[Vec<f64>]) {
+        for (actual_row, expected_row) in actual.iter().zip(expected) {
+            for (&x, &y) in actual_row.iter().zip(expected_row) {
+                assert!(x.approx_eq(y, 1e-9), "{} != {}", x, y);
+            }
+        }
+    }
+
+    fn operand<T>(matrix: &[Vec<T>], transpose: Transpose) -> Operand<'_, T> {
+        Operand { matrix, transpose }
+    }
+
+    #[test]
+    fn test_pack_b() {
+        let b = vec![vec![1, 2, 3], vec![4, 5, 6]];
+        let b_t = vec![vec![1, 4], vec![2, 5], vec![3, 6]];
+
+        for b in [operand(&b, Transpose::No), operand(&b_t, Transpose::Yes)] {
+            assert_eq!(pack_b(b, 0..2, 0..3, 2), vec![1, 2, 4, 5, 3, 0, 6, 0]);
+            assert_eq!(pack_b(b, 1..2, 0..3, 4), vec![4, 5, 6, 0]);
+            assert_eq!(pack_b(b, 0..2, 1..3, 1), vec![2, 5, 3, 6]);
+
+            let pool = ThreadPool::new(2);
+            assert_eq!(
+                pack_b_parallel(&pool, b, 0..2, 0..3, 1, 2),
+                vec![1, 4, 2, 5, 3, 6]
+            );
+            ThreadPool::terminate(pool);
+        }
+    }
+
+    #[test]
+    fn test_pack_a() {
+        let a: Vec<Vec<i32>> = (0..5).map(|i| vec![i, 10 + i]).collect();
+        let a_t = vec![(0..5).collect(), (10..15).collect()];
+
+        for a in [operand(&a, Transpose::No), operand(&a_t, Transpose::Yes)] {
+            assert_eq!(
+                pack_a(a, 0..5, 0..2),
+                vec![0, 1, 2, 3, 10, 11, 12, 13, 4, 0, 0, 0, 14, 0, 0, 0]
+            );
+            assert_eq!(pack_a(a, 1..3, 1..2), vec![11, 12, 0, 0]);
+        }
+    }
+
+    #[test]
+    fn test_packing_buffer_len() {
+        let block_sizes = BlockSizes {
+            mc: 6,
+            kc: 2,
+            nc: 4,
+        };
+        let len = |rows, inner, threads| {
+            packing_buffer_len::<f64>(rows, inner, 6, threads, block_sizes, SimdLevel::Scalar)
+        };
+
+        // B: 1 panel of 2x4, A: 2 panels of 2x4 and a 4x4 tile per thread
+        assert_eq!(len(5, 3, 1), Some(8 + 16 + 16));
+        assert_eq!(len(5, 3, 2), Some(8 + 2 * (16 + 16)));
+        assert_eq!(len(1, 1, 1), Some(4 + 4 + 16));
+        assert_eq!(
+            packing_buffer_len::<f64>(1, 1, 6, usize::MAX, block_sizes, SimdLevel::Scalar),
+            None
+        );
+    }
+
+    #[test]
+    fn test_packed_matches_ikj() {
+        let block_sizes = BlockSizes {
+            mc: 6,
+            kc: 5,
+            nc: 9,
+        };
+
+        for (rows, inner, cols) in [(1, 1, 1), (7, 13, 33), (20, 11, 5)] {
+            let [a, b] = get_operands::<i64>(rows, inner, cols);
+            let expected = matrix_multiplication_sequential_ikj(&a, &b).unwrap();
+            for threads in [1, 3] {
+                assert_eq!(
+                    matrix_multiplication_packed_with(
+                        &a,
+                        &b,
+                        threads,
+                        block_sizes,
+                        SimdLevel::Scalar
+                    ),
+                    Some(expected.clone())
+                );
+            }
+
+            let [a, b] = get_operands::<f64>(rows, inner, cols);
+            let expected = matrix_multiplication_sequential_ikj(&a, &b).unwrap();
+            for level in SimdLevel::ALL.into_iter().filter(|l| l.is_available()) {
+                let c = matrix_multiplication_packed_with(&a, &b, 3, block_sizes, level);
+                assert_close(&c.unwrap(), &expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_packed_default_block_sizes() {
+        let [a, b] = get_operands::<f32>(70, 300, 45);
+        let expected = matrix_multiplication_sequential_ikj(&a, &b).unwrap();
+
+        let c = matrix_multiplication_packed(&a, &b, 2).unwrap();
+
+        for (c_row, expected_row) in c.iter().zip(&expected) {
+            for (&x, &y) in c_row.iter().zip(expected_row) {
+                assert!(x.approx_eq(y, 1e-4), "{} != {}", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn test_packed_invalid_operands() {
+        assert!(matrix_multiplication_packed(&[vec![1]], &[vec![1], vec![2]], 2).is_none());
+    }
+}
@@ -0,0 +1,297 @@
+use std::fmt;
+use std::ops::Range;
+use std::sync::mpsc;
+
+use clap::ValueEnum;
+use log::error;
+
+use super::{
+    element::Element,
+    generate::generate_zero_matrix,
+    matrix_multiplication_parallel_i_loop,
+    sanitize::{sanitize_product, SanitizeResult},
+    types::{MatrixRowMutPtr, SharedPtr},
+};
+use crate::thread_pool::ThreadPool;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+/// Enum to represent how the parallel kernels split the product into jobs
+pub enum Partition {
+    /// One job per row of C
+    Rows,
+    /// One job per contiguous block of rows of C, one block per thread
+    RowBlocks,
+    /// One job per 2D tile of C, on a grid with one tile per thread
+    Tiles,
+    /// One job per slice of the inner dimension, each computing a partial product,
+    /// followed by a reduction of the partial products
+    KSplit,
+}
+
+impl Partition {
+    /// Every partition, in the order of increasing job size
+    pub const ALL: [Partition; 4] = [
+        Partition::Rows,
+        Partition::RowBlocks,
+        Partition::Tiles,
+        Partition::KSplit,
+    ];
+}
+
+impl fmt::Display for Partition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Partition::Rows => "rows",
+            Partition::RowBlocks => "row-blocks",
+            Partition::Tiles => "tiles",
+            Partition::KSplit => "k-split",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Splits `0..len` into at most `parts` contiguous non-empty ranges whose lengths differ
+/// by at most one
+pub fn split_evenly(len: usize, parts: usize) -> Vec<Range<usize>> {
+    let parts = parts.clamp(1, len.max(1));
+    let (size, remainder) = (len / parts, len % parts);
+    let mut start = 0;
+
+    (0..parts)
+        .map(|part| {
+            let end = start + size + usize::from(part < remainder);
+            let range = start..end;
+            start = end;
+            range
+        })
+        .filter(|range| !range.is_empty())
+        .collect()
+}
+
+/// Splits `threads` into a `rows` x `cols` grid as close to square as possible, with
+/// more tiles along the longer side of C
+pub(crate) fn tile_grid(threads: usize, rows: usize, cols: usize) -> (usize, usize) {
+    let short = (1..=threads)
+        .take_while(|d| d * d <= threads)
+        .filter(|&d| threads.is_multiple_of(d))
+        .last()
+        .unwrap_or(1);
+
+    match rows >= cols {
+        true => (threads / short, short),
+        false => (short, threads / short),
+    }
+}
+
+/// Multiplies the matrices on `threads` threads, splitting the work as `partition` says
+///
+/// # Arguments
+///
+/// * `a` - The first matrix
+/// * `b` - The second matrix
+/// * `threads` - The number of threads to use, which is also the number of jobs for
+///   every partition but `Partition::Rows`
+/// * `partition` - How to split the product into jobs
+///
+/// # Returns
+///
+/// The product of the matrices, or `None` if they can't be multiplied
+pub fn matrix_multiplication_partitioned<T: Element>(
+    a: &[Vec<T>],
+    b: &[Vec<T>],
+    threads: usize,
+    partition: Partition,
+) -> Option<Vec<Vec<T>>> {
+    match sanitize_product(a, b) {
+        SanitizeResult::Ok => (),
+        SanitizeResult::NotOk(error) => {
+            error!("Error: {:?}", error);
+            return None;
+        }
+    };
+
+    let (rows, inner, cols) = (a.len(), b.len(), b[0].len());
+    let threads = threads.max(1);
+
+    match partition {
+        Partition::Rows => matrix_multiplication_parallel_i_loop(a, b, threads),
+        Partition::RowBlocks => {
+            let blocks = split_evenly(rows, threads)
+                .into_iter()
+                .map(|block| (block, 0..cols))
+                .collect();
+            Some(multiply_blocks(a, b, threads, blocks))
+        }
+        Partition::Tiles => {
+            let (grid_rows, grid_cols) = tile_grid(threads, rows, cols);
+            let row_blocks = split_evenly(rows, grid_rows);
+            let col_blocks = split_evenly(cols, grid_cols);
+            let tiles = row_blocks
+                .iter()
+                .flat_map(|block| col_blocks.iter().map(|cols| (block.clone(), cols.clone())))
+                .collect();
+            Some(multiply_blocks(a, b, threads, tiles))
+        }
+        Partition::KSplit => Some(multiply_k_split(
+            a,
+            b,
+            threads,
+            split_evenly(inner, threads),
+        )),
+    }
+}
+
+/// Computes every `(rows, cols)` block of C in its own job
+///
+/// The blocks must not overlap.
+fn multiply_blocks<T: Element>(
+    a: &[Vec<T>],
+    b: &[Vec<T>],
+    threads: usize,
+    blocks: Vec<(Range<usize>, Range<usize>)>,
+) -> Vec<Vec<T>> {
+    let mut c = generate_zero_matrix(a.len(), b[0].len());
+
+    let pool = ThreadPool::new(threads);
+
+    for (rows, cols) in blocks {
+        let a = SharedPtr::new(a);
+        let b = SharedPtr::new(b);
+        let mut c_rows: Vec<MatrixRowMutPtr<T>> = c[rows.clone()]
+            .iter_mut()
+            .map(|row| MatrixRowMutPtr(row.as_mut_ptr()))
+            .collect();
+
+        // SAFETY: the operands outlive the pool, and the jobs write disjoint blocks of `c`
+        pool.execute(move || unsafe {
+            let (a, b) = (a.get(), b.get());
+            for (c_row, a_row) in c_rows.iter_mut().zip(&a[rows]) {
+                for (&a_ik, b_row) in a_row.iter().zip(b) {
+                    for j in cols.clone() {
+                        *c_row.add(j) += a_ik * b_row[j];
+                    }
+                }
+            }
+        });
+    }
+
+    ThreadPool::terminate(pool);
+
+    c
+}
+
+/// Computes the partial product of every slice of the inner dimension in its own job,
+/// then sums the partial products in slice order
+fn multiply_k_split<T: Element>(
+    a: &[Vec<T>],
+    b: &[Vec<T>],
+    threads: usize,
+    slices: Vec<Range<usize>>,
+) -> Vec<Vec<T>> {
+    let (rows, cols) = (a.len(), b[0].len());
+    let (sender, receiver) = mpsc::channel();
+
+    let pool = ThreadPool::new(threads);
+
+    for (index, depth) in slices.into_iter().enumerate() {
+        let a = SharedPtr::new(a);
+        let b = SharedPtr::new(b);
+        let sender = sender.clone();
+
+        // SAFETY: the operands outlive the pool
+        pool.execute(move || unsafe {
+            let (a, b) = (a.get(), b.get());
+            let mut partial: Vec<Vec<T>> = generate_zero_matrix(rows, cols);
+            for (partial_row, a_row) in partial.iter_mut().zip(a) {
+                for k in depth.clone() {
+                    let a_ik = a_row[k];
+                    for (value, &b_kj) in partial_row.iter_mut().zip(&b[k]) {
+                        *value += a_ik * b_kj;
+                    }
+                }
+            }
+            sender.send((index, partial)).unwrap();
+        });
+    }
+
+    ThreadPool::terminate(pool);
+    drop(sender);
+
+    // sum in slice order, so the result doesn't depend on the order the jobs finished in
+    let mut partials: Vec<(usize, Vec<Vec<T>>)> = receiver.into_iter().collect();
+    partials.sort_by_key(|&(index, _)| index);
+
+    let mut partials = partials.into_iter().map(|(_, partial)| partial);
+    let mut c = partials.next().unwrap();
+    for partial in partials {
+        for (c_row, partial_row) in c.iter_mut().zip(partial) {
+            for (value, partial_value) in c_row.iter_mut().zip(partial_row) {
+                *value += partial_value;
+            }
+        }
+    }
+
+    c
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matrix_multiplication::{
+        generate::{Distribution, MatrixGenerator},
+        matrix_multiplication_sequential_ikj,
+    };
+
+    #[test]
+    fn test_split_evenly() {
+        assert_eq!(split_evenly(10, 3), vec![0..4, 4..7, 7..10]);
+        assert_eq!(split_evenly(2, 4), vec![0..1, 1..2]);
+        assert_eq!(split_evenly(5, 1), vec![0..5]);
+        assert_eq!(split_evenly(0, 3), vec![]);
+    }
+
+    #[test]
+    fn test_tile_grid() {
+        assert_eq!(tile_grid(4, 10, 10), (2, 2));
+        assert_eq!(tile_grid(6, 100, 10), (3, 2));
+        assert_eq!(tile_grid(6, 10, 100), (2, 3));
+        assert_eq!(tile_grid(5, 10, 10), (5, 1));
+        assert_eq!(tile_grid(1, 10, 10), (1, 1));
+    }
+
+    #[test]
+    fn test_partitions_match_ikj() {
+        let mut generator = MatrixGenerator::new(Some(36), Distribution::default());
+
+        for (rows, inner, cols) in [(1, 1, 1), (3, 7, 2), (13, 9, 17)] {
+            let a: Vec<Vec<i64>> = generator.generate(rows, inner);
+            let b: Vec<Vec<i64>> = generator.generate(inner, cols);
+            let expected = matrix_multiplication_sequential_ikj(&a, &b).unwrap();
+
+            for partition in Partition::ALL {
+                for threads in [1, 4, 6, 32] {
+                    assert_eq!(
+                        matrix_multiplication_partitioned(&a, &b, threads, partition).as_ref(),
+                        Some(&expected),
+                        "{} on {} threads",
+                        partition,
+                        threads
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_partitioned_invalid_operands() {
+        for partition in Partition::ALL {
+            assert!(matrix_multiplication_partitioned(
+                &[vec![1]],
+                &vec![vec![1, 2]; 2],
+                2,
+                partition
+            )
+            .is_none());
+        }
+    }
+}
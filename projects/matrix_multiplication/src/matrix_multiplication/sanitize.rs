@@ -1,11 +1,52 @@
+use std::error::Error;
+use std::fmt;
+
 #[derive(Debug, PartialEq)]
 /// Enum to represent the errors that can occur during the sanitization of the matrices
 pub enum SanitizeError {
     EmptyMatrix(String),
     NotSquareMatrix(String),
     NotSameSize,
+    /// The rows of the matrix don't all have the same length
+    RaggedMatrix(String),
+    /// The number of columns of A differs from the number of rows of B
+    NotConformable {
+        a_cols: usize,
+        b_rows: usize,
+    },
+    /// The output matrix doesn't have the shape of the product
+    OutputSizeMismatch {
+        expected: (usize, usize),
+        actual: (usize, usize),
+    },
+    /// The matrix has no inverse, detected by a vanishing pivot
+    SingularMatrix(String),
 }
 
+impl fmt::Display for SanitizeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SanitizeError::EmptyMatrix(name) => write!(f, "matrix {} is empty", name),
+            SanitizeError::NotSquareMatrix(name) => write!(f, "matrix {} is not square", name),
+            SanitizeError::NotSameSize => write!(f, "matrices are not the same size"),
+            SanitizeError::RaggedMatrix(name) => {
+                write!(f, "rows of matrix {} have different lengths", name)
+            }
+            SanitizeError::NotConformable { a_cols, b_rows } => {
+                write!(f, "A has {} columns but B has {} rows", a_cols, b_rows)
+            }
+            SanitizeError::OutputSizeMismatch { expected, actual } => write!(
+                f,
+                "output is {}x{} but the product is {}x{}",
+                actual.0, actual.1, expected.0, expected.1
+            ),
+            SanitizeError::SingularMatrix(name) => write!(f, "matrix {} is singular", name),
+        }
+    }
+}
+
+impl Error for SanitizeError {}
+
 #[derive(Debug, PartialEq)]
 /// Enum to represent the result of the sanitization of the matrices
 ///
@@ -21,13 +62,13 @@ pub enum SanitizeResult {
     NotOk(SanitizeError),
 }
 
-fn is_matrix_square(a: &Vec<Vec<i32>>, matrix_name: &str) -> SanitizeResult {
+pub(crate) fn is_matrix_square<T>(a: &[Vec<T>], matrix_name: &str) -> SanitizeResult {
     if a.is_empty() {
         return SanitizeResult::NotOk(SanitizeError::EmptyMatrix(matrix_name.to_string()));
     }
 
     let row_length = a.len();
-    
+
     let mut is_square = true;
 
     for row in a {
@@ -43,7 +84,20 @@ fn is_matrix_square(a: &Vec<Vec<i32>>, matrix_name: &str) -> SanitizeResult {
     }
 }
 
-fn are_square_matrices_same_size(a: &Vec<Vec<i32>>, b: &Vec<Vec<i32>>) -> bool {
+pub(crate) fn is_matrix_rectangular<T>(a: &[Vec<T>], matrix_name: &str) -> SanitizeResult {
+    if a.is_empty() || a[0].is_empty() {
+        return SanitizeResult::NotOk(SanitizeError::EmptyMatrix(matrix_name.to_string()));
+    }
+
+    let row_length = a[0].len();
+
+    match a.iter().all(|row| row.len() == row_length) {
+        true => SanitizeResult::Ok,
+        false => SanitizeResult::NotOk(SanitizeError::RaggedMatrix(matrix_name.to_string())),
+    }
+}
+
+fn are_square_matrices_same_size<T>(a: &[Vec<T>], b: &[Vec<T>]) -> bool {
     a.len() == b.len()
 }
 
@@ -57,7 +111,7 @@ fn are_square_matrices_same_size(a: &Vec<Vec<i32>>, b: &Vec<Vec<i32>>) -> bool {
 /// # Returns
 ///
 /// A `SanitizeResult` enum
-pub fn sanitize_matrices(a: &Vec<Vec<i32>>, b: &Vec<Vec<i32>>) -> SanitizeResult {
+pub fn sanitize_matrices<T>(a: &[Vec<T>], b: &[Vec<T>]) -> SanitizeResult {
     match is_matrix_square(a, "A") {
         SanitizeResult::Ok => (),
         SanitizeResult::NotOk(e) => return SanitizeResult::NotOk(e),
@@ -74,6 +128,39 @@ pub fn sanitize_matrices(a: &Vec<Vec<i32>>, b: &Vec<Vec<i32>>) -> SanitizeResult
     }
 }
 
+/// Sanitizes the operands of the product `a * b`
+///
+/// Unlike `sanitize_matrices`, the matrices may be rectangular as long as the number
+/// of columns of `a` matches the number of rows of `b`.
+///
+/// # Arguments
+///
+/// * `a` - The first matrix
+/// * `b` - The second matrix
+///
+/// # Returns
+///
+/// A `SanitizeResult` enum
+pub fn sanitize_product<T>(a: &[Vec<T>], b: &[Vec<T>]) -> SanitizeResult {
+    match is_matrix_rectangular(a, "A") {
+        SanitizeResult::Ok => (),
+        SanitizeResult::NotOk(e) => return SanitizeResult::NotOk(e),
+    };
+
+    match is_matrix_rectangular(b, "B") {
+        SanitizeResult::Ok => (),
+        SanitizeResult::NotOk(e) => return SanitizeResult::NotOk(e),
+    };
+
+    match a[0].len() == b.len() {
+        true => SanitizeResult::Ok,
+        false => SanitizeResult::NotOk(SanitizeError::NotConformable {
+            a_cols: a[0].len(),
+            b_rows: b.len(),
+        }),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -133,8 +220,8 @@ mod tests {
         let b = get_3x3();
         let c = get_2x2();
 
-        assert_eq!(are_square_matrices_same_size(&a, &b), true);
-        assert_eq!(are_square_matrices_same_size(&a, &c), false);
+        assert!(are_square_matrices_same_size(&a, &b));
+        assert!(!are_square_matrices_same_size(&a, &c));
     }
 
     #[test]
@@ -149,4 +236,36 @@ mod tests {
             SanitizeResult::NotOk(SanitizeError::NotSameSize)
         );
     }
+
+    #[test]
+    fn test_is_matrix_rectangular() {
+        assert_eq!(is_matrix_rectangular(&get_3x2(), "A"), SanitizeResult::Ok);
+        assert_eq!(
+            is_matrix_rectangular(&[Vec::<i32>::new()], "A"),
+            SanitizeResult::NotOk(SanitizeError::EmptyMatrix("A".to_string()))
+        );
+        assert_eq!(
+            is_matrix_rectangular(&get_row_of_different_length(), "B"),
+            SanitizeResult::NotOk(SanitizeError::RaggedMatrix("B".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_sanitize_product() {
+        let a = get_3x2();
+        let b = get_2x2();
+
+        assert_eq!(sanitize_product(&a, &b), SanitizeResult::Ok);
+        assert_eq!(
+            sanitize_product(&b, &a),
+            SanitizeResult::NotOk(SanitizeError::NotConformable {
+                a_cols: 2,
+                b_rows: 3
+            })
+        );
+        assert_eq!(
+            sanitize_product(&get_empty(), &b),
+            SanitizeResult::NotOk(SanitizeError::EmptyMatrix("A".to_string()))
+        );
+    }
 }
@@ -0,0 +1,390 @@
+use std::any::TypeId;
+use std::fmt;
+
+use log::error;
+
+use super::{
+    element::Element,
+    generate::generate_zero_matrix,
+    sanitize::{sanitize_product, SanitizeResult},
+};
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+mod x86;
+
+/// Number of rows of `C` computed at once by every micro-kernel
+pub const MR: usize = 4;
+/// Number of columns of `C` computed at once by the scalar micro-kernel
+pub const SCALAR_NR: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+/// Enum to represent the instruction sets the micro-kernel can use
+pub enum SimdLevel {
+    /// Portable Rust, left to the compiler to vectorise
+    Scalar,
+    /// 128-bit SSE2 vectors
+    Sse2,
+    /// 256-bit AVX2 vectors
+    Avx2,
+    /// 512-bit AVX-512 vectors
+    Avx512,
+}
+
+impl SimdLevel {
+    /// Every level, from the least to the most capable
+    pub const ALL: [SimdLevel; 4] = [
+        SimdLevel::Scalar,
+        SimdLevel::Sse2,
+        SimdLevel::Avx2,
+        SimdLevel::Avx512,
+    ];
+
+    /// The most capable level supported by the running CPU
+    pub fn detect() -> SimdLevel {
+        SimdLevel::ALL
+            .into_iter()
+            .rev()
+            .find(|level| level.is_available())
+            .unwrap()
+    }
+
+    /// Whether the running CPU supports the level
+    pub fn is_available(self) -> bool {
+        match self {
+            SimdLevel::Scalar => true,
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            SimdLevel::Sse2 => is_x86_feature_detected!("sse2"),
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            SimdLevel::Avx2 => is_x86_feature_detected!("avx2"),
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            SimdLevel::Avx512 => is_x86_feature_detected!("avx512f"),
+            #[cfg(not(any(target_arch = "x86", target_arch = "x86_64")))]
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for SimdLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            SimdLevel::Scalar => "scalar",
+            SimdLevel::Sse2 => "SSE2",
+            SimdLevel::Avx2 => "AVX2",
+            SimdLevel::Avx512 => "AVX-512",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Multiplies the matrices with a register-blocked micro-kernel
+///
+/// `C` is computed by blocks of `MR` rows and as many columns as the vector registers of
+/// `level` hold, keeping the block in registers for the whole `k` loop. Cells outside
+/// the full blocks are computed one at a time. `f32` and `f64` use the vector
+/// instructions of `level`, other element types always use the scalar micro-kernel.
+///
+/// Every cell is accumulated in increasing `k` order without fused multiply-add, so the
+/// result is identical to `matrix_multiplication_sequential_ikj`.
+///
+/// # Arguments
+///
+/// * `a` - The first matrix
+/// * `b` - The second matrix
+/// * `level` - The instruction set to use
+///
+/// # Returns
+///
+/// The product of the matrices, or `None` if they can't be multiplied
+///
+/// # Panics
+///
+/// Panics if the running CPU doesn't support `level`.
+pub fn matrix_multiplication_simd<T: Element>(
+    a: &[Vec<T>],
+    b: &[Vec<T>],
+    level: SimdLevel,
+) -> Option<Vec<Vec<T>>> {
+    match sanitize_product(a, b) {
+        SanitizeResult::Ok => (),
+        SanitizeResult::NotOk(error) => {
+            error!("Error: {:?}", error);
+            return None;
+        }
+    };
+
+    assert!(
+        level.is_available(),
+        "{} is not supported by this CPU",
+        level
+    );
+
+    let mut c = generate_zero_matrix(a.len(), b[0].len());
+
+    if TypeId::of::<T>() == TypeId::of::<f64>() {
+        // SAFETY: T is f64
+        let (a, b, c) = unsafe { (cast(a), cast(b), cast_mut(&mut c)) };
+        multiply_f64(a, b, c, level);
+    } else if TypeId::of::<T>() == TypeId::of::<f32>() {
+        // SAFETY: T is f32
+        let (a, b, c) = unsafe { (cast(a), cast(b), cast_mut(&mut c)) };
+        multiply_f32(a, b, c, level);
+    } else {
+        multiply_blocks(a, b, &mut c, SCALAR_NR, scalar_kernel::<T, SCALAR_NR>);
+    }
+
+    Some(c)
+}
+
+/// Reinterprets a matrix of `T` as a matrix of `U`
+///
+/// # Safety
+///
+/// `T` and `U` must be the same type.
+unsafe fn cast<T, U>(matrix: &[Vec<T>]) -> &[Vec<U>] {
+    &*(matrix as *const [Vec<T>] as *const [Vec<U>])
+}
+
+/// Reinterprets a mutable matrix of `T` as a mutable matrix of `U`
+///
+/// # Safety
+///
+/// `T` and `U` must be the same type.
+unsafe fn cast_mut<T, U>(matrix: &mut [Vec<T>]) -> &mut [Vec<U>] {
+    &mut *(matrix as *mut [Vec<T>] as *mut [Vec<U>])
+}
+
+fn multiply_f64(a: &[Vec<f64>], b: &[Vec<f64>], c: &mut [Vec<f64>], level: SimdLevel) {
+    match level {
+        // SAFETY: the caller checked that the CPU supports `level`
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        SimdLevel::Sse2 => multiply_blocks(a, b, c, 4, |a, b, c, i, j| unsafe {
+            x86::sse2_f64(a, b, c, i, j)
+        }),
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        SimdLevel::Avx2 => multiply_blocks(a, b, c, 8, |a, b, c, i, j| unsafe {
+            x86::avx2_f64(a, b, c, i, j)
+        }),
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        SimdLevel::Avx512 => multiply_blocks(a, b, c, 16, |a, b, c, i, j| unsafe {
+            x86::avx512_f64(a, b, c, i, j)
+        }),
+        _ => multiply_blocks(a, b, c, SCALAR_NR, scalar_kernel::<f64, SCALAR_NR>),
+    }
+}
+
+fn multiply_f32(a: &[Vec<f32>], b: &[Vec<f32>], c: &mut [Vec<f32>], level: SimdLevel) {
+    match level {
+        // SAFETY: the caller checked that the CPU supports `level`
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        SimdLevel::Sse2 => multiply_blocks(a, b, c, 8, |a, b, c, i, j| unsafe {
+            x86::sse2_f32(a, b, c, i, j)
+        }),
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        SimdLevel::Avx2 => multiply_blocks(a, b, c, 16, |a, b, c, i, j| unsafe {
+            x86::avx2_f32(a, b, c, i, j)
+        }),
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        SimdLevel::Avx512 => multiply_blocks(a, b, c, 32, |a, b, c, i, j| unsafe {
+            x86::avx512_f32(a, b, c, i, j)
+        }),
+        _ => multiply_blocks(a, b, c, SCALAR_NR, scalar_kernel::<f32, SCALAR_NR>),
+    }
+}
+
+/// Calls `kernel` on every full `MR` x `nr` block of `c`, then computes the remaining
+/// cells one at a time
+fn multiply_blocks<T: Element>(
+    a: &[Vec<T>],
+    b: &[Vec<T>],
+    c: &mut [Vec<T>],
+    nr: usize,
+    kernel: impl Fn(&[Vec<T>], &[Vec<T>], &mut [Vec<T>], usize, usize),
+) {
+    let (rows, cols) = (c.len(), b[0].len());
+    let (full_rows, full_cols) = (rows - rows % MR, cols - cols % nr);
+
+    for i in (0..full_rows).step_by(MR) {
+        for j in (0..full_cols).step_by(nr) {
+            kernel(a, b, c, i, j);
+        }
+    }
+
+    for (i, c_row) in c.iter_mut().enumerate() {
+        let first_col = match i < full_rows {
+            true => full_cols,
+            false => 0,
+        };
+        for (j, value) in c_row.iter_mut().enumerate().skip(first_col) {
+            *value = a[i]
+                .iter()
+                .zip(b)
+                .fold(T::zero(), |sum, (&a_ik, b_row)| sum + a_ik * b_row[j]);
+        }
+    }
+}
+
+/// Portable micro-kernel computing the `MR` x `NR` block of `c` at `(i, j)`
+fn scalar_kernel<T: Element, const NR: usize>(
+    a: &[Vec<T>],
+    b: &[Vec<T>],
+    c: &mut [Vec<T>],
+    i: usize,
+    j: usize,
+) {
+    let mut accumulators = [[T::zero(); NR]; MR];
+
+    for (k, b_row) in b.iter().enumerate() {
+        let b_block: &[T; NR] = b_row[j..j + NR].try_into().unwrap();
+        for (accumulator, a_row) in accumulators.iter_mut().zip(&a[i..i + MR]) {
+            let a_ik = a_row[k];
+            for (sum, &b_kj) in accumulator.iter_mut().zip(b_block) {
+                *sum += a_ik * b_kj;
+            }
+        }
+    }
+
+    for (accumulator, c_row) in accumulators.iter().zip(&mut c[i..i + MR]) {
+        c_row[j..j + NR].copy_from_slice(accumulator);
+    }
+}
+
+/// Micro-kernel of the packed GEMM, computing the `MR` x `NR` tile `a * b` of packed
+/// panels of depth `kc`
+///
+/// `a` holds `MR` values per `k`, `b` holds `NR` values per `k` and the tile is written
+/// row-major, overwriting its previous content.
+pub(crate) type PackedKernel<T> = unsafe fn(kc: usize, a: *const T, b: *const T, tile: *mut T);
+
+/// Selects the packed micro-kernel for `T` and `level`
+///
+/// # Returns
+///
+/// The width `NR` of the tiles and the micro-kernel, which may only be called if the
+/// running CPU supports `level`
+pub(crate) fn packed_kernel<T: Element>(level: SimdLevel) -> (usize, PackedKernel<T>) {
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    {
+        if TypeId::of::<T>() == TypeId::of::<f64>() {
+            let kernel: Option<(usize, PackedKernel<f64>)> = match level {
+                SimdLevel::Sse2 => Some((4, x86::packed_sse2_f64)),
+                SimdLevel::Avx2 => Some((8, x86::packed_avx2_f64)),
+                SimdLevel::Avx512 => Some((16, x86::packed_avx512_f64)),
+                SimdLevel::Scalar => None,
+            };
+            if let Some((nr, kernel)) = kernel {
+                // SAFETY: T is f64
+                return (nr, unsafe { cast_kernel(kernel) });
+            }
+        } else if TypeId::of::<T>() == TypeId::of::<f32>() {
+            let kernel: Option<(usize, PackedKernel<f32>)> = match level {
+                SimdLevel::Sse2 => Some((8, x86::packed_sse2_f32)),
+                SimdLevel::Avx2 => Some((16, x86::packed_avx2_f32)),
+                SimdLevel::Avx512 => Some((32, x86::packed_avx512_f32)),
+                SimdLevel::Scalar => None,
+            };
+            if let Some((nr, kernel)) = kernel {
+                // SAFETY: T is f32
+                return (nr, unsafe { cast_kernel(kernel) });
+            }
+        }
+    }
+
+    (SCALAR_NR, scalar_packed_kernel::<T, SCALAR_NR>)
+}
+
+/// Reinterprets a packed micro-kernel for `U` as one for `T`
+///
+/// # Safety
+///
+/// `T` and `U` must be the same type.
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+unsafe fn cast_kernel<T, U>(kernel: PackedKernel<U>) -> PackedKernel<T> {
+    std::mem::transmute::<PackedKernel<U>, PackedKernel<T>>(kernel)
+}
+
+/// Portable packed micro-kernel, see `PackedKernel`
+///
+/// # Safety
+///
+/// The panels and the tile must hold as many values as described by `PackedKernel`.
+unsafe fn scalar_packed_kernel<T: Element, const NR: usize>(
+    kc: usize,
+    a: *const T,
+    b: *const T,
+    tile: *mut T,
+) {
+    let a = std::slice::from_raw_parts(a, kc * MR);
+    let b = std::slice::from_raw_parts(b, kc * NR);
+    let mut accumulators = [[T::zero(); NR]; MR];
+
+    for (a_k, b_k) in a.chunks_exact(MR).zip(b.chunks_exact(NR)) {
+        for (accumulator, &a_rk) in accumulators.iter_mut().zip(a_k) {
+            for (sum, &b_kj) in accumulator.iter_mut().zip(b_k) {
+                *sum += a_rk * b_kj;
+            }
+        }
+    }
+
+    let tile = std::slice::from_raw_parts_mut(tile, MR * NR);
+    for (tile_row, accumulator) in tile.chunks_exact_mut(NR).zip(&accumulators) {
+        tile_row.copy_from_slice(accumulator);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matrix_multiplication::{
+        generate::{Distribution, MatrixGenerator},
+        matrix_multiplication_sequential_ikj,
+    };
+
+    fn assert_matches_ikj<T: Element>(rows: usize, inner: usize, cols: usize) {
+        let mut generator = MatrixGenerator::new(Some(33), Distribution::default());
+        let a: Vec<Vec<T>> = generator.generate(rows, inner);
+        let b: Vec<Vec<T>> = generator.generate(inner, cols);
+        let expected = matrix_multiplication_sequential_ikj(&a, &b).unwrap();
+
+        for level in SimdLevel::ALL.into_iter().filter(|l| l.is_available()) {
+            assert_eq!(
+                matrix_multiplication_simd(&a, &b, level).unwrap(),
+                expected,
+                "{} {}x{}x{}",
+                level,
+                rows,
+                inner,
+                cols
+            );
+        }
+    }
+
+    #[test]
+    fn test_simd_matches_ikj() {
+        for (rows, inner, cols) in [(1, 1, 1), (4, 3, 32), (9, 17, 35), (64, 20, 64)] {
+            assert_matches_ikj::<f64>(rows, inner, cols);
+            assert_matches_ikj::<f32>(rows, inner, cols);
+            assert_matches_ikj::<i32>(rows, inner, cols);
+            assert_matches_ikj::<i64>(rows, inner, cols);
+        }
+    }
+
+    #[test]
+    fn test_detect() {
+        let level = SimdLevel::detect();
+
+        assert!(level.is_available());
+        assert!(SimdLevel::Scalar.is_available());
+        #[cfg(target_arch = "x86_64")]
+        assert!(level >= SimdLevel::Sse2);
+    }
+
+    #[test]
+    fn test_simd_invalid_operands() {
+        assert!(matrix_multiplication_simd(
+            &[vec![1.0]],
+            &[vec![1.0], vec![2.0]],
+            SimdLevel::Scalar
+        )
+        .is_none());
+    }
+}
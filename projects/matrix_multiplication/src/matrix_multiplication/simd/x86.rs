@@ -0,0 +1,233 @@
+#[cfg(target_arch = "x86")]
+use std::arch::x86::*;
+#[cfg(target_arch = "x86_64")]
+use std::arch::x86_64::*;
+
+use super::MR;
+
+/// Defines a micro-kernel computing the `MR` x `2 * lanes` block of `c` at `(i, j)`
+///
+/// Each row of the block is held in two vector registers for the whole `k` loop, so the
+/// block needs `2 * MR` accumulators.
+macro_rules! micro_kernel {
+    (
+        $name:ident, $t:ty, $feature:literal, $lanes:expr,
+        $zero:ident, $load:ident, $store:ident, $splat:ident, $add:ident, $mul:ident
+    ) => {
+        /// # Safety
+        ///
+        #[doc = concat!("The CPU must support `", $feature, "`, and the block must lie within `c`.")]
+        #[target_feature(enable = $feature)]
+        pub(super) unsafe fn $name(
+            a: &[Vec<$t>],
+            b: &[Vec<$t>],
+            c: &mut [Vec<$t>],
+            i: usize,
+            j: usize,
+        ) {
+            let mut accumulators = [[$zero(); 2]; MR];
+
+            for (k, b_row) in b.iter().enumerate() {
+                let b_block = &b_row[j..j + 2 * $lanes];
+                let low = $load(b_block.as_ptr());
+                let high = $load(b_block.as_ptr().add($lanes));
+
+                for (accumulator, a_row) in accumulators.iter_mut().zip(&a[i..i + MR]) {
+                    let a_ik = $splat(a_row[k]);
+                    accumulator[0] = $add(accumulator[0], $mul(a_ik, low));
+                    accumulator[1] = $add(accumulator[1], $mul(a_ik, high));
+                }
+            }
+
+            for (accumulator, c_row) in accumulators.iter().zip(&mut c[i..i + MR]) {
+                let c_block = &mut c_row[j..j + 2 * $lanes];
+                $store(c_block.as_mut_ptr(), accumulator[0]);
+                $store(c_block.as_mut_ptr().add($lanes), accumulator[1]);
+            }
+        }
+    };
+}
+
+/// Defines a micro-kernel for the packed GEMM, computing the `MR` x `2 * lanes` tile
+/// `a * b` of packed panels of depth `kc`
+///
+/// `a` holds `MR` values per `k` and `b` holds `2 * lanes` values per `k`. The tile is
+/// written row-major, overwriting its previous content.
+macro_rules! packed_micro_kernel {
+    (
+        $name:ident, $t:ty, $feature:literal, $lanes:expr,
+        $zero:ident, $load:ident, $store:ident, $splat:ident, $add:ident, $mul:ident
+    ) => {
+        /// # Safety
+        ///
+        #[doc = concat!("The CPU must support `", $feature, "`, and the panels and the tile must")]
+        /// hold as many values as described above.
+        #[target_feature(enable = $feature)]
+        pub(super) unsafe fn $name(kc: usize, a: *const $t, b: *const $t, tile: *mut $t) {
+            let mut accumulators = [[$zero(); 2]; MR];
+
+            for k in 0..kc {
+                let b_k = b.add(k * 2 * $lanes);
+                let low = $load(b_k);
+                let high = $load(b_k.add($lanes));
+
+                for (r, accumulator) in accumulators.iter_mut().enumerate() {
+                    let a_rk = $splat(*a.add(k * MR + r));
+                    accumulator[0] = $add(accumulator[0], $mul(a_rk, low));
+                    accumulator[1] = $add(accumulator[1], $mul(a_rk, high));
+                }
+            }
+
+            for (r, accumulator) in accumulators.iter().enumerate() {
+                let tile_row = tile.add(r * 2 * $lanes);
+                $store(tile_row, accumulator[0]);
+                $store(tile_row.add($lanes), accumulator[1]);
+            }
+        }
+    };
+}
+
+micro_kernel!(
+    sse2_f64,
+    f64,
+    "sse2",
+    2,
+    _mm_setzero_pd,
+    _mm_loadu_pd,
+    _mm_storeu_pd,
+    _mm_set1_pd,
+    _mm_add_pd,
+    _mm_mul_pd
+);
+micro_kernel!(
+    sse2_f32,
+    f32,
+    "sse2",
+    4,
+    _mm_setzero_ps,
+    _mm_loadu_ps,
+    _mm_storeu_ps,
+    _mm_set1_ps,
+    _mm_add_ps,
+    _mm_mul_ps
+);
+micro_kernel!(
+    avx2_f64,
+    f64,
+    "avx2",
+    4,
+    _mm256_setzero_pd,
+    _mm256_loadu_pd,
+    _mm256_storeu_pd,
+    _mm256_set1_pd,
+    _mm256_add_pd,
+    _mm256_mul_pd
+);
+micro_kernel!(
+    avx2_f32,
+    f32,
+    "avx2",
+    8,
+    _mm256_setzero_ps,
+    _mm256_loadu_ps,
+    _mm256_storeu_ps,
+    _mm256_set1_ps,
+    _mm256_add_ps,
+    _mm256_mul_ps
+);
+micro_kernel!(
+    avx512_f64,
+    f64,
+    "avx512f",
+    8,
+    _mm512_setzero_pd,
+    _mm512_loadu_pd,
+    _mm512_storeu_pd,
+    _mm512_set1_pd,
+    _mm512_add_pd,
+    _mm512_mul_pd
+);
+micro_kernel!(
+    avx512_f32,
+    f32,
+    "avx512f",
+    16,
+    _mm512_setzero_ps,
+    _mm512_loadu_ps,
+    _mm512_storeu_ps,
+    _mm512_set1_ps,
+    _mm512_add_ps,
+    _mm512_mul_ps
+);
+packed_micro_kernel!(
+    packed_sse2_f64,
+    f64,
+    "sse2",
+    2,
+    _mm_setzero_pd,
+    _mm_loadu_pd,
+    _mm_storeu_pd,
+    _mm_set1_pd,
+    _mm_add_pd,
+    _mm_mul_pd
+);
+packed_micro_kernel!(
+    packed_sse2_f32,
+    f32,
+    "sse2",
+    4,
+    _mm_setzero_ps,
+    _mm_loadu_ps,
+    _mm_storeu_ps,
+    _mm_set1_ps,
+    _mm_add_ps,
+    _mm_mul_ps
+);
+packed_micro_kernel!(
+    packed_avx2_f64,
+    f64,
+    "avx2",
+    4,
+    _mm256_setzero_pd,
+    _mm256_loadu_pd,
+    _mm256_storeu_pd,
+    _mm256_set1_pd,
+    _mm256_add_pd,
+    _mm256_mul_pd
+);
+packed_micro_kernel!(
+    packed_avx2_f32,
+    f32,
+    "avx2",
+    8,
+    _mm256_setzero_ps,
+    _mm256_loadu_ps,
+    _mm256_storeu_ps,
+    _mm256_set1_ps,
+    _mm256_add_ps,
+    _mm256_mul_ps
+);
+packed_micro_kernel!(
+    packed_avx512_f64,
+    f64,
+    "avx512f",
+    8,
+    _mm512_setzero_pd,
+    _mm512_loadu_pd,
+    _mm512_storeu_pd,
+    _mm512_set1_pd,
+    _mm512_add_pd,
+    _mm512_mul_pd
+);
+packed_micro_kernel!(
+    packed_avx512_f32,
+    f32,
+    "avx512f",
+    16,
+    _mm512_setzero_ps,
+    _mm512_loadu_ps,
+    _mm512_storeu_ps,
+    _mm512_set1_ps,
+    _mm512_add_ps,
+    _mm512_mul_ps
+);
@@ -0,0 +1,290 @@
+use std::array;
+use std::error::Error;
+use std::fmt;
+use std::ops::{Add, Index, IndexMut, Mul, Neg, Sub};
+
+use super::{element::Element, matrix::Matrix};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Error converting a dynamic matrix into an `SMatrix` of another shape
+pub struct ShapeError {
+    /// The shape of the `SMatrix`, as `(rows, columns)`
+    pub expected: (usize, usize),
+    /// The shape of the dynamic matrix, with the length of its first row that differs
+    /// from the expected one as the number of columns if its rows are ragged
+    pub actual: (usize, usize),
+}
+
+impl fmt::Display for ShapeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "expected a {}x{} matrix, got {}x{}",
+            self.expected.0, self.expected.1, self.actual.0, self.actual.1
+        )
+    }
+}
+
+impl Error for ShapeError {}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Fixed-size `R` x `C` matrix stored inline in arrays
+///
+/// Meant for small matrices, up to 8x8 or so, where allocating every row of a
+/// `Matrix` would cost more than the arithmetic. The shapes are part of the type, so
+/// multiplying or adding matrices of incompatible shapes doesn't compile:
+///
+/// ```compile_fail
+/// use matrix_multiplication::matrix_multiplication::smatrix::SMatrix;
+///
+/// let a = SMatrix::<i32, 2, 3>::zeros();
+/// let _ = a * a;
+/// ```
+///
+/// All the loops have compile-time bounds, so the compiler unrolls them completely.
+pub struct SMatrix<T, const R: usize, const C: usize>([[T; C]; R]);
+
+impl<T: Element, const R: usize, const C: usize> SMatrix<T, R, C> {
+    pub const fn new(rows: [[T; C]; R]) -> Self {
+        SMatrix(rows)
+    }
+
+    pub fn zeros() -> Self {
+        SMatrix([[T::zero(); C]; R])
+    }
+
+    /// Creates the matrix whose cell `(i, j)` is `f(i, j)`
+    pub fn from_fn(mut f: impl FnMut(usize, usize) -> T) -> Self {
+        SMatrix(array::from_fn(|i| array::from_fn(|j| f(i, j))))
+    }
+
+    pub const fn rows(&self) -> usize {
+        R
+    }
+
+    pub const fn cols(&self) -> usize {
+        C
+    }
+
+    pub fn into_array(self) -> [[T; C]; R] {
+        self.0
+    }
+
+    pub fn transpose(&self) -> SMatrix<T, C, R> {
+        SMatrix::from_fn(|i, j| self.0[j][i])
+    }
+
+    /// Computes the matrix-vector product `self * x`
+    #[inline]
+    pub fn mul_vec(&self, x: &[T; C]) -> [T; R] {
+        array::from_fn(|i| {
+            let mut sum = T::zero();
+            for (&a_ij, &x_j) in self.0[i].iter().zip(x) {
+                sum += a_ij * x_j;
+            }
+            sum
+        })
+    }
+
+    fn map_cells(mut self, other: &Self, combine: impl Fn(&mut T, T)) -> Self {
+        for (row, other_row) in self.0.iter_mut().zip(&other.0) {
+            for (x, &y) in row.iter_mut().zip(other_row) {
+                combine(x, y);
+            }
+        }
+        self
+    }
+}
+
+impl<T: Element, const N: usize> SMatrix<T, N, N> {
+    pub fn identity() -> Self {
+        SMatrix::from_fn(|i, j| if i == j { T::one() } else { T::zero() })
+    }
+
+    /// Returns the sum of the diagonal
+    pub fn trace(&self) -> T {
+        let mut sum = T::zero();
+        for i in 0..N {
+            sum += self.0[i][i];
+        }
+        sum
+    }
+}
+
+impl<T: Element, const R: usize, const C: usize> Default for SMatrix<T, R, C> {
+    fn default() -> Self {
+        SMatrix::zeros()
+    }
+}
+
+impl<T, const R: usize, const C: usize> Index<(usize, usize)> for SMatrix<T, R, C> {
+    type Output = T;
+
+    fn index(&self, (i, j): (usize, usize)) -> &T {
+        &self.0[i][j]
+    }
+}
+
+impl<T, const R: usize, const C: usize> IndexMut<(usize, usize)> for SMatrix<T, R, C> {
+    fn index_mut(&mut self, (i, j): (usize, usize)) -> &mut T {
+        &mut self.0[i][j]
+    }
+}
+
+impl<T: Element, const R: usize, const C: usize> Add for SMatrix<T, R, C> {
+    type Output = Self;
+
+    #[inline]
+    fn add(self, other: Self) -> Self {
+        self.map_cells(&other, |x, y| *x += y)
+    }
+}
+
+impl<T: Element, const R: usize, const C: usize> Sub for SMatrix<T, R, C> {
+    type Output = Self;
+
+    #[inline]
+    fn sub(self, other: Self) -> Self {
+        self.map_cells(&other, |x, y| *x -= y)
+    }
+}
+
+impl<T: Element, const R: usize, const C: usize> Neg for SMatrix<T, R, C> {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        SMatrix::from_fn(|i, j| -self.0[i][j])
+    }
+}
+
+impl<T: Element, const R: usize, const C: usize> Mul<T> for SMatrix<T, R, C> {
+    type Output = Self;
+
+    fn mul(self, scalar: T) -> Self {
+        SMatrix::from_fn(|i, j| self.0[i][j] * scalar)
+    }
+}
+
+impl<T: Element, const R: usize, const C: usize, const K: usize> Mul<SMatrix<T, C, K>>
+    for SMatrix<T, R, C>
+{
+    type Output = SMatrix<T, R, K>;
+
+    /// Computes the matrix product, summing in the same order as the ijk kernel
+    #[inline]
+    fn mul(self, other: SMatrix<T, C, K>) -> SMatrix<T, R, K> {
+        SMatrix::from_fn(|i, j| {
+            let mut sum = T::zero();
+            for k in 0..C {
+                sum += self.0[i][k] * other.0[k][j];
+            }
+            sum
+        })
+    }
+}
+
+impl<T: Element, const R: usize, const C: usize> TryFrom<&[Vec<T>]> for SMatrix<T, R, C> {
+    type Error = ShapeError;
+
+    fn try_from(rows: &[Vec<T>]) -> Result<Self, ShapeError> {
+        let mismatch = rows.iter().find(|row| row.len() != C);
+
+        if rows.len() != R || mismatch.is_some() {
+            return Err(ShapeError {
+                expected: (R, C),
+                actual: (
+                    rows.len(),
+                    mismatch.or(rows.first()).map_or(0, |row| row.len()),
+                ),
+            });
+        }
+
+        Ok(SMatrix::from_fn(|i, j| rows[i][j]))
+    }
+}
+
+impl<T: Element, const R: usize, const C: usize> TryFrom<&Matrix<T>> for SMatrix<T, R, C> {
+    type Error = ShapeError;
+
+    fn try_from(matrix: &Matrix<T>) -> Result<Self, ShapeError> {
+        SMatrix::try_from(&**matrix)
+    }
+}
+
+impl<T: Element, const R: usize, const C: usize> From<SMatrix<T, R, C>> for Vec<Vec<T>> {
+    fn from(matrix: SMatrix<T, R, C>) -> Self {
+        matrix.0.iter().map(|row| row.to_vec()).collect()
+    }
+}
+
+impl<T: Element, const R: usize, const C: usize> From<SMatrix<T, R, C>> for Matrix<T> {
+    fn from(matrix: SMatrix<T, R, C>) -> Self {
+        Matrix::new(matrix.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matrix_multiplication::{
+        generate::{Distribution, MatrixGenerator},
+        matrix_multiplication_sequential_ijk,
+    };
+
+    #[test]
+    fn test_mul_matches_ijk() {
+        let mut generator = MatrixGenerator::new(Some(40), Distribution::default());
+        let a: Vec<Vec<f64>> = generator.generate(3, 5);
+        let b: Vec<Vec<f64>> = generator.generate(5, 2);
+
+        let sa = SMatrix::<f64, 3, 5>::try_from(&a[..]).unwrap();
+        let sb = SMatrix::<f64, 5, 2>::try_from(&b[..]).unwrap();
+        let product: Vec<Vec<f64>> = (sa * sb).into();
+
+        assert_eq!(
+            product,
+            matrix_multiplication_sequential_ijk(&a, &b).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        let a = SMatrix::new([[1, 2], [3, 4]]);
+        let b = SMatrix::from_fn(|i, j| (i * 2 + j) as i32);
+
+        assert_eq!(a + b, SMatrix::new([[1, 3], [5, 7]]));
+        assert_eq!(a - b, SMatrix::new([[1, 1], [1, 1]]));
+        assert_eq!(-a * 2, SMatrix::new([[-2, -4], [-6, -8]]));
+        assert_eq!(a * SMatrix::identity(), a);
+        assert_eq!(a.transpose()[(0, 1)], 3);
+        assert_eq!(a.trace(), 5);
+        assert_eq!(a.mul_vec(&[1, 1]), [3, 7]);
+        assert_eq!(
+            SMatrix::new([[1, 2, 3]]) * SMatrix::new([[1], [1], [1]]),
+            SMatrix::new([[6]])
+        );
+    }
+
+    #[test]
+    fn test_conversions() {
+        let matrix = Matrix::new(vec![vec![1, 2, 3], vec![4, 5, 6]]);
+        let fixed = SMatrix::<i32, 2, 3>::try_from(&matrix).unwrap();
+
+        assert_eq!(fixed.into_array(), [[1, 2, 3], [4, 5, 6]]);
+        assert_eq!(Matrix::from(fixed), matrix);
+        assert_eq!(
+            SMatrix::<i32, 3, 2>::try_from(&matrix),
+            Err(ShapeError {
+                expected: (3, 2),
+                actual: (2, 3)
+            })
+        );
+        assert_eq!(
+            SMatrix::<i32, 2, 2>::try_from(&[vec![1, 2], vec![3]][..]),
+            Err(ShapeError {
+                expected: (2, 2),
+                actual: (2, 1)
+            })
+        );
+    }
+}
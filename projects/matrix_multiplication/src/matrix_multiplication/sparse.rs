@@ -0,0 +1,689 @@
+use std::ops::Range;
+use std::sync::mpsc;
+
+use log::error;
+
+use super::{
+    element::Element,
+    generate::generate_zero_matrix,
+    sanitize::{SanitizeError, SanitizeResult},
+    types::{MatrixRowMutPtr, SharedPtr, SquareMatrixPtr},
+};
+use crate::thread_pool::ThreadPool;
+
+#[derive(Debug, Clone, PartialEq)]
+/// Sparse matrix in compressed sparse row (CSR) format
+///
+/// The non-zero values of row `i` are `values[row_offsets[i]..row_offsets[i + 1]]`,
+/// stored by increasing column, with their columns at the same positions in `col_indices`.
+pub struct CsrMatrix<T> {
+    rows: usize,
+    cols: usize,
+    row_offsets: Vec<usize>,
+    col_indices: Vec<usize>,
+    values: Vec<T>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// Sparse matrix in compressed sparse column (CSC) format
+///
+/// The non-zero values of column `j` are `values[col_offsets[j]..col_offsets[j + 1]]`,
+/// stored by increasing row, with their rows at the same positions in `row_indices`.
+pub struct CscMatrix<T> {
+    rows: usize,
+    cols: usize,
+    col_offsets: Vec<usize>,
+    row_indices: Vec<usize>,
+    values: Vec<T>,
+}
+
+/// Compresses `(major, minor, value)` triplets into offsets, minor indices and values
+///
+/// Triplets are sorted by `(major, minor)`, duplicates are summed and zeros are dropped.
+fn compress<T: Element>(
+    majors: usize,
+    mut triplets: Vec<(usize, usize, T)>,
+) -> (Vec<usize>, Vec<usize>, Vec<T>) {
+    triplets.sort_by_key(|&(major, minor, _)| (major, minor));
+
+    let mut offsets = vec![0; majors + 1];
+    let mut indices: Vec<usize> = Vec::with_capacity(triplets.len());
+    let mut values: Vec<T> = Vec::with_capacity(triplets.len());
+    let mut last = None;
+
+    for (major, minor, value) in triplets {
+        if last == Some((major, minor)) {
+            *values.last_mut().unwrap() += value;
+        } else {
+            offsets[major + 1] += 1;
+            indices.push(minor);
+            values.push(value);
+            last = Some((major, minor));
+        }
+    }
+
+    for i in 0..majors {
+        offsets[i + 1] += offsets[i];
+    }
+
+    // drop the entries whose duplicates cancelled out
+    let mut kept = 0;
+    let mut start = 0;
+    for major in 0..majors {
+        let end = offsets[major + 1];
+        for position in start..end {
+            if values[position] != T::zero() {
+                indices[kept] = indices[position];
+                values[kept] = values[position];
+                kept += 1;
+            }
+        }
+        start = end;
+        offsets[major + 1] = kept;
+    }
+    indices.truncate(kept);
+    values.truncate(kept);
+
+    (offsets, indices, values)
+}
+
+impl<T: Element> CsrMatrix<T> {
+    /// Create new `CsrMatrix` from `(row, column, value)` triplets
+    ///
+    /// Duplicate triplets are summed and zero values are not stored.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a row or column index is out of bounds.
+    pub fn from_triplets(rows: usize, cols: usize, triplets: Vec<(usize, usize, T)>) -> Self {
+        for &(i, j, _) in &triplets {
+            assert!(i < rows && j < cols, "Index ({}, {}) out of bounds", i, j);
+        }
+
+        let (row_offsets, col_indices, values) = compress(rows, triplets);
+
+        CsrMatrix {
+            rows,
+            cols,
+            row_offsets,
+            col_indices,
+            values,
+        }
+    }
+
+    /// Create new `CsrMatrix` from the non-zero cells of a dense matrix
+    ///
+    /// # Panics
+    ///
+    /// Panics if the rows don't all have the same length.
+    pub fn from_dense(matrix: &[Vec<T>]) -> Self {
+        let cols = matrix.first().map_or(0, |row| row.len());
+        assert!(
+            matrix.iter().all(|row| row.len() == cols),
+            "Cannot compress a ragged matrix"
+        );
+        let mut row_offsets = Vec::with_capacity(matrix.len() + 1);
+        let mut col_indices = Vec::new();
+        let mut values = Vec::new();
+
+        row_offsets.push(0);
+        for row in matrix {
+            for (j, &value) in row.iter().enumerate() {
+                if value != T::zero() {
+                    col_indices.push(j);
+                    values.push(value);
+                }
+            }
+            row_offsets.push(values.len());
+        }
+
+        CsrMatrix {
+            rows: matrix.len(),
+            cols,
+            row_offsets,
+            col_indices,
+            values,
+        }
+    }
+
+    /// Converts to a dense matrix
+    pub fn to_dense(&self) -> Vec<Vec<T>> {
+        let mut matrix = generate_zero_matrix(self.rows, self.cols);
+
+        for (i, row) in matrix.iter_mut().enumerate() {
+            for (j, value) in self.row(i) {
+                row[j] = value;
+            }
+        }
+
+        matrix
+    }
+
+    /// Converts to CSC format
+    pub fn to_csc(&self) -> CscMatrix<T> {
+        let triplets = (0..self.rows)
+            .flat_map(|i| self.row(i).map(move |(j, value)| (j, i, value)))
+            .collect();
+        let (col_offsets, row_indices, values) = compress(self.cols, triplets);
+
+        CscMatrix {
+            rows: self.rows,
+            cols: self.cols,
+            col_offsets,
+            row_indices,
+            values,
+        }
+    }
+
+    /// The number of rows
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    /// The number of columns
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    /// The number of stored non-zero values
+    pub fn nnz(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Iterates over the `(column, value)` pairs of row `i`
+    pub fn row(&self, i: usize) -> impl Iterator<Item = (usize, T)> + '_ {
+        let range = self.row_offsets[i]..self.row_offsets[i + 1];
+        self.col_indices[range.clone()]
+            .iter()
+            .copied()
+            .zip(self.values[range].iter().copied())
+    }
+
+    /// Iterates over the `(row, column, value)` triplets in row-major order
+    pub fn triplets(&self) -> impl Iterator<Item = (usize, usize, T)> + '_ {
+        (0..self.rows).flat_map(move |i| self.row(i).map(move |(j, value)| (i, j, value)))
+    }
+}
+
+impl<T: Element> CscMatrix<T> {
+    /// Create new `CscMatrix` from `(row, column, value)` triplets
+    ///
+    /// Duplicate triplets are summed and zero values are not stored.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a row or column index is out of bounds.
+    pub fn from_triplets(rows: usize, cols: usize, triplets: Vec<(usize, usize, T)>) -> Self {
+        for &(i, j, _) in &triplets {
+            assert!(i < rows && j < cols, "Index ({}, {}) out of bounds", i, j);
+        }
+
+        let transposed = triplets.into_iter().map(|(i, j, v)| (j, i, v)).collect();
+        let (col_offsets, row_indices, values) = compress(cols, transposed);
+
+        CscMatrix {
+            rows,
+            cols,
+            col_offsets,
+            row_indices,
+            values,
+        }
+    }
+
+    /// Create new `CscMatrix` from the non-zero cells of a dense matrix
+    ///
+    /// # Panics
+    ///
+    /// Panics if the rows don't all have the same length.
+    pub fn from_dense(matrix: &[Vec<T>]) -> Self {
+        CsrMatrix::from_dense(matrix).to_csc()
+    }
+
+    /// Converts to a dense matrix
+    pub fn to_dense(&self) -> Vec<Vec<T>> {
+        let mut matrix = generate_zero_matrix(self.rows, self.cols);
+
+        for (i, j, value) in self.to_csr().triplets() {
+            matrix[i][j] = value;
+        }
+
+        matrix
+    }
+
+    /// Converts to CSR format
+    pub fn to_csr(&self) -> CsrMatrix<T> {
+        let triplets = (0..self.cols)
+            .flat_map(|j| self.col(j).map(move |(i, value)| (i, j, value)))
+            .collect();
+
+        CsrMatrix::from_triplets(self.rows, self.cols, triplets)
+    }
+
+    /// The number of rows
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    /// The number of columns
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    /// The number of stored non-zero values
+    pub fn nnz(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Iterates over the `(row, value)` pairs of column `j`
+    pub fn col(&self, j: usize) -> impl Iterator<Item = (usize, T)> + '_ {
+        let range = self.col_offsets[j]..self.col_offsets[j + 1];
+        self.row_indices[range.clone()]
+            .iter()
+            .copied()
+            .zip(self.values[range].iter().copied())
+    }
+}
+
+fn check_conformable(a_cols: usize, b_rows: usize) -> SanitizeResult {
+    match a_cols == b_rows {
+        true => SanitizeResult::Ok,
+        false => SanitizeResult::NotOk(SanitizeError::NotConformable { a_cols, b_rows }),
+    }
+}
+
+/// Multiplies a CSR matrix by a dense vector (SpMV)
+///
+/// # Returns
+///
+/// `a * x`, or `None` if the length of `x` differs from the number of columns of `a`
+pub fn spmv<T: Element>(a: &CsrMatrix<T>, x: &[T]) -> Option<Vec<T>> {
+    if let SanitizeResult::NotOk(e) = check_conformable(a.cols, x.len()) {
+        error!("Error: {:?}", e);
+        return None;
+    }
+
+    Some(
+        (0..a.rows)
+            .map(|i| {
+                a.row(i)
+                    .fold(T::zero(), |acc, (j, value)| acc + value * x[j])
+            })
+            .collect(),
+    )
+}
+
+/// Multiplies a CSC matrix by a dense vector (SpMV)
+///
+/// # Returns
+///
+/// `a * x`, or `None` if the length of `x` differs from the number of columns of `a`
+pub fn spmv_csc<T: Element>(a: &CscMatrix<T>, x: &[T]) -> Option<Vec<T>> {
+    if let SanitizeResult::NotOk(e) = check_conformable(a.cols, x.len()) {
+        error!("Error: {:?}", e);
+        return None;
+    }
+
+    let mut y = vec![T::zero(); a.rows];
+    for (j, &x_j) in x.iter().enumerate() {
+        for (i, value) in a.col(j) {
+            y[i] += value * x_j;
+        }
+    }
+
+    Some(y)
+}
+
+fn sanitize_spmm<T>(a: &CsrMatrix<T>, b: &[Vec<T>]) -> SanitizeResult {
+    if b.iter().any(|row| row.len() != b[0].len()) {
+        return SanitizeResult::NotOk(SanitizeError::RaggedMatrix("B".to_string()));
+    }
+
+    check_conformable(a.cols, b.len())
+}
+
+/// Multiplies a CSR matrix by a dense matrix (SpMM)
+///
+/// # Returns
+///
+/// The dense product, or `None` if the matrices can't be multiplied
+pub fn spmm<T: Element>(a: &CsrMatrix<T>, b: &[Vec<T>]) -> Option<Vec<Vec<T>>> {
+    match sanitize_spmm(a, b) {
+        SanitizeResult::Ok => (),
+        SanitizeResult::NotOk(error) => {
+            error!("Error: {:?}", error);
+            return None;
+        }
+    };
+
+    let cols = b.first().map_or(0, |row| row.len());
+    let mut c = generate_zero_matrix(a.rows, cols);
+
+    for (i, c_i) in c.iter_mut().enumerate() {
+        for (k, a_ik) in a.row(i) {
+            for (c_ij, &b_kj) in c_i.iter_mut().zip(&b[k]) {
+                *c_ij += a_ik * b_kj;
+            }
+        }
+    }
+
+    Some(c)
+}
+
+/// Splits the rows of `a` into at most `parts` contiguous ranges with similar numbers of non-zeros
+fn balanced_row_ranges<T>(a: &CsrMatrix<T>, parts: usize) -> Vec<Range<usize>> {
+    let target = a.values.len().div_ceil(parts.max(1)).max(1);
+    let mut ranges = Vec::with_capacity(parts);
+    let mut start = 0;
+
+    for i in 0..a.rows {
+        if a.row_offsets[i + 1] - a.row_offsets[start] >= target {
+            ranges.push(start..i + 1);
+            start = i + 1;
+        }
+    }
+    if start < a.rows {
+        ranges.push(start..a.rows);
+    }
+
+    ranges
+}
+
+/// Multiplies a CSR matrix by a dense matrix (SpMM) on a `ThreadPool`
+///
+/// The rows of `a` are split into one block per thread, balanced by number of non-zeros.
+///
+/// # Returns
+///
+/// The dense product, or `None` if the matrices can't be multiplied
+pub fn spmm_parallel<T: Element>(
+    a: &CsrMatrix<T>,
+    b: &[Vec<T>],
+    preferred_number_of_threads: usize,
+) -> Option<Vec<Vec<T>>> {
+    match sanitize_spmm(a, b) {
+        SanitizeResult::Ok => (),
+        SanitizeResult::NotOk(error) => {
+            error!("Error: {:?}", error);
+            return None;
+        }
+    };
+
+    let cols = b.first().map_or(0, |row| row.len());
+    let mut c = generate_zero_matrix(a.rows, cols);
+
+    let pool = ThreadPool::new(preferred_number_of_threads);
+
+    for range in balanced_row_ranges(a, preferred_number_of_threads) {
+        let a = SharedPtr::new(a);
+        let b = SquareMatrixPtr::new(b);
+        let mut c_rows: Vec<MatrixRowMutPtr<T>> = c[range.clone()]
+            .iter_mut()
+            .map(|row| MatrixRowMutPtr(row.as_mut_ptr()))
+            .collect();
+
+        unsafe {
+            pool.execute(move || {
+                let a = a.get();
+                for (i, c_i) in range.zip(c_rows.iter_mut()) {
+                    for (k, a_ik) in a.row(i) {
+                        let b_k = b.get_row(k);
+                        for j in 0..cols {
+                            *c_i.add(j) += a_ik * *b_k.add(j);
+                        }
+                    }
+                }
+            });
+        }
+    }
+
+    ThreadPool::terminate(pool);
+
+    Some(c)
+}
+
+/// Computes rows `range` of `a * b` with Gustavson's algorithm
+///
+/// # Returns
+///
+/// The number of non-zeros of each row, and their columns and values in row-major order
+fn spgemm_rows<T: Element>(
+    a: &CsrMatrix<T>,
+    b: &CsrMatrix<T>,
+    range: Range<usize>,
+) -> (Vec<usize>, Vec<usize>, Vec<T>) {
+    let mut accumulator = vec![T::zero(); b.cols];
+    let mut last_row_seen = vec![usize::MAX; b.cols];
+    let mut touched = Vec::new();
+
+    let mut row_lengths = Vec::with_capacity(range.len());
+    let mut col_indices = Vec::new();
+    let mut values = Vec::new();
+
+    for i in range {
+        for (k, a_ik) in a.row(i) {
+            for (j, b_kj) in b.row(k) {
+                if last_row_seen[j] != i {
+                    last_row_seen[j] = i;
+                    accumulator[j] = T::zero();
+                    touched.push(j);
+                }
+                accumulator[j] += a_ik * b_kj;
+            }
+        }
+
+        touched.sort_unstable();
+        let before = values.len();
+        for &j in &touched {
+            if accumulator[j] != T::zero() {
+                col_indices.push(j);
+                values.push(accumulator[j]);
+            }
+        }
+        row_lengths.push(values.len() - before);
+        touched.clear();
+    }
+
+    (row_lengths, col_indices, values)
+}
+
+fn assemble<T>(
+    rows: usize,
+    cols: usize,
+    blocks: Vec<(Vec<usize>, Vec<usize>, Vec<T>)>,
+) -> CsrMatrix<T> {
+    let mut row_offsets = Vec::with_capacity(rows + 1);
+    let mut col_indices = Vec::new();
+    let mut values = Vec::new();
+
+    row_offsets.push(0);
+    for (row_lengths, block_col_indices, block_values) in blocks {
+        for length in row_lengths {
+            row_offsets.push(row_offsets.last().unwrap() + length);
+        }
+        col_indices.extend(block_col_indices);
+        values.extend(block_values);
+    }
+
+    CsrMatrix {
+        rows,
+        cols,
+        row_offsets,
+        col_indices,
+        values,
+    }
+}
+
+/// Multiplies two CSR matrices (SpGEMM) with Gustavson's row-by-row algorithm
+///
+/// # Returns
+///
+/// The sparse product, or `None` if the matrices can't be multiplied
+pub fn spgemm<T: Element>(a: &CsrMatrix<T>, b: &CsrMatrix<T>) -> Option<CsrMatrix<T>> {
+    if let SanitizeResult::NotOk(e) = check_conformable(a.cols, b.rows) {
+        error!("Error: {:?}", e);
+        return None;
+    }
+
+    let block = spgemm_rows(a, b, 0..a.rows);
+
+    Some(assemble(a.rows, b.cols, vec![block]))
+}
+
+/// Multiplies two CSR matrices (SpGEMM) on a `ThreadPool`
+///
+/// The rows of `a` are split into one block per thread, balanced by number of non-zeros,
+/// and the blocks of the product are concatenated once every job is done.
+///
+/// # Returns
+///
+/// The sparse product, or `None` if the matrices can't be multiplied
+pub fn spgemm_parallel<T: Element>(
+    a: &CsrMatrix<T>,
+    b: &CsrMatrix<T>,
+    preferred_number_of_threads: usize,
+) -> Option<CsrMatrix<T>> {
+    if let SanitizeResult::NotOk(e) = check_conformable(a.cols, b.rows) {
+        error!("Error: {:?}", e);
+        return None;
+    }
+
+    let ranges = balanced_row_ranges(a, preferred_number_of_threads);
+    let (sender, receiver) = mpsc::channel();
+
+    let pool = ThreadPool::new(preferred_number_of_threads);
+
+    for (index, range) in ranges.iter().cloned().enumerate() {
+        let a = SharedPtr::new(a);
+        let b = SharedPtr::new(b);
+        let sender = sender.clone();
+
+        unsafe {
+            pool.execute(move || {
+                let block = spgemm_rows(a.get(), b.get(), range);
+                sender.send((index, block)).unwrap();
+            });
+        }
+    }
+
+    ThreadPool::terminate(pool);
+    drop(sender);
+
+    let mut blocks: Vec<_> = receiver.iter().collect();
+    blocks.sort_by_key(|&(index, _)| index);
+
+    Some(assemble(
+        a.rows,
+        b.cols,
+        blocks.into_iter().map(|(_, block)| block).collect(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matrix_multiplication::matrix_multiplication_sequential_ijk;
+
+    fn get_a() -> Vec<Vec<i32>> {
+        vec![vec![1, 0, 2], vec![0, 0, 0], vec![0, 3, 0], vec![4, 0, 5]]
+    }
+
+    fn get_b() -> Vec<Vec<i32>> {
+        vec![vec![0, 1], vec![2, 0], vec![0, -1]]
+    }
+
+    #[test]
+    fn test_csr_from_dense() {
+        let a = CsrMatrix::from_dense(&get_a());
+
+        assert_eq!(a.nnz(), 5);
+        assert_eq!(a.row_offsets, vec![0, 2, 2, 3, 5]);
+        assert_eq!(a.col_indices, vec![0, 2, 1, 0, 2]);
+        assert_eq!(a.values, vec![1, 2, 3, 4, 5]);
+        assert_eq!(a.to_dense(), get_a());
+    }
+
+    #[test]
+    fn test_csr_from_triplets_sums_duplicates() {
+        let a = CsrMatrix::from_triplets(
+            2,
+            2,
+            vec![(1, 1, 2), (0, 1, 1), (1, 1, 3), (1, 0, 4), (1, 0, -4)],
+        );
+
+        assert_eq!(a.nnz(), 2);
+        assert_eq!(a.to_dense(), vec![vec![0, 1], vec![0, 5]]);
+    }
+
+    #[test]
+    #[should_panic(expected = "ragged")]
+    fn test_csr_from_ragged_dense() {
+        CsrMatrix::from_dense(&[vec![1, 2], vec![3, 4, 5]]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_csr_from_triplets_out_of_bounds() {
+        CsrMatrix::from_triplets(2, 2, vec![(2, 0, 1)]);
+    }
+
+    #[test]
+    fn test_csc_conversions() {
+        let csc = CscMatrix::from_dense(&get_a());
+
+        assert_eq!(csc.col_offsets, vec![0, 2, 3, 5]);
+        assert_eq!(csc.row_indices, vec![0, 3, 2, 0, 3]);
+        assert_eq!(csc.to_dense(), get_a());
+        assert_eq!(csc.to_csr(), CsrMatrix::from_dense(&get_a()));
+        assert_eq!(
+            CscMatrix::from_triplets(4, 3, CsrMatrix::from_dense(&get_a()).triplets().collect()),
+            csc
+        );
+    }
+
+    #[test]
+    fn test_spmv() {
+        let a = CsrMatrix::from_dense(&get_a());
+        let x = vec![1, 2, 3];
+
+        assert_eq!(spmv(&a, &x), Some(vec![7, 0, 6, 19]));
+        assert_eq!(spmv_csc(&a.to_csc(), &x), Some(vec![7, 0, 6, 19]));
+        assert_eq!(spmv(&a, &[1, 2]), None);
+    }
+
+    #[test]
+    fn test_spmm() {
+        let a = CsrMatrix::from_dense(&get_a());
+        let expected = matrix_multiplication_sequential_ijk(&get_a(), &get_b());
+
+        assert_eq!(spmm(&a, &get_b()), expected);
+        assert_eq!(spmm_parallel(&a, &get_b(), 3), expected);
+        assert_eq!(spmm(&a, &get_a()), None);
+    }
+
+    #[test]
+    fn test_spgemm() {
+        let a = CsrMatrix::from_dense(&get_a());
+        let b = CsrMatrix::from_dense(&get_b());
+        let expected = matrix_multiplication_sequential_ijk(&get_a(), &get_b()).unwrap();
+
+        assert_eq!(spgemm(&a, &b).unwrap().to_dense(), expected);
+        assert_eq!(spgemm_parallel(&a, &b, 3).unwrap(), spgemm(&a, &b).unwrap());
+        assert_eq!(spgemm(&b, &b), None);
+    }
+
+    #[test]
+    fn test_spgemm_drops_cancelled_values() {
+        let a = CsrMatrix::from_dense(&[vec![1, 1]]);
+        let b = CsrMatrix::from_dense(&[vec![1], vec![-1]]);
+
+        assert_eq!(spgemm(&a, &b).unwrap().nnz(), 0);
+    }
+
+    #[test]
+    fn test_balanced_row_ranges() {
+        let a = CsrMatrix::from_dense(&get_a());
+
+        assert_eq!(balanced_row_ranges(&a, 2), vec![0..3, 3..4]);
+        assert_eq!(balanced_row_ranges(&a, 1), vec![0..4]);
+    }
+}
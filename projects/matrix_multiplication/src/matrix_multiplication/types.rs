@@ -1,9 +1,9 @@
 /// Struct holding pointers to `MatrixRowPtr` type
-pub struct SquareMatrixPtr(pub Vec<MatrixRowPtr>);
+pub struct SquareMatrixPtr<T>(pub Vec<MatrixRowPtr<T>>);
 
-impl SquareMatrixPtr {
-    /// Create new `SquareMatrixPtr` from `Vec<Vec<i32>>`
-    pub fn new(matrix: &Vec<Vec<i32>>) -> SquareMatrixPtr {
+impl<T> SquareMatrixPtr<T> {
+    /// Create new `SquareMatrixPtr` from `Vec<Vec<T>>`
+    pub fn new(matrix: &[Vec<T>]) -> SquareMatrixPtr<T> {
         let mut matrix_ptr = Vec::new();
 
         for row in matrix {
@@ -18,7 +18,7 @@ impl SquareMatrixPtr {
     /// # Panics
     /// 
     /// Panics if `row` is out of bounds
-    pub fn get_row(&self, row: usize) -> &MatrixRowPtr {
+    pub fn get_row(&self, row: usize) -> &MatrixRowPtr<T> {
         let size = self.0.len();
         if row > size {
             panic!("Row index out of bounds");
@@ -28,14 +28,14 @@ impl SquareMatrixPtr {
     }
 }
 
-unsafe impl Send for SquareMatrixPtr {}
+unsafe impl<T: Send> Send for SquareMatrixPtr<T> {}
 
-// incapsule *mut i32 into custom type implementing send
-pub struct MatrixRowMutPtr(pub *mut i32);
+// incapsule *mut T into custom type implementing send
+pub struct MatrixRowMutPtr<T>(pub *mut T);
 
-/// Struct holding mutable pointers to `i32` type
+/// Struct holding mutable pointers to `T` type
 /// It represents a row of a matrix that can be modified
-impl MatrixRowMutPtr {
+impl<T> MatrixRowMutPtr<T> {
     /// Get value by index
     /// 
     /// # Arguments
@@ -46,20 +46,20 @@ impl MatrixRowMutPtr {
     /// 
     /// This function is unsafe because it dereferences a raw pointer, and it
     /// is the caller's responsibility to ensure that the pointer is valid.
-    pub unsafe fn add(&mut self, offset: usize) -> &mut i32 {
+    pub unsafe fn add(&mut self, offset: usize) -> &mut T {
         &mut *self.0.add(offset)
     }
 }
 
-unsafe impl Send for MatrixRowMutPtr {}
+unsafe impl<T: Send> Send for MatrixRowMutPtr<T> {}
 
-/// Struct holding pointers to `i32` type
+/// Struct holding pointers to `T` type
 /// It represents a row of a matrix
 /// 
 /// 
-pub struct MatrixRowPtr(pub *const i32);
+pub struct MatrixRowPtr<T>(pub *const T);
 
-impl MatrixRowPtr {
+impl<T> MatrixRowPtr<T> {
     /// Get value by index
     /// 
     /// # Arguments
@@ -70,12 +70,37 @@ impl MatrixRowPtr {
     /// 
     /// This function is unsafe because it dereferences a raw pointer, and it
     /// is the caller's responsibility to ensure that the pointer is valid.
-    pub unsafe fn add(&self, offset: usize) -> &i32 {
+    pub unsafe fn add(&self, offset: usize) -> &T {
         &*self.0.add(offset)
     }
 }
 
-unsafe impl Send for MatrixRowPtr {}
+unsafe impl<T: Send> Send for MatrixRowPtr<T> {}
+
+/// Struct holding a pointer to a value shared with the jobs of a `ThreadPool`
+///
+/// The jobs of a `ThreadPool` must be `'static`, so borrowed operands are passed
+/// to them through this type instead of a reference.
+pub struct SharedPtr<T: ?Sized>(pub *const T);
+
+impl<T: ?Sized> SharedPtr<T> {
+    /// Create new `SharedPtr` from a reference
+    pub fn new(value: &T) -> SharedPtr<T> {
+        SharedPtr(value)
+    }
+
+    /// Get the shared value
+    ///
+    /// # Safety
+    ///
+    /// This function is unsafe because it dereferences a raw pointer, and it
+    /// is the caller's responsibility to ensure that the value outlives the jobs.
+    pub unsafe fn get(&self) -> &T {
+        &*self.0
+    }
+}
+
+unsafe impl<T: ?Sized + Sync> Send for SharedPtr<T> {}
 
 #[cfg(test)]
 mod tests {
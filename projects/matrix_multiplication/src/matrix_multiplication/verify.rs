@@ -0,0 +1,304 @@
+use std::error::Error;
+use std::fmt;
+
+use rand::Rng;
+
+use super::element::Element;
+
+#[derive(Debug, PartialEq)]
+/// Enum to represent the ways a result can fail verification
+pub enum VerifyError<T> {
+    /// The result does not have the shape of the reference, as `(rows, columns)`
+    DimensionMismatch {
+        expected: (usize, usize),
+        actual: (usize, usize),
+    },
+    /// The first cell, in row-major order, that differs from the reference
+    CellMismatch {
+        row: usize,
+        col: usize,
+        expected: T,
+        actual: T,
+    },
+}
+
+impl<T: fmt::Display> fmt::Display for VerifyError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VerifyError::DimensionMismatch { expected, actual } => write!(
+                f,
+                "expected a {}x{} matrix, got {}x{}",
+                expected.0, expected.1, actual.0, actual.1
+            ),
+            VerifyError::CellMismatch {
+                row,
+                col,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "mismatch at cell ({}, {}): expected {}, got {}",
+                row, col, expected, actual
+            ),
+        }
+    }
+}
+
+impl<T: fmt::Debug + fmt::Display> Error for VerifyError<T> {}
+
+fn dimensions<T>(matrix: &[Vec<T>]) -> (usize, usize) {
+    (matrix.len(), matrix.first().map_or(0, |row| row.len()))
+}
+
+fn check_dimensions<T>(expected: (usize, usize), actual: &[Vec<T>]) -> Result<(), VerifyError<T>> {
+    let ragged = actual.iter().any(|row| row.len() != expected.1);
+
+    if dimensions(actual) != expected || ragged {
+        return Err(VerifyError::DimensionMismatch {
+            expected,
+            actual: dimensions(actual),
+        });
+    }
+
+    Ok(())
+}
+
+/// Compares `actual` against `expected` cell by cell
+///
+/// # Arguments
+///
+/// * `expected` - The reference result
+/// * `actual` - The result to check
+/// * `tolerance` - The relative tolerance used for floats, ignored for integers
+///
+/// # Returns
+///
+/// The first mismatching cell in row-major order, if any
+pub fn verify_against_reference<T: Element>(
+    expected: &[Vec<T>],
+    actual: &[Vec<T>],
+    tolerance: f64,
+) -> Result<(), VerifyError<T>> {
+    check_dimensions(dimensions(expected), actual)?;
+
+    for (row, (expected_row, actual_row)) in expected.iter().zip(actual).enumerate() {
+        for (col, (&e, &a)) in expected_row.iter().zip(actual_row).enumerate() {
+            if !e.approx_eq(a, tolerance) {
+                return Err(VerifyError::CellMismatch {
+                    row,
+                    col,
+                    expected: e,
+                    actual: a,
+                });
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn matrix_vector_product<T: Element>(m: &[Vec<T>], v: &[T]) -> Vec<T> {
+    m.iter()
+        .map(|row| {
+            row.iter().zip(v).fold(T::zero(), |acc, (&x, &y)| {
+                acc.wrapping_add(x.wrapping_mul(y))
+            })
+        })
+        .collect()
+}
+
+/// Checks that `c == a * b` with Freivalds' randomized algorithm
+///
+/// Each round draws a random 0/1 vector `r` and compares `a * (b * r)` with `c * r`,
+/// which costs O(n^2) instead of the O(n^3) of recomputing the product. A wrong `c`
+/// passes a single round with probability at most 1/2, so `rounds` rounds miss an
+/// error with probability at most 2^-rounds.
+///
+/// Integer products are computed modulo 2^bits with wrapping arithmetic, as the kernels
+/// compute them in release builds. The check stays valid modulo 2^bits: a row of
+/// `a * b - c` with a non-zero cell is still orthogonal to at most half of the vectors.
+///
+/// When a round fails, the offending row of `a * b` is recomputed to report the
+/// exact mismatching cell.
+///
+/// # Arguments
+///
+/// * `a` - The left operand
+/// * `b` - The right operand
+/// * `c` - The result to check
+/// * `rounds` - The number of random vectors to try
+/// * `tolerance` - The relative tolerance used for floats, ignored for integers
+/// * `rng` - The random number generator used to draw the vectors
+pub fn verify_freivalds<T: Element, R: Rng>(
+    a: &[Vec<T>],
+    b: &[Vec<T>],
+    c: &[Vec<T>],
+    rounds: usize,
+    tolerance: f64,
+    rng: &mut R,
+) -> Result<(), VerifyError<T>> {
+    let expected_dimensions = (a.len(), dimensions(b).1);
+    check_dimensions(expected_dimensions, c)?;
+
+    for _ in 0..rounds {
+        let r: Vec<T> = (0..expected_dimensions.1)
+            .map(|_| {
+                if rng.gen::<bool>() {
+                    T::one()
+                } else {
+                    T::zero()
+                }
+            })
+            .collect();
+
+        let abr = matrix_vector_product(a, &matrix_vector_product(b, &r));
+        let cr = matrix_vector_product(c, &r);
+
+        for (i, (&expected, &actual)) in abr.iter().zip(&cr).enumerate() {
+            if !expected.approx_eq(actual, tolerance) {
+                return Err(first_mismatch_in_row(a, b, c, i, tolerance));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn first_mismatch_in_row<T: Element>(
+    a: &[Vec<T>],
+    b: &[Vec<T>],
+    c: &[Vec<T>],
+    row: usize,
+    tolerance: f64,
+) -> VerifyError<T> {
+    let mut expected_row = vec![T::zero(); c[row].len()];
+    for (k, &a_ik) in a[row].iter().enumerate() {
+        for (e, &b_kj) in expected_row.iter_mut().zip(&b[k]) {
+            *e = e.wrapping_add(a_ik.wrapping_mul(b_kj));
+        }
+    }
+
+    // the row-vector sums disagree, so at least one cell must, up to rounding for floats
+    let col = expected_row
+        .iter()
+        .zip(&c[row])
+        .position(|(&e, &a)| !e.approx_eq(a, tolerance))
+        .unwrap_or(0);
+
+    VerifyError::CellMismatch {
+        row,
+        col,
+        expected: expected_row[col],
+        actual: c[row][col],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+
+    fn get_a() -> Vec<Vec<i32>> {
+        vec![vec![1, 2], vec![3, 4]]
+    }
+
+    fn get_b() -> Vec<Vec<i32>> {
+        vec![vec![5, 6], vec![7, 8]]
+    }
+
+    fn get_c() -> Vec<Vec<i32>> {
+        vec![vec![19, 22], vec![43, 50]]
+    }
+
+    #[test]
+    fn test_verify_against_reference() {
+        let mut wrong = get_c();
+        wrong[1][0] = 42;
+
+        assert_eq!(verify_against_reference(&get_c(), &get_c(), 0.0), Ok(()));
+        assert_eq!(
+            verify_against_reference(&get_c(), &wrong, 0.0),
+            Err(VerifyError::CellMismatch {
+                row: 1,
+                col: 0,
+                expected: 43,
+                actual: 42
+            })
+        );
+    }
+
+    #[test]
+    fn test_verify_against_reference_dimensions() {
+        let short = vec![vec![19, 22]];
+
+        assert_eq!(
+            verify_against_reference(&get_c(), &short, 0.0),
+            Err(VerifyError::DimensionMismatch {
+                expected: (2, 2),
+                actual: (1, 2)
+            })
+        );
+    }
+
+    #[test]
+    fn test_verify_against_reference_float_tolerance() {
+        let expected = vec![vec![1.0, 2.0], vec![3.0, 4.0]];
+        let close = vec![vec![1.0 + 1e-12, 2.0], vec![3.0, 4.0 - 1e-12]];
+        let far = vec![vec![1.0, 2.0], vec![3.0, 4.1]];
+
+        assert_eq!(verify_against_reference(&expected, &close, 1e-9), Ok(()));
+        assert!(matches!(
+            verify_against_reference(&expected, &far, 1e-9),
+            Err(VerifyError::CellMismatch { row: 1, col: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn test_verify_freivalds() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut wrong = get_c();
+        wrong[0][1] = 21;
+
+        assert_eq!(
+            verify_freivalds(&get_a(), &get_b(), &get_c(), 20, 0.0, &mut rng),
+            Ok(())
+        );
+        assert_eq!(
+            verify_freivalds(&get_a(), &get_b(), &wrong, 20, 0.0, &mut rng),
+            Err(VerifyError::CellMismatch {
+                row: 0,
+                col: 1,
+                expected: 22,
+                actual: 21
+            })
+        );
+    }
+
+    #[test]
+    fn test_verify_freivalds_overflow() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let a = vec![vec![i64::MAX, 3], vec![-2, i64::MIN]];
+        let b = vec![vec![i64::MAX, 5], vec![7, i64::MAX]];
+        let c: Vec<Vec<i64>> = a
+            .iter()
+            .map(|row| {
+                (0..2)
+                    .map(|j| {
+                        row.iter().zip(&b).fold(0_i64, |acc, (&x, b_row)| {
+                            acc.wrapping_add(x.wrapping_mul(b_row[j]))
+                        })
+                    })
+                    .collect()
+            })
+            .collect();
+        let mut wrong = c.clone();
+        wrong[1][1] = wrong[1][1].wrapping_add(1);
+
+        assert_eq!(verify_freivalds(&a, &b, &c, 20, 0.0, &mut rng), Ok(()));
+        assert!(matches!(
+            verify_freivalds(&a, &b, &wrong, 20, 0.0, &mut rng),
+            Err(VerifyError::CellMismatch { row: 1, col: 1, .. })
+        ));
+    }
+}
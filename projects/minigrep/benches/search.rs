@@ -0,0 +1,158 @@
+use std::env;
+use std::fs;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use minigrep::searcher::aho_corasick::AhoCorasick;
+use minigrep::searcher::substring::{Algorithm, Finder};
+use minigrep::searcher::{LiteralMatcher, Searcher};
+
+/// The size of the generated corpus
+const CORPUS_LEN: usize = 32 * 1024 * 1024;
+/// The number of times each search is run, of which the fastest is reported
+const RUNS: usize = 5;
+
+const WORDS: [&str; 16] = [
+    "the",
+    "rust",
+    "safe",
+    "fast",
+    "productive",
+    "borrow",
+    "checker",
+    "lifetime",
+    "trait",
+    "crate",
+    "module",
+    "iterator",
+    "closure",
+    "thread",
+    "channel",
+    "pattern",
+];
+
+/// The original `search`, which splits the text into lines before searching each one
+fn search_lines<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
+    contents
+        .lines()
+        .filter(|line| line.contains(query))
+        .collect()
+}
+
+/// Returns lines of pseudo-random words, with some rarer words made up of random letters
+fn generate_corpus() -> String {
+    let mut state = 0x9e37_79b9_7f4a_7c15_u64;
+    let mut next = |bound: u64| {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        (state % bound) as usize
+    };
+
+    let mut corpus = String::with_capacity(CORPUS_LEN + 100);
+    while corpus.len() < CORPUS_LEN {
+        for _ in 0..next(12) + 1 {
+            match next(8) {
+                0 => (0..next(8) + 2).for_each(|_| corpus.push((b'a' + next(26) as u8) as char)),
+                _ => corpus.push_str(WORDS[next(WORDS.len() as u64)]),
+            }
+            corpus.push(' ');
+        }
+        corpus.push('\n');
+    }
+
+    corpus
+}
+
+/// Runs `search` a few times and prints its fastest time and throughput over `len` bytes,
+/// along with what it found
+fn bench<T: std::fmt::Debug>(name: &str, len: usize, mut search: impl FnMut() -> T) {
+    let mut fastest = Duration::MAX;
+    let mut found = None;
+    for _ in 0..RUNS {
+        let start = Instant::now();
+        let result = black_box(search());
+        fastest = fastest.min(start.elapsed());
+        found = Some(result);
+    }
+
+    let throughput = len as f64 / fastest.as_secs_f64() / (1024.0 * 1024.0);
+    println!(
+        "{:<40} {:>10.2?} {:>9.0} MiB/s   {:?}",
+        name,
+        fastest,
+        throughput,
+        found.unwrap()
+    );
+}
+
+fn main() {
+    // the arguments Cargo passes, such as --bench, aren't files
+    let corpus = match env::args().skip(1).find(|arg| !arg.starts_with("--")) {
+        Some(path) => fs::read_to_string(&path).expect("the corpus can't be read"),
+        None => generate_corpus(),
+    };
+    let len = corpus.len();
+    println!(
+        "corpus of {} bytes, {} lines\n",
+        len,
+        corpus.lines().count()
+    );
+
+    let long_query = "the borrow checker checks that every reference is valid for its whole \
+                      lifetime, and that nothing mutates what is borrowed";
+    for query in [
+        "qz",
+        "fast",
+        "lifetime",
+        "iterator closure",
+        "productive crate module",
+        long_query,
+    ] {
+        println!("query {:?} ({} bytes)", query, query.len());
+        bench("lines + str::contains (old search)", len, || {
+            search_lines(query, &corpus).len()
+        });
+        bench("minigrep::search", len, || {
+            minigrep::search(query, &corpus).len()
+        });
+        bench("Searcher over the bytes", len, || {
+            let mut searcher = Searcher::new(LiteralMatcher::new(query), corpus.as_bytes());
+            let mut count = 0;
+            while searcher.next_match().unwrap().is_some() {
+                count += 1;
+            }
+            count
+        });
+        for algorithm in [Algorithm::Horspool, Algorithm::TwoWay] {
+            let finder = Finder::with_algorithm(query.as_bytes(), algorithm);
+            bench(&format!("Finder::find, {:?}", algorithm), len, || {
+                let mut haystack = corpus.as_bytes();
+                let mut count = 0;
+                while let Some(found) = finder.find(haystack) {
+                    haystack = &haystack[found + 1..];
+                    count += 1;
+                }
+                count
+            });
+        }
+        println!();
+    }
+
+    for pattern_count in [4, 64] {
+        let patterns: Vec<String> = (0..pattern_count)
+            .map(|i| format!("{} {}", WORDS[i % WORDS.len()], WORDS[i * 7 % WORDS.len()]))
+            .collect();
+        println!("{} patterns", pattern_count);
+        bench("minigrep::search per pattern", len, || {
+            patterns
+                .iter()
+                .map(|pattern| minigrep::search(pattern, &corpus).len())
+                .sum::<usize>()
+        });
+        bench("Searcher with Aho-Corasick", len, || {
+            Searcher::new(AhoCorasick::new(&patterns), corpus.as_bytes()).count()
+        });
+        println!();
+    }
+}
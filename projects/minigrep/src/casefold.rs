@@ -0,0 +1,111 @@
+/// Returns the simple case folding of `c`, the one character which all the cases of `c` fold
+/// to
+///
+/// It is derived from the case mappings of the standard library. Like the Unicode
+/// `CaseFolding.txt` simple mappings, `ς` and `Σ` fold to `σ`, `ſ` and `S` to `s`, `ẞ` to
+/// `ß`, while the Turkish `ı` and `İ` fold to themselves.
+pub fn simple_fold(c: char) -> char {
+    // the dotless i is a letter of its own, even though its uppercase is `I`
+    if c == 'ı' {
+        return c;
+    }
+
+    let lower = single_char(c.to_lowercase()).unwrap_or(c);
+    single_char(lower.to_uppercase())
+        .and_then(|upper| single_char(upper.to_lowercase()))
+        .unwrap_or(lower)
+}
+
+/// Returns the full case folding of `c`, which is several characters for the characters
+/// whose uppercase is
+///
+/// `ß` and `ẞ` fold to `ss`, `ﬁ` to `fi` and `İ` to `i` followed by a combining dot above,
+/// for example. The other characters fold like `simple_fold` folds them.
+pub fn full_fold(c: char) -> FullFold {
+    let mut fold = FullFold {
+        chars: ['\0'; 3],
+        len: 0,
+        next: 0,
+    };
+
+    let lower = c.to_lowercase();
+    if lower.len() > 1 {
+        lower.for_each(|c| fold.push(c));
+        return fold;
+    }
+
+    let simple = simple_fold(c);
+    let upper = simple.to_uppercase();
+    match upper.len() {
+        1 => fold.push(simple),
+        _ => upper.for_each(|c| fold.push(simple_fold(c))),
+    }
+    fold
+}
+
+#[derive(Debug, Clone)]
+/// The characters of a full case folding, at most 3
+pub struct FullFold {
+    chars: [char; 3],
+    len: usize,
+    next: usize,
+}
+
+impl FullFold {
+    fn push(&mut self, c: char) {
+        self.chars[self.len] = c;
+        self.len += 1;
+    }
+}
+
+impl Iterator for FullFold {
+    type Item = char;
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.chars[..self.len].get(self.next).copied();
+        self.next += 1;
+        c
+    }
+}
+
+/// Returns the only character of `chars`, if there is exactly one
+fn single_char(mut chars: impl Iterator<Item = char>) -> Option<char> {
+    let first = chars.next();
+    first.filter(|_| chars.next().is_none())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn full(c: char) -> String {
+        full_fold(c).collect()
+    }
+
+    #[test]
+    fn simple() {
+        let folds = |text: &str| text.chars().map(simple_fold).collect::<String>();
+
+        assert_eq!(folds("Rust ÉTÉ"), "rust été");
+        assert_eq!(folds("ΣσςΜµ"), "σσσμμ");
+        assert_eq!(folds("ſSsKk\u{212A}"), "ssskkk");
+        assert_eq!(folds("ẞßǅǄ"), "ßßǆǆ");
+        assert_eq!(folds("İıIi"), "İıii");
+        assert_eq!(folds("ᾼᾳ"), "ᾳᾳ");
+        assert_eq!(folds("ﬁ1-"), "ﬁ1-");
+    }
+
+    #[test]
+    fn full_folds() {
+        assert_eq!(full('ß'), "ss");
+        assert_eq!(full('ẞ'), "ss");
+        assert_eq!(full('ﬃ'), "ffi");
+        assert_eq!(full('İ'), "i\u{307}");
+        assert_eq!(full('ı'), "ı");
+        assert_eq!(full('ŉ'), "ʼn");
+        assert_eq!(full('ᾼ'), "αι");
+        assert_eq!(full('\u{390}'), "\u{3B9}\u{308}\u{301}");
+        assert_eq!(full('Σ'), "σ");
+        assert_eq!(full('x'), "x");
+    }
+}
@@ -0,0 +1,233 @@
+use std::error::Error;
+use std::fmt;
+use std::path::{Path, MAIN_SEPARATOR};
+
+use crate::regex::{escape, ErrorKind, Regex, RegexBuilder};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Error compiling a glob, such as a class range `[z-a]` going backwards
+pub struct GlobError {
+    pub glob: String,
+    pub kind: ErrorKind,
+}
+
+impl fmt::Display for GlobError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid glob {}: {}", self.glob, self.kind)
+    }
+}
+
+impl Error for GlobError {}
+
+#[derive(Debug, Clone)]
+/// A shell wildcard pattern such as `*.rs`, `src/**/test_?.rs` or `[!.]*`
+///
+/// `*` matches any characters but `/`, `?` any one character but `/` and `[...]` one
+/// character of a class, negated by a leading `!` or `^`. `**` matches any characters,
+/// `/` included, when it is a whole path component. A `\` makes the next character
+/// literal, and so does a `[` that isn't closed.
+///
+/// Globs are translated to a `Regex` matching whole texts.
+pub struct Glob {
+    glob: String,
+    regex: Regex,
+}
+
+impl Glob {
+    pub fn new(glob: &str) -> Result<Glob, GlobError> {
+        let regex = RegexBuilder::new()
+            .whole_text(true)
+            .build(&translate(glob))
+            .map_err(|error| GlobError {
+                glob: glob.to_string(),
+                kind: error.kind,
+            })?;
+
+        Ok(Glob {
+            glob: glob.to_string(),
+            regex,
+        })
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.glob
+    }
+
+    pub fn is_match(&self, text: &str) -> bool {
+        self.regex.is_match(text)
+    }
+
+    /// Matches `path`, with its components separated by `/` whatever the platform
+    pub fn is_match_path(&self, path: &Path) -> bool {
+        let text = path.to_string_lossy();
+        match MAIN_SEPARATOR {
+            '/' => self.is_match(&text),
+            separator => self.is_match(&text.replace(separator, "/")),
+        }
+    }
+}
+
+/// Translates a glob to the equivalent pattern
+fn translate(glob: &str) -> String {
+    let chars: Vec<char> = glob.chars().collect();
+    let mut pattern = String::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        match chars[i] {
+            '*' if chars.get(i + 1) == Some(&'*') => {
+                let component_start = i == 0 || chars[i - 1] == '/';
+                match chars.get(i + 2) {
+                    Some('/') if component_start => {
+                        pattern.push_str("(?:[^/]*/)*");
+                        i += 3;
+                    }
+                    None if component_start => {
+                        pattern.push_str("(?:[^/]*/)*[^/]*");
+                        i += 2;
+                    }
+                    // anywhere else `**` is the same as `*`
+                    _ => {
+                        pattern.push_str("[^/]*");
+                        i += 2;
+                    }
+                }
+                continue;
+            }
+            '*' => pattern.push_str("[^/]*"),
+            '?' => pattern.push_str("[^/]"),
+            '[' => {
+                if let Some(end) = class_end(&chars, i) {
+                    translate_class(&chars[i + 1..end], &mut pattern);
+                    i = end + 1;
+                    continue;
+                }
+                pattern.push_str(r"\[");
+            }
+            '\\' if i + 1 < chars.len() => {
+                pattern.push_str(&escape(&chars[i + 1].to_string()));
+                i += 2;
+                continue;
+            }
+            c => pattern.push_str(&escape(&c.to_string())),
+        }
+        i += 1;
+    }
+
+    pattern
+}
+
+/// Returns the index of the `]` closing the class opened at `start`
+fn class_end(chars: &[char], start: usize) -> Option<usize> {
+    let mut i = start + 1;
+    if matches!(chars.get(i), Some('!' | '^')) {
+        i += 1;
+    }
+    // a `]` first in the class is a literal
+    if chars.get(i) == Some(&']') {
+        i += 1;
+    }
+
+    while i < chars.len() && chars[i] != ']' {
+        if chars[i] == '\\' {
+            i += 1;
+        }
+        i += 1;
+    }
+
+    (i < chars.len()).then_some(i)
+}
+
+/// Translates the inside of a class, between its brackets
+fn translate_class(class: &[char], pattern: &mut String) {
+    let (negated, mut class) = match class.split_first() {
+        Some(('!' | '^', rest)) => (true, rest),
+        _ => (false, class),
+    };
+
+    // a negated class doesn't match `/` either, like `?`
+    pattern.push_str(if negated { "[^/" } else { "[" });
+    let mut first = true;
+    while let Some((&c, rest)) = class.split_first() {
+        class = rest;
+        let (c, escaped) = match (c, class.split_first()) {
+            ('\\', Some((&escaped, rest))) => {
+                class = rest;
+                (escaped, true)
+            }
+            _ => (c, false),
+        };
+
+        let range = c == '-' && !escaped && !first && !class.is_empty();
+        if !range && c.is_ascii_punctuation() {
+            pattern.push('\\');
+        }
+        pattern.push(c);
+        first = false;
+    }
+    pattern.push(']');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(glob: &str, text: &str) -> bool {
+        Glob::new(glob).unwrap().is_match(text)
+    }
+
+    #[test]
+    fn wildcards() {
+        assert!(matches("*.rs", "lib.rs"));
+        assert!(matches("*.rs", ".rs"));
+        assert!(!matches("*.rs", "lib.rs.bak"));
+        assert!(!matches("*.rs", "src/lib.rs"));
+        assert!(matches("lib.??", "lib.rs"));
+        assert!(!matches("lib.?", "lib.rs"));
+        assert!(matches("a+(b).txt", "a+(b).txt"));
+        assert!(matches(r"\*\?", "*?"));
+        assert!(!matches(r"\*", "a"));
+    }
+
+    #[test]
+    fn classes() {
+        assert!(matches("[abc].txt", "b.txt"));
+        assert!(matches("[a-c0-9]", "7"));
+        assert!(!matches("[!a-c]", "b"));
+        assert!(matches("[^a-c]", "d"));
+        assert!(!matches("[!a]", "/"));
+        assert!(matches("[]x]", "]"));
+        assert!(matches("[a-]", "-"));
+        assert!(matches("[-a]", "-"));
+        assert!(!matches("[!-a]", "-"));
+        assert!(matches("[!-a]", "."));
+        assert!(matches(r"[\]]", "]"));
+        assert!(matches("[[]", "["));
+        assert!(matches("[", "["));
+        assert!(matches("a[b", "a[b"));
+        assert_eq!(
+            Glob::new("[z-a]").unwrap_err().to_string(),
+            "invalid glob [z-a]: invalid character class range"
+        );
+    }
+
+    #[test]
+    fn double_star() {
+        assert!(matches("**/*.rs", "main.rs"));
+        assert!(matches("**/*.rs", "src/regex/nfa.rs"));
+        assert!(matches("src/**/nfa.rs", "src/nfa.rs"));
+        assert!(matches("src/**/nfa.rs", "src/regex/nfa.rs"));
+        assert!(matches("src/**", "src/regex/nfa.rs"));
+        assert!(!matches("src/**", "target/src"));
+        assert!(matches("a**b", "axxb"));
+        assert!(!matches("a**b", "a/b"));
+    }
+
+    #[test]
+    fn paths() {
+        let glob = Glob::new("src/*.rs").unwrap();
+        assert!(glob.is_match_path(&Path::new("src").join("lib.rs")));
+        assert!(!glob.is_match_path(Path::new("lib.rs")));
+        assert_eq!(glob.as_str(), "src/*.rs");
+    }
+}
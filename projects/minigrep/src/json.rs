@@ -0,0 +1,209 @@
+use std::io::{self, Write};
+use std::path::Path;
+
+use crate::searcher::Match;
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+/// What the search of one or more files found
+pub struct Stats {
+    /// The number of files searched
+    pub searches: usize,
+    /// The number of files with selected lines
+    pub searches_with_match: usize,
+    pub matched_lines: usize,
+    /// The number of matches in the selected lines
+    pub matches: usize,
+}
+
+impl Stats {
+    pub fn add(&mut self, other: &Stats) {
+        self.searches += other.searches;
+        self.searches_with_match += other.searches_with_match;
+        self.matched_lines += other.matched_lines;
+        self.matches += other.matches;
+    }
+}
+
+/// Writes the event starting the results of the file `path`
+pub fn write_begin<W: Write>(writer: &mut W, path: &Path) -> io::Result<()> {
+    write!(writer, r#"{{"type":"begin","data":{{"path":"#)?;
+    write_path(writer, path)?;
+    writeln!(writer, "}}}}")
+}
+
+/// Writes the event of a line of the file `path`, `"match"` if it is selected or
+/// `"context"` otherwise
+///
+/// The line is written without its line break, and the start and end of its submatches are
+/// byte offsets in it.
+pub fn write_line<W: Write>(
+    writer: &mut W,
+    path: &Path,
+    line: &Match,
+    selected: bool,
+) -> io::Result<()> {
+    let kind = match selected {
+        true => "match",
+        false => "context",
+    };
+    write!(writer, r#"{{"type":"{}","data":{{"path":"#, kind)?;
+    write_path(writer, path)?;
+    write!(writer, r#","lines":"#)?;
+    write_data(writer, &line.line)?;
+    write!(
+        writer,
+        r#","line_number":{},"absolute_offset":{},"submatches":["#,
+        line.line_number, line.byte_offset
+    )?;
+    let spans = match selected {
+        true => &line.spans[..],
+        false => &[],
+    };
+    for (i, span) in spans.iter().enumerate() {
+        if i > 0 {
+            write!(writer, ",")?;
+        }
+        write!(writer, r#"{{"match":"#)?;
+        write_data(writer, &line.line[span.clone()])?;
+        write!(writer, r#","start":{},"end":{}}}"#, span.start, span.end)?;
+    }
+    writeln!(writer, "]}}}}")
+}
+
+/// Writes the event ending the results of the file `path`, with what was found in it
+pub fn write_end<W: Write>(writer: &mut W, path: &Path, stats: &Stats) -> io::Result<()> {
+    write!(writer, r#"{{"type":"end","data":{{"path":"#)?;
+    write_path(writer, path)?;
+    write!(writer, r#","stats":"#)?;
+    write_stats(writer, stats)?;
+    writeln!(writer, "}}}}")
+}
+
+/// Writes the last event, with what was found in all the files
+pub fn write_summary<W: Write>(writer: &mut W, stats: &Stats) -> io::Result<()> {
+    write!(writer, r#"{{"type":"summary","data":{{"stats":"#)?;
+    write_stats(writer, stats)?;
+    writeln!(writer, "}}}}")
+}
+
+fn write_stats<W: Write>(writer: &mut W, stats: &Stats) -> io::Result<()> {
+    write!(
+        writer,
+        r#"{{"searches":{},"searches_with_match":{},"matched_lines":{},"matches":{}}}"#,
+        stats.searches, stats.searches_with_match, stats.matched_lines, stats.matches
+    )
+}
+
+fn write_path<W: Write>(writer: &mut W, path: &Path) -> io::Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+        write_data(writer, path.as_os_str().as_bytes())
+    }
+    #[cfg(not(unix))]
+    {
+        write_data(writer, path.to_string_lossy().as_bytes())
+    }
+}
+
+/// Writes `bytes` as `{"text": ...}` if they are valid UTF-8, or as `{"bytes": ...}` with
+/// their base64 encoding otherwise
+pub fn write_data<W: Write>(writer: &mut W, bytes: &[u8]) -> io::Result<()> {
+    match std::str::from_utf8(bytes) {
+        Ok(text) => {
+            write!(writer, r#"{{"text":"#)?;
+            write_string(writer, text)?;
+        }
+        Err(_) => write!(writer, r#"{{"bytes":"{}""#, base64(bytes))?,
+    }
+    write!(writer, "}}")
+}
+
+/// Writes `text` as a JSON string, escaping the quotes, the backslashes and the control
+/// characters
+pub fn write_string<W: Write>(writer: &mut W, text: &str) -> io::Result<()> {
+    write!(writer, "\"")?;
+    let mut written = 0;
+    for (i, byte) in text.bytes().enumerate() {
+        let escaped = match byte {
+            b'"' => r#"\""#,
+            b'\\' => r"\\",
+            b'\n' => r"\n",
+            b'\r' => r"\r",
+            b'\t' => r"\t",
+            0..=0x1f => "",
+            _ => continue,
+        };
+        writer.write_all(&text.as_bytes()[written..i])?;
+        match escaped.is_empty() {
+            true => write!(writer, r"\u{:04x}", byte)?,
+            false => write!(writer, "{}", escaped)?,
+        }
+        written = i + 1;
+    }
+    writer.write_all(&text.as_bytes()[written..])?;
+    write!(writer, "\"")
+}
+
+/// Returns the standard base64 encoding of `bytes`, padded with `=`
+pub fn base64(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |group, (i, &byte)| {
+            group | u32::from(byte) << (16 - 8 * i)
+        });
+        for i in 0..4 {
+            match i <= chunk.len() {
+                true => {
+                    let index = (group >> (18 - 6 * i)) & 0x3f;
+                    encoded.push(char::from(BASE64_ALPHABET[index as usize]));
+                }
+                false => encoded.push('='),
+            }
+        }
+    }
+
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn json(write: impl FnOnce(&mut Vec<u8>) -> io::Result<()>) -> String {
+        let mut output = Vec::new();
+        write(&mut output).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn strings() {
+        assert_eq!(json(|w| write_string(w, "a frog")), r#""a frog""#);
+        assert_eq!(
+            json(|w| write_string(w, "\"quoted\"\\\ttab\r\n\x01\x7f é")),
+            r#""\"quoted\"\\\ttab\r\n\u0001"#.to_owned() + "\x7f é\""
+        );
+        assert_eq!(
+            json(|w| write_data(w, "Straße".as_bytes())),
+            r#"{"text":"Straße"}"#
+        );
+        assert_eq!(
+            json(|w| write_data(w, b"frog\xFF")),
+            r#"{"bytes":"ZnJvZ/8="}"#
+        );
+    }
+
+    #[test]
+    fn base64_encoding() {
+        let encodings = [
+            "", "Zg==", "Zm8=", "Zm9v", "Zm9vYg==", "Zm9vYmE=", "Zm9vYmFy",
+        ];
+        for (len, encoding) in encodings.iter().enumerate() {
+            assert_eq!(base64(&b"foobar"[..len]), *encoding);
+        }
+        assert_eq!(base64(&[0xFF, 0xFE, 0x00]), "//4A");
+    }
+}
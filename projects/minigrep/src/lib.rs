@@ -1,75 +1,979 @@
+use std::collections::{HashMap, VecDeque};
 use std::env;
 use std::error::Error;
-use std::fs;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, IsTerminal, Write};
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::slice;
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Mutex};
+use std::thread;
 
+use clap::{Parser, ValueEnum};
+use glob::Glob;
+use json::Stats;
+use regex::{Regex, RegexBuilder, RegexError};
+use searcher::aho_corasick::AhoCorasick;
+use searcher::substring::find_byte;
+use searcher::{
+    AnyMatcher, CaseInsensitiveMatcher, LiteralMatcher, Match, Matcher, Searcher, WholeMatcher,
+};
+use walk::{walk, WalkError, WalkOptions};
+
+pub mod casefold;
+pub mod glob;
+pub mod json;
+pub mod regex;
+pub mod searcher;
+pub mod walk;
+
+#[derive(Parser, Debug)]
+#[command(version)]
+/// Searches each FILE for PATTERN and prints the lines that contain it
 pub struct Config {
-    pub query: String,
-    pub file_path: String,
+    #[arg(value_name = "PATTERN", required_unless_present_any = ["patterns", "pattern_files"])]
+    /// Text to search for, or regular expression with -E. With -e or -f, it is the first
+    /// FILE instead
+    pub query: Option<String>,
+
+    #[arg(value_name = "FILE")]
+    /// Files to search, or directories with -r. With no FILE, or when FILE is -, standard
+    /// input is searched, or the working directory with -r
+    pub paths: Vec<PathBuf>,
+
+    #[arg(short = 'e', long = "regexp", value_name = "PATTERN")]
+    /// Search for PATTERN too, which can be repeated to select the lines matching any of them
+    pub patterns: Vec<String>,
+
+    #[arg(short = 'f', long = "file", value_name = "FILE")]
+    /// Search for each line of FILE too
+    pub pattern_files: Vec<PathBuf>,
+
+    #[arg(short, long)]
+    /// Search the files under each directory, skipping those listed in .gitignore files
+    pub recursive: bool,
+
+    #[arg(long, value_name = "GLOB", value_parser = Glob::new)]
+    /// Only search the files whose name matches GLOB
+    pub include: Vec<Glob>,
+
+    #[arg(long, value_name = "GLOB", value_parser = Glob::new)]
+    /// Skip the files and directories whose name matches GLOB
+    pub exclude: Vec<Glob>,
+
+    #[arg(long)]
+    /// Don't skip the files listed in .gitignore files, nor .git directories
+    pub no_ignore: bool,
+
+    #[arg(short = 'H', long)]
+    /// Print the file name with each match, the default with -r or several files
+    pub with_filename: bool,
+
+    #[arg(short, long, overrides_with_all = ["no_ignore_case", "smart_case"])]
+    /// Ignore case distinctions, the default when IGNORE_CASE is set. With -E, characters are
+    /// compared by their simple case folding, so that `ß` matches `ẞ` but not `ss`
     pub ignore_case: bool,
+
+    #[arg(long, overrides_with_all = ["ignore_case", "smart_case"])]
+    /// Match case exactly, even when IGNORE_CASE is set
+    pub no_ignore_case: bool,
+
+    #[arg(short = 'S', long, overrides_with_all = ["ignore_case", "no_ignore_case"])]
+    /// Ignore case distinctions in each PATTERN that has no uppercase letter, even when
+    /// IGNORE_CASE is set
+    pub smart_case: bool,
+
+    #[arg(short = 'E', long)]
+    /// Interpret PATTERN as a regular expression
+    pub regex: bool,
+
+    #[arg(short = 'v', long)]
+    /// Select the lines that don't match
+    pub invert_match: bool,
+
+    #[arg(short, long)]
+    /// Only match whole words, with no word character right before or after the match
+    pub word_regexp: bool,
+
+    #[arg(short = 'x', long)]
+    /// Only match whole lines
+    pub line_regexp: bool,
+
+    #[arg(short, long)]
+    /// Print only the number of selected lines
+    pub count: bool,
+
+    #[arg(short = 'n', long)]
+    /// Prefix each line with its line number
+    pub line_number: bool,
+
+    #[arg(short, long)]
+    /// Prefix each line with the byte offset of its start in the file
+    pub byte_offset: bool,
+
+    #[arg(short = 'A', long, value_name = "NUM")]
+    /// Print NUM lines of context after each selected line
+    pub after_context: Option<usize>,
+
+    #[arg(short = 'B', long, value_name = "NUM")]
+    /// Print NUM lines of context before each selected line
+    pub before_context: Option<usize>,
+
+    #[arg(short = 'C', long, value_name = "NUM")]
+    /// Print NUM lines of context before and after each selected line
+    pub context: Option<usize>,
+
+    #[arg(
+        long,
+        alias = "colour",
+        value_enum,
+        value_name = "WHEN",
+        default_value_t = ColorChoice::Auto,
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "auto"
+    )]
+    /// Highlight the matches, the file names and the line numbers
+    pub color: ColorChoice,
+
+    #[arg(short = 'l', long)]
+    /// Print only the names of the files with selected lines
+    pub files_with_matches: bool,
+
+    #[arg(short, long, value_name = "NUM")]
+    /// Stop after NUM selected lines
+    pub max_count: Option<usize>,
+
+    #[arg(long, value_enum, value_name = "TYPE", default_value_t = BinaryFiles::Text)]
+    /// How to search the files that aren't valid UTF-8
+    pub binary_files: BinaryFiles,
+
+    #[arg(short = 'j', long, value_name = "N")]
+    /// Search N files at once, by default as many as there are CPUs. The output of each file
+    /// is printed whole, as soon as it is searched
+    pub threads: Option<NonZeroUsize>,
+
+    #[arg(long, conflicts_with_all = ["count", "files_with_matches"])]
+    /// Print the results as JSON Lines: an event beginning and ending each file, one for
+    /// each selected line or line of context, and a final summary. Text that isn't valid
+    /// UTF-8 is given as base64 bytes
+    pub json: bool,
+
+    #[arg(long, value_enum, value_name = "BY")]
+    /// Print the output of the files in order, rather than as soon as they are searched
+    pub sort: Option<SortBy>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+/// The order of the files in the output
+pub enum SortBy {
+    /// The order of their paths, the entries of each directory sorted by name
+    Path,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+/// What to do with a file that isn't valid UTF-8
+pub enum BinaryFiles {
+    /// Search it like text, printing the invalid bytes as U+FFFD
+    Text,
+    /// Assume that it doesn't match
+    WithoutMatch,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+/// When to color the output
+pub enum ColorChoice {
+    /// When writing to a terminal
+    Auto,
+    Always,
+    Never,
 }
 
+/// The path standing for standard input
+const STDIN_PATH: &str = "-";
+/// The name printed for standard input
+const STDIN_NAME: &str = "(standard input)";
+
 impl Config {
-    pub fn build(args: &[String]) -> Result<Config, &'static str> {
-        if args.len() < 3 {
-            return Err("not enough arguments");
+    /// Parses the command line `args`, program name included
+    ///
+    /// Case is ignored if the `IGNORE_CASE` environment variable is set, unless
+    /// `--no-ignore-case` is given.
+    pub fn build(args: &[String]) -> Result<Config, clap::Error> {
+        Config::build_with_env(args, env::var("IGNORE_CASE").is_ok())
+    }
+
+    fn build_with_env(args: &[String], env_ignore_case: bool) -> Result<Config, clap::Error> {
+        let mut config = Config::try_parse_from(args)?;
+        if !config.no_ignore_case && !config.smart_case {
+            config.ignore_case |= env_ignore_case;
+        }
+        if !config.patterns.is_empty() || !config.pattern_files.is_empty() {
+            if let Some(path) = config.query.take() {
+                config.paths.insert(0, PathBuf::from(path));
+            }
+        }
+        if config.paths.is_empty() {
+            config.paths.push(match config.recursive {
+                true => PathBuf::from("."),
+                false => PathBuf::from(STDIN_PATH),
+            });
         }
 
-        let query = args[1].clone();
-        let file_path = args[2].clone();
+        Ok(config)
+    }
 
-        let ignore_case = env::var("IGNORE_CASE").is_ok();
+    /// Returns whether the output names the file of each result
+    fn with_filename(&self) -> bool {
+        self.with_filename || self.recursive || self.paths.len() > 1
+    }
 
-        Ok(Config {
-            query,
-            file_path,
-            ignore_case,
-        })
+    /// Returns whether the selected lines are printed, rather than a count or file name
+    fn prints_lines(&self) -> bool {
+        !self.count && !self.files_with_matches
+    }
+
+    fn after_context(&self) -> usize {
+        self.after_context.or(self.context).unwrap_or(0)
+    }
+
+    fn before_context(&self) -> usize {
+        self.before_context.or(self.context).unwrap_or(0)
+    }
+
+    /// Returns the number of files searched at once
+    fn threads(&self) -> usize {
+        match self.threads {
+            Some(threads) => threads.get(),
+            None => thread::available_parallelism().map_or(1, NonZeroUsize::get),
+        }
+    }
+
+    fn walk_options(&self) -> WalkOptions<'_> {
+        WalkOptions {
+            recursive: self.recursive,
+            include: &self.include,
+            exclude: &self.exclude,
+            gitignore: !self.no_ignore,
+        }
+    }
+
+    /// Returns the patterns to search for: the query, those given with -e and the lines of
+    /// the files given with -f
+    pub fn patterns(&self) -> io::Result<Vec<String>> {
+        let mut patterns: Vec<String> = self.query.iter().chain(&self.patterns).cloned().collect();
+        for path in &self.pattern_files {
+            let contents = fs::read_to_string(path)
+                .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))?;
+            patterns.extend(contents.lines().map(String::from));
+        }
+
+        Ok(patterns)
     }
 }
 
 pub fn search_case_insensitive<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
-    let query = query.to_lowercase();
-    let mut results = Vec::new();
+    selected_lines(CaseInsensitiveMatcher::new(query), contents)
+}
 
-    for line in contents.lines() {
-        if line.to_lowercase().contains(&query) {
-            results.push(line);
+pub fn search<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
+    selected_lines(LiteralMatcher::new(query), contents)
+}
+
+pub fn search_regex<'a>(regex: &Regex, contents: &'a str) -> Vec<&'a str> {
+    selected_lines(regex, contents)
+}
+
+/// Returns the lines of `contents` that `matcher` matches
+///
+/// A line-oblivious matcher searches `contents` directly, the lines being only split
+/// around its matches, while the others search it with a `Searcher`.
+fn selected_lines<M: Matcher>(matcher: M, contents: &str) -> Vec<&str> {
+    let mut lines = Vec::new();
+    if !matcher.is_line_oblivious() {
+        let mut searcher = Searcher::new(matcher, contents.as_bytes());
+        while let Some(found) = searcher.next_match().expect("reading a slice doesn't fail") {
+            lines.push(&contents[found.byte_offset..found.byte_offset + found.line.len()]);
         }
+        return lines;
     }
 
-    results
+    let bytes = contents.as_bytes();
+    // `start` is always the start of a line
+    let mut start = 0;
+    while start < bytes.len() {
+        let Some(found) = matcher.find_at(bytes, start) else {
+            break;
+        };
+        let line_start = bytes[start..found.start]
+            .iter()
+            .rposition(|&byte| byte == b'\n')
+            .map_or(start, |newline| start + newline + 1);
+        let line_end = find_byte(b'\n', &bytes[found.start..])
+            .map_or(bytes.len(), |newline| found.start + newline);
+        start = line_end + 1;
+
+        let mut line = &contents[line_start..line_end];
+        if line_end < bytes.len() {
+            line = line.strip_suffix('\r').unwrap_or(line);
+        }
+        // the line may still not match once its line break is stripped
+        if found.end <= line_start + line.len() || matcher.is_match(line.as_bytes()) {
+            lines.push(line);
+        }
+    }
+
+    lines
 }
 
-pub fn search<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
-    let mut results = Vec::new();
+/// Builds the matcher selecting the lines that match any of `patterns`, according to the
+/// matching flags
+///
+/// Several texts matched exactly are searched at once with Aho-Corasick, unless they must be
+/// whole words or lines, the others one after the other.
+fn build_matcher(config: &Config, patterns: &[String]) -> Result<Box<dyn Matcher>, RegexError> {
+    let any = |mut matchers: Vec<Box<dyn Matcher>>| -> Box<dyn Matcher> {
+        match matchers.len() {
+            1 => matchers.pop().unwrap(),
+            _ => Box::new(AnyMatcher::new(matchers)),
+        }
+    };
 
-    for line in contents.lines() {
-        if line.contains(query) {
-            results.push(line);
+    if !config.regex {
+        let ignores_case = |pattern: &str| {
+            config.ignore_case || (config.smart_case && !pattern.chars().any(char::is_uppercase))
+        };
+        let bounded = config.word_regexp || config.line_regexp;
+        if !bounded && patterns.len() != 1 && !patterns.iter().any(|pattern| ignores_case(pattern))
+        {
+            return Ok(Box::new(AhoCorasick::new(patterns)));
         }
+
+        // each text is checked to be a whole word or line on its own, so that a longer text
+        // that isn't one doesn't hide a shorter one that is
+        let matchers = patterns
+            .iter()
+            .map(|pattern| -> Box<dyn Matcher> {
+                let matcher: Box<dyn Matcher> = match ignores_case(pattern) {
+                    true => Box::new(CaseInsensitiveMatcher::new(pattern)),
+                    false => Box::new(LiteralMatcher::new(pattern)),
+                };
+                if config.line_regexp {
+                    Box::new(WholeMatcher::line(matcher))
+                } else if config.word_regexp {
+                    Box::new(WholeMatcher::words(matcher))
+                } else {
+                    matcher
+                }
+            })
+            .collect();
+        return Ok(any(matchers));
     }
 
-    results
+    let matchers = patterns
+        .iter()
+        .map(|pattern| -> Result<Box<dyn Matcher>, RegexError> {
+            let regex = RegexBuilder::new()
+                .case_insensitive(config.ignore_case)
+                .smart_case(config.smart_case)
+                .whole_words(config.word_regexp)
+                .whole_text(config.line_regexp)
+                .build(pattern)?;
+            Ok(Box::new(regex))
+        })
+        .collect::<Result<_, _>>()?;
+
+    Ok(any(matchers))
 }
 
-pub fn run(config: Config) -> Result<(), Box<dyn Error>> {
-    let contents = fs::read_to_string(config.file_path)?;
+/// The escape sequences coloring the output, as GNU grep colors it by default
+mod colors {
+    pub const MATCH: &str = "\x1b[01;31m";
+    pub const FILE_NAME: &str = "\x1b[35m";
+    pub const NUMBER: &str = "\x1b[32m";
+    pub const SEPARATOR: &str = "\x1b[36m";
+    pub const RESET: &str = "\x1b[m";
+}
+
+/// Writes the results of searching files like grep does, or as JSON Lines with `--json`
+struct Printer<'c, W: Write> {
+    config: &'c Config,
+    writer: W,
+    color: bool,
+    /// Whether any line was written, which the next group of context must be separated from
+    wrote_line: bool,
+    /// The number of the last line written from the file being searched
+    last_number: Option<usize>,
+    /// The number of matches in the selected lines of the file being searched
+    file_matches: usize,
+    /// What the files searched so far contain
+    stats: Stats,
+}
 
-    let results = if config.ignore_case {
-        search_case_insensitive(&config.query, &contents) // do not put semicolon here
-    } else {
-        search(&config.query, &contents)
+impl<'c, W: Write> Printer<'c, W> {
+    /// Creates a printer writing to `writer`, which colors the output with `--color=auto` if
+    /// `is_terminal`
+    fn new(config: &'c Config, writer: W, is_terminal: bool) -> Printer<'c, W> {
+        let color = match config.color {
+            ColorChoice::Auto => is_terminal,
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+        };
+
+        Printer {
+            config,
+            writer,
+            color,
+            wrote_line: false,
+            last_number: None,
+            file_matches: 0,
+            stats: Stats::default(),
+        }
+    }
+
+    /// Writes the output of a whole file, written by a printer from `Printer::buffered`,
+    /// which found `stats`
+    fn write_file_output(&mut self, output: &[u8], stats: &Stats) -> io::Result<()> {
+        self.stats.add(stats);
+        if output.is_empty() {
+            return Ok(());
+        }
+
+        // the context of different files is separated too
+        let has_context = self.config.after_context() > 0 || self.config.before_context() > 0;
+        if has_context && self.config.prints_lines() && !self.config.json && self.wrote_line {
+            self.write_colored(colors::SEPARATOR, "--")?;
+            writeln!(self.writer)?;
+        }
+        self.wrote_line = true;
+
+        self.writer.write_all(output)
+    }
+
+    fn write_colored(&mut self, color: &str, text: &str) -> io::Result<()> {
+        match self.color {
+            true => write!(self.writer, "{}{}{}", color, text, colors::RESET),
+            false => write!(self.writer, "{}", text),
+        }
+    }
+
+    fn write_file_name(&mut self, name: &Path) -> io::Result<()> {
+        self.write_colored(colors::FILE_NAME, &name.display().to_string())
+    }
+
+    /// Writes what starts the results of the file `name`
+    fn begin_file(&mut self, name: &Path) -> io::Result<()> {
+        self.file_matches = 0;
+
+        match self.config.json {
+            true => json::write_begin(&mut self.writer, name),
+            false => Ok(()),
+        }
+    }
+
+    /// Writes `line` of the file `name`, followed by a newline
+    ///
+    /// A selected line has its prefixes separated by `:` and its matches highlighted, a line
+    /// of context has them separated by `-`. A `--` line separates the groups of lines that
+    /// aren't adjacent when there is context.
+    fn write_line(&mut self, name: &Path, line: &Match, selected: bool) -> io::Result<()> {
+        if selected {
+            self.file_matches += line.spans.len();
+        }
+        if self.config.json {
+            return json::write_line(&mut self.writer, name, line, selected);
+        }
+
+        let has_context = self.config.after_context() > 0 || self.config.before_context() > 0;
+        let adjacent = self.last_number == Some(line.line_number - 1);
+        if has_context && self.wrote_line && !adjacent {
+            self.write_colored(colors::SEPARATOR, "--")?;
+            writeln!(self.writer)?;
+        }
+        self.wrote_line = true;
+        self.last_number = Some(line.line_number);
+
+        let separator = match selected {
+            true => ":",
+            false => "-",
+        };
+        if self.config.with_filename() {
+            self.write_file_name(name)?;
+            self.write_colored(colors::SEPARATOR, separator)?;
+        }
+        if self.config.line_number {
+            self.write_colored(colors::NUMBER, &line.line_number.to_string())?;
+            self.write_colored(colors::SEPARATOR, separator)?;
+        }
+        if self.config.byte_offset {
+            self.write_colored(colors::NUMBER, &line.byte_offset.to_string())?;
+            self.write_colored(colors::SEPARATOR, separator)?;
+        }
+
+        let mut written = 0;
+        if self.color && selected {
+            for span in &line.spans {
+                let before = String::from_utf8_lossy(&line.line[written..span.start]);
+                write!(self.writer, "{}", before)?;
+                self.write_colored(
+                    colors::MATCH,
+                    &String::from_utf8_lossy(&line.line[span.clone()]),
+                )?;
+                written = span.end;
+            }
+        }
+        writeln!(
+            self.writer,
+            "{}",
+            String::from_utf8_lossy(&line.line[written..])
+        )
+    }
+
+    /// Writes what is printed for the file `name` once searched, given the number of
+    /// selected lines
+    fn finish_file(&mut self, name: &Path, count: usize) -> io::Result<()> {
+        self.last_number = None;
+        let stats = Stats {
+            searches: 1,
+            searches_with_match: usize::from(count > 0),
+            matched_lines: count,
+            matches: self.file_matches,
+        };
+        self.stats.add(&stats);
+
+        if self.config.json {
+            json::write_end(&mut self.writer, name, &stats)?;
+        } else if self.config.files_with_matches {
+            if count > 0 {
+                self.write_file_name(name)?;
+                writeln!(self.writer)?;
+            }
+        } else if self.config.count {
+            if self.config.with_filename() {
+                self.write_file_name(name)?;
+                self.write_colored(colors::SEPARATOR, ":")?;
+            }
+            writeln!(self.writer, "{}", count)?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes what ends the output, the summary of the search with `--json`, and flushes it
+    fn finish(&mut self) -> io::Result<()> {
+        if self.config.json {
+            json::write_summary(&mut self.writer, &self.stats)?;
+        }
+        self.writer.flush()
+    }
+}
+
+impl<'c> Printer<'c, Vec<u8>> {
+    /// Creates a printer writing the output of a file to a buffer, which is colored if
+    /// `color`
+    fn buffered(config: &'c Config, color: bool) -> Printer<'c, Vec<u8>> {
+        Printer {
+            color,
+            ..Printer::new(config, Vec::new(), false)
+        }
+    }
+}
+
+#[derive(Debug)]
+/// Error stopping the search of a file
+enum SearchError {
+    /// Reading the file failed, which doesn't stop searching the next ones
+    Read(io::Error),
+    /// Writing the output failed, which stops the whole search
+    Write(io::Error),
+}
+
+/// Searches the lines of `reader` and prints those selected by `config` with their context
+///
+/// Lines end with `\n` or `\r\n`. Without context to print, the selected lines are found in
+/// whole blocks of the file, and otherwise every line is read one at a time.
+///
+/// With `--binary-files=without-match`, every line is read to check it, and nothing is
+/// selected in a file that isn't valid UTF-8: the output of the file is held until it is
+/// read whole, and dropped at the first line that isn't valid UTF-8.
+fn search_reader<R: BufRead, W: Write>(
+    config: &Config,
+    matcher: &dyn Matcher,
+    reader: R,
+    name: &Path,
+    printer: &mut Printer<W>,
+) -> Result<(), SearchError> {
+    if config.binary_files == BinaryFiles::Text {
+        return search_lines(config, matcher, reader, name, printer).map(|_| ());
+    }
+
+    let mut file_printer = Printer::buffered(config, printer.color);
+    let result = match search_lines(config, matcher, reader, name, &mut file_printer)? {
+        true => printer.write_file_output(&file_printer.writer, &file_printer.stats),
+        false => printer
+            .begin_file(name)
+            .and_then(|()| printer.finish_file(name, 0)),
+    };
+    result.map_err(SearchError::Write)
+}
+
+/// Searches the lines of `reader` like `search_reader`, printing the results with `printer`
+///
+/// # Returns
+///
+/// Whether the file was searched whole, which with `--binary-files=without-match` only
+/// happens if it is valid UTF-8
+fn search_lines<R: BufRead, W: Write>(
+    config: &Config,
+    matcher: &dyn Matcher,
+    mut reader: R,
+    name: &Path,
+    printer: &mut Printer<W>,
+) -> Result<bool, SearchError> {
+    // a single line is enough to know that the file matches
+    let max_count = match config.files_with_matches {
+        true => Some(1),
+        false => config.max_count,
+    };
+    let (after_context, before_context) = match config.prints_lines() {
+        true => (config.after_context(), config.before_context()),
+        false => (0, 0),
+    };
+    let without_match = config.binary_files == BinaryFiles::WithoutMatch;
+
+    printer.begin_file(name).map_err(SearchError::Write)?;
+    if without_match {
+        let start = reader.fill_buf().map_err(SearchError::Read)?;
+        if starts_invalid(start) {
+            return Ok(false);
+        }
+    }
+
+    let mut searcher = Searcher::new(matcher, &mut reader);
+    searcher.invert_match(config.invert_match);
+
+    // without context nor lines to check, the selected lines can be found in whole blocks
+    if after_context == 0 && before_context == 0 && !without_match {
+        let mut count = 0;
+        while max_count.is_none_or(|max_count| count < max_count) {
+            let Some(found) = searcher.next_match().map_err(SearchError::Read)? else {
+                break;
+            };
+            count += 1;
+            if config.prints_lines() {
+                printer
+                    .write_line(name, found, true)
+                    .map_err(SearchError::Write)?;
+            }
+        }
+
+        printer
+            .finish_file(name, count)
+            .map_err(SearchError::Write)?;
+        return Ok(true);
+    }
+
+    let mut count = 0;
+    // the lines that may be printed as context before the next selected line
+    let mut before = VecDeque::new();
+    let mut after = 0;
+    loop {
+        let done = max_count.is_some_and(|max_count| count >= max_count);
+        if done && after == 0 && !without_match {
+            break;
+        }
+
+        let Some((line, selected)) = searcher.next_line().map_err(SearchError::Read)? else {
+            break;
+        };
+        if without_match && std::str::from_utf8(&line.line).is_err() {
+            return Ok(false);
+        }
+        // the rest of the file is only read to check that it is valid UTF-8
+        if done && after == 0 {
+            continue;
+        }
+
+        if selected && !done {
+            count += 1;
+            if config.prints_lines() {
+                for context in before.drain(..) {
+                    printer
+                        .write_line(name, &context, false)
+                        .map_err(SearchError::Write)?;
+                }
+                printer
+                    .write_line(name, line, true)
+                    .map_err(SearchError::Write)?;
+            }
+            after = after_context;
+        } else if after > 0 {
+            printer
+                .write_line(name, line, false)
+                .map_err(SearchError::Write)?;
+            after -= 1;
+        } else if before_context > 0 {
+            // the line leaving the context lends its buffers to the new one
+            match before.len() == before_context {
+                true => {
+                    let mut context = before.pop_front().unwrap();
+                    context.clone_from(line);
+                    before.push_back(context);
+                }
+                false => before.push_back(line.clone()),
+            }
+        }
+    }
+
+    printer
+        .finish_file(name, count)
+        .map_err(SearchError::Write)?;
+    Ok(true)
+}
+
+/// Returns whether `bytes`, the start of a file, aren't valid UTF-8, ignoring a character
+/// that may be cut at their end
+fn starts_invalid(bytes: &[u8]) -> bool {
+    match std::str::from_utf8(bytes) {
+        Ok(_) => false,
+        Err(e) => e.error_len().is_some(),
+    }
+}
+
+/// Searches the file at `path`, or standard input if it is `-`
+fn search_path<W: Write>(
+    config: &Config,
+    matcher: &dyn Matcher,
+    path: &Path,
+    printer: &mut Printer<W>,
+) -> Result<(), SearchError> {
+    match path == Path::new(STDIN_PATH) {
+        true => {
+            let stdin = io::stdin().lock();
+            search_reader(config, matcher, stdin, Path::new(STDIN_NAME), printer)
+        }
+        false => {
+            let file = File::open(path).map_err(SearchError::Read)?;
+            search_reader(config, matcher, BufReader::new(file), path, printer)
+        }
+    }
+}
+
+/// Returns the message reporting that the file at `path` couldn't be read
+fn read_error_message(path: &Path, error: &io::Error) -> String {
+    let name = match path == Path::new(STDIN_PATH) {
+        true => Path::new(STDIN_NAME),
+        false => path,
     };
+    format!("{}: {}", name.display(), error)
+}
+
+/// Calls `visit` with every file to search, standard input included, in order
+fn visit_files(config: &Config, visit: &mut dyn FnMut(Result<PathBuf, WalkError>)) {
+    let options = config.walk_options();
+    for path in &config.paths {
+        match path == Path::new(STDIN_PATH) {
+            true => visit(Ok(path.clone())),
+            false => walk(slice::from_ref(path), &options, visit),
+        }
+    }
+}
+
+/// Searches every file of `config`, reporting the files that can't be read on stderr
+/// without stopping
+///
+/// Several files are searched at once when there may be more than one, each by a worker
+/// which buffers its output for it to be printed whole.
+///
+/// # Returns
+///
+/// The number of files that couldn't be searched, or the error writing the output
+fn search_files<W: Write>(
+    config: &Config,
+    matcher: &dyn Matcher,
+    printer: &mut Printer<W>,
+) -> io::Result<usize> {
+    let threads = config.threads();
+    if threads > 1 && (config.recursive || config.paths.len() > 1) {
+        return search_files_in_parallel(config, matcher, threads, printer);
+    }
+
+    let mut failures = 0;
+    let mut write_error = None;
+    visit_files(config, &mut |file| {
+        if write_error.is_some() {
+            return;
+        }
 
-    println!("Results:");
-    print!("{:?}", results);
+        let message = match file {
+            Ok(path) => match search_path(config, matcher, &path, printer) {
+                Ok(()) => return,
+                Err(SearchError::Read(e)) => read_error_message(&path, &e),
+                Err(SearchError::Write(e)) => {
+                    write_error = Some(e);
+                    return;
+                }
+            },
+            Err(e) => e.to_string(),
+        };
+        eprintln!("minigrep: {}", message);
+        failures += 1;
+    });
 
-    Ok(())
+    match write_error {
+        Some(e) => Err(e),
+        None => Ok(failures),
+    }
+}
+
+/// What searching a file with a printer from `Printer::buffered` produced
+struct FileOutput {
+    output: Vec<u8>,
+    stats: Stats,
+    /// The message reporting why the search failed
+    error: Option<String>,
+}
+
+/// Searches the files of `config` with `threads` workers, while a thread walks the
+/// directories and this one prints the output of each file once it is searched
+fn search_files_in_parallel<W: Write>(
+    config: &Config,
+    matcher: &dyn Matcher,
+    threads: usize,
+    printer: &mut Printer<W>,
+) -> io::Result<usize> {
+    // the files are numbered in the order they are walked, to be printed in that order
+    let (file_sender, file_receiver) = mpsc::sync_channel::<(usize, PathBuf)>(threads);
+    let file_receiver = Arc::new(Mutex::new(file_receiver));
+    let (output_sender, output_receiver) = mpsc::channel::<(usize, FileOutput)>();
+    let color = printer.color;
+
+    thread::scope(|scope| {
+        for _ in 0..threads {
+            let file_receiver = Arc::clone(&file_receiver);
+            let output_sender = output_sender.clone();
+            scope.spawn(move || loop {
+                let next_file = file_receiver.lock().unwrap().recv();
+                let Ok((index, path)) = next_file else {
+                    break;
+                };
+
+                let mut file_printer = Printer::buffered(config, color);
+                let error = match search_path(config, matcher, &path, &mut file_printer) {
+                    Ok(()) => None,
+                    Err(SearchError::Read(e)) => Some(read_error_message(&path, &e)),
+                    Err(SearchError::Write(e)) => unreachable!("writing to a Vec failed: {}", e),
+                };
+                let output = FileOutput {
+                    output: file_printer.writer,
+                    stats: file_printer.stats,
+                    error,
+                };
+                // printing stopped if the output can't be sent
+                if output_sender.send((index, output)).is_err() {
+                    break;
+                }
+            });
+        }
+        // the workers stop once all the files are taken and they can't receive any more
+        drop(file_receiver);
+
+        let walk_output_sender = output_sender.clone();
+        scope.spawn(move || {
+            let mut index = 0;
+            let mut stopped = false;
+            visit_files(config, &mut |file| {
+                if stopped {
+                    return;
+                }
+                let sent = match file {
+                    Ok(path) => file_sender.send((index, path)).is_ok(),
+                    Err(e) => {
+                        let output = FileOutput {
+                            output: Vec::new(),
+                            stats: Stats::default(),
+                            error: Some(e.to_string()),
+                        };
+                        walk_output_sender.send((index, output)).is_ok()
+                    }
+                };
+                stopped = !sent;
+                index += 1;
+            });
+        });
+        drop(output_sender);
+
+        print_file_outputs(config, output_receiver, printer)
+    })
+}
+
+/// Prints the outputs of the files as they are received, or in the order of the files with
+/// `--sort path`
+///
+/// # Returns
+///
+/// The number of files that couldn't be searched, or the error writing the output, once
+/// `outputs` is dropped so that the searches stop
+fn print_file_outputs<W: Write>(
+    config: &Config,
+    outputs: Receiver<(usize, FileOutput)>,
+    printer: &mut Printer<W>,
+) -> io::Result<usize> {
+    let mut failures = 0;
+    let mut print = |output: FileOutput| {
+        if let Some(message) = output.error {
+            eprintln!("minigrep: {}", message);
+            failures += 1;
+        }
+        printer.write_file_output(&output.output, &output.stats)
+    };
+
+    // the outputs received before those of the files walked before them
+    let mut waiting = HashMap::new();
+    let mut next_index = 0;
+    for (index, output) in outputs {
+        match config.sort {
+            None => print(output)?,
+            Some(SortBy::Path) => {
+                waiting.insert(index, output);
+                while let Some(output) = waiting.remove(&next_index) {
+                    print(output)?;
+                    next_index += 1;
+                }
+            }
+        }
+    }
+
+    Ok(failures)
+}
+
+pub fn run(config: Config) -> Result<(), Box<dyn Error>> {
+    let matcher = build_matcher(&config, &config.patterns()?)?;
+    let stdout = io::stdout();
+    let is_terminal = stdout.is_terminal();
+    let mut printer = Printer::new(&config, BufWriter::new(stdout.lock()), is_terminal);
+
+    let failures = match search_files(&config, &*matcher, &mut printer)
+        .and_then(|failures| printer.finish().map(|()| failures))
+    {
+        Ok(failures) => failures,
+        // the reader of the output, such as `head`, has seen enough
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => return Ok(()),
+        Err(e) => return Err(e.into()),
+    };
+
+    match failures {
+        0 => Ok(()),
+        1 => Err("1 file could not be searched".into()),
+        _ => Err(format!("{} files could not be searched", failures).into()),
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::fs;
 
     #[test]
     fn case_sensitive() {
@@ -89,4 +993,561 @@ mod tests {
             search_case_insensitive(query, contents)
         );
     }
+
+    #[test]
+    fn search_line_breaks() {
+        assert_eq!(search("og", "frog\r\n\nbog\ndog"), ["frog", "bog", "dog"]);
+        assert_eq!(search("", "a\n\nb\n"), ["a", "", "b"]);
+        assert_eq!(search("g\r", "frog\r\nbog\r"), ["bog\r"]);
+        assert_eq!(search("og", ""), Vec::<&str>::new());
+        assert_eq!(
+            search_regex(&Regex::new("^b").unwrap(), "ab\r\nba\r\n"),
+            ["ba"]
+        );
+    }
+
+    #[test]
+    fn regex_search() {
+        let regex = Regex::new(r"^\w+:$|\bt\w*e\.$").unwrap();
+        let contents = "\nRust:\nsafe, fast, productive.\nPick three.\nDuct tape.";
+
+        assert_eq!(
+            vec!["Rust:", "Pick three.", "Duct tape."],
+            search_regex(&regex, contents)
+        );
+    }
+
+    const POEM: &str = "\
+I'm nobody! Who are you?
+Are you nobody, too?
+Then there's a pair of us - don't tell!
+They'd banish us, you know.
+
+How dreary to be somebody!
+How public, like a frog
+To tell your name the livelong day
+To an admiring bog!";
+
+    fn config(args: &[&str]) -> Config {
+        let args: Vec<String> = ["minigrep"]
+            .iter()
+            .chain(args)
+            .chain(&["poem.txt"])
+            .map(|arg| arg.to_string())
+            .collect();
+        Config::build_with_env(&args, false).unwrap()
+    }
+
+    /// Returns what searching `contents` prints, as the file named by `config`
+    fn output_of(config: &Config, contents: &[u8]) -> String {
+        let matcher = build_matcher(config, &config.patterns().unwrap()).unwrap();
+        let mut printer = Printer::new(config, Vec::new(), false);
+        search_reader(config, &*matcher, contents, &config.paths[0], &mut printer).unwrap();
+        String::from_utf8(printer.writer).unwrap()
+    }
+
+    fn output(args: &[&str]) -> String {
+        output_of(&config(args), POEM.as_bytes())
+    }
+
+    fn selected_numbers(args: &[&str]) -> Vec<usize> {
+        output(&[&["-n"], args].concat())
+            .lines()
+            .map(|line| line.split(':').next().unwrap().parse().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn positional_arguments() {
+        let config = config(&["nobody"]);
+        assert_eq!(config.query.as_deref(), Some("nobody"));
+        assert_eq!(config.paths, [PathBuf::from("poem.txt")]);
+        assert!(!config.with_filename());
+
+        let args = ["minigrep", "nobody"].map(String::from);
+        let config = Config::build_with_env(&args, false).unwrap();
+        assert_eq!(config.paths, [PathBuf::from("-")]);
+        assert!(!config.with_filename());
+        let args = ["minigrep", "-r", "nobody"].map(String::from);
+        let config = Config::build_with_env(&args, false).unwrap();
+        assert_eq!(config.paths, [PathBuf::from(".")]);
+        let args = ["minigrep", "a", "b", "c"].map(String::from);
+        let config = Config::build_with_env(&args, false).unwrap();
+        assert_eq!(config.paths, [PathBuf::from("b"), PathBuf::from("c")]);
+        assert!(config.with_filename());
+    }
+
+    #[test]
+    fn double_dash() {
+        let config = config(&["-n", "--", "-v"]);
+        assert_eq!(config.query.as_deref(), Some("-v"));
+        assert!(config.line_number);
+        assert!(!config.invert_match);
+    }
+
+    #[test]
+    fn several_patterns() {
+        let parsed = config(&["-e", "nobody", "-e", "frog"]);
+        assert_eq!(parsed.query, None);
+        assert_eq!(parsed.paths, [PathBuf::from("poem.txt")]);
+        assert_eq!(selected_numbers(&["-e", "nobody", "-e", "frog"]), [1, 2, 7]);
+        assert_eq!(selected_numbers(&["-e", "nobody", "-e", ""]).len(), 9);
+        assert_eq!(
+            selected_numbers(&["-S", "-e", "how", "-e", "Who"]),
+            [1, 6, 7]
+        );
+        assert_eq!(selected_numbers(&["-w", "-e", "us", "-e", "to"]), [3, 4, 6]);
+        assert_eq!(
+            selected_numbers(&["-E", "-e", "^To", "-e", "bog!$"]),
+            [8, 9]
+        );
+        assert_eq!(
+            output(&["--color=always", "-e", "ody", "-e", "nobody", "-e", "Who"]),
+            "I'm \x1b[01;31mnobody\x1b[m! \x1b[01;31mWho\x1b[m are you?\n\
+             Are you \x1b[01;31mnobody\x1b[m, too?\n\
+             How dreary to be someb\x1b[01;31mody\x1b[m!\n"
+        );
+
+        let patterns =
+            std::env::temp_dir().join(format!("minigrep_patterns_{}", std::process::id()));
+        fs::write(&patterns, "frog\r\nbog\n").unwrap();
+        let file = patterns.to_str().unwrap();
+        assert_eq!(selected_numbers(&["-f", file]), [7, 9]);
+        assert_eq!(selected_numbers(&["-f", file, "-e", "Then"]), [3, 7, 9]);
+        fs::write(&patterns, "").unwrap();
+        assert!(selected_numbers(&["-f", file]).is_empty());
+        fs::remove_file(&patterns).unwrap();
+        let error = config(&["-f", file]).patterns().unwrap_err();
+        assert!(error.to_string().starts_with(file));
+
+        let args = ["minigrep"].map(String::from);
+        assert!(Config::build_with_env(&args, false).is_err());
+    }
+
+    #[test]
+    fn help() {
+        let args = ["minigrep", "--help"].map(String::from);
+        let error = Config::build_with_env(&args, false).unwrap_err();
+        assert_eq!(error.kind(), clap::error::ErrorKind::DisplayHelp);
+        assert!(error.to_string().contains("--invert-match"));
+    }
+
+    #[test]
+    fn ignore_case_flag_and_env() {
+        let args = |flags: &[&str]| -> Vec<String> {
+            ["minigrep"]
+                .iter()
+                .chain(flags)
+                .chain(&["how", "poem.txt"])
+                .map(|arg| arg.to_string())
+                .collect()
+        };
+        let ignore_case = |flags: &[&str], env| {
+            Config::build_with_env(&args(flags), env)
+                .unwrap()
+                .ignore_case
+        };
+
+        assert!(!ignore_case(&[], false));
+        assert!(ignore_case(&[], true));
+        assert!(ignore_case(&["-i"], false));
+        assert!(!ignore_case(&["--no-ignore-case"], true));
+        assert!(ignore_case(&["--no-ignore-case", "-i"], true));
+        assert!(!ignore_case(&["-i", "--no-ignore-case"], false));
+
+        assert_eq!(selected_numbers(&["How"]), vec![6, 7]);
+        assert_eq!(selected_numbers(&["-i", "how"]), vec![6, 7]);
+    }
+
+    #[test]
+    fn smart_case() {
+        let config = |flags: &[&str], env| {
+            let args: Vec<String> = ["minigrep"]
+                .iter()
+                .chain(flags)
+                .chain(&["how", "poem.txt"])
+                .map(|arg| arg.to_string())
+                .collect();
+            let config = Config::build_with_env(&args, env).unwrap();
+            (config.ignore_case, config.smart_case)
+        };
+        assert_eq!(config(&["-S"], true), (false, true));
+        assert_eq!(config(&["-i", "--smart-case"], false), (false, true));
+        assert_eq!(config(&["-S", "-i"], false), (true, false));
+        assert_eq!(config(&["-S", "--no-ignore-case"], false), (false, false));
+
+        assert_eq!(selected_numbers(&["-S", "how"]), vec![6, 7]);
+        assert_eq!(selected_numbers(&["-S", "HOW"]), Vec::<usize>::new());
+        assert_eq!(selected_numbers(&["-S", "-E", r"^\Sow"]), vec![6, 7]);
+        assert_eq!(selected_numbers(&["-S", "-E", "^How"]), vec![6, 7]);
+        assert_eq!(selected_numbers(&["-S", "-w", "TO"]), Vec::<usize>::new());
+        assert_eq!(selected_numbers(&["-S", "-w", "to"]), vec![6, 8, 9]);
+    }
+
+    #[test]
+    fn case_folding() {
+        let contents = "Die Straße\nDIE STRASSE\nstrasse\nİstanbul\nıstanbul\n";
+        let output = |args: &[&str]| output_of(&config(args), contents.as_bytes());
+
+        assert_eq!(
+            output(&["-i", "-n", "STRASSE"]),
+            "1:Die Straße\n2:DIE STRASSE\n3:strasse\n"
+        );
+        assert_eq!(
+            output(&["-i", "-n", "-w", "STRASSE"]),
+            "1:Die Straße\n2:DIE STRASSE\n3:strasse\n"
+        );
+        assert_eq!(
+            output(&["-i", "-n", "-x", "die strasse"]),
+            "1:Die Straße\n2:DIE STRASSE\n"
+        );
+        assert_eq!(
+            output(&["-S", "-n", "-w", "-e", "strasse", "-e", "die"]),
+            "1:Die Straße\n2:DIE STRASSE\n3:strasse\n"
+        );
+        // regular expressions only fold characters to single characters
+        assert_eq!(
+            output(&["-i", "-n", "-E", "STRASSE"]),
+            "2:DIE STRASSE\n3:strasse\n"
+        );
+        assert_eq!(output(&["-i", "-n", "istanbul"]), "");
+        assert_eq!(output(&["-i", "-n", "ISTANBUL"]), "");
+        assert_eq!(output(&["-i", "-n", "ıSTANBUL"]), "5:ıstanbul\n");
+        assert_eq!(
+            output(&["-i", "--color=always", "ss"]),
+            "Die Stra\x1b[01;31mß\x1b[me\nDIE STRA\x1b[01;31mSS\x1b[mE\nstra\x1b[01;31mss\x1b[me\n"
+        );
+    }
+
+    #[test]
+    fn regex_flag() {
+        assert!(config(&["-E", "a+"]).regex);
+        assert!(config(&["--regex", "a+"]).regex);
+        assert_eq!(selected_numbers(&["-E", "^To (tell|an)"]), vec![8, 9]);
+        assert_eq!(selected_numbers(&["To (tell|an)"]), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn invert_match() {
+        assert_eq!(selected_numbers(&["-v", "o"]), vec![5]);
+        assert_eq!(
+            selected_numbers(&["--invert-match", "-i", "TO"]),
+            vec![1, 3, 4, 5, 7]
+        );
+    }
+
+    #[test]
+    fn word_regexp() {
+        assert_eq!(selected_numbers(&["-w", "us"]), vec![3, 4]);
+        assert_eq!(selected_numbers(&["-w", "body"]), Vec::<usize>::new());
+        assert_eq!(selected_numbers(&["-w", "-i", "how"]), vec![6, 7]);
+        assert_eq!(
+            selected_numbers(&["--word-regexp", "-E", "n\\w+y"]),
+            vec![1, 2]
+        );
+    }
+
+    #[test]
+    fn line_regexp() {
+        assert_eq!(
+            selected_numbers(&["-x", "How public, like a frog"]),
+            vec![7]
+        );
+        assert_eq!(selected_numbers(&["-x", "How public"]), Vec::<usize>::new());
+        assert_eq!(
+            selected_numbers(&["--line-regexp", "-E", "[^!]*!"]),
+            vec![3, 6, 9]
+        );
+    }
+
+    #[test]
+    fn count() {
+        assert_eq!(output(&["-c", "you"]), "4\n");
+        assert_eq!(output(&["--count", "-v", "you"]), "5\n");
+    }
+
+    #[test]
+    fn line_number() {
+        assert_eq!(output(&["-n", "frog"]), "7:How public, like a frog\n");
+        assert_eq!(output(&["frog"]), "How public, like a frog\n");
+        assert_eq!(
+            output(&["-n", "nobody"]),
+            "1:I'm nobody! Who are you?\n2:Are you nobody, too?\n"
+        );
+    }
+
+    #[test]
+    fn files_with_matches() {
+        assert_eq!(output(&["-l", "frog"]), "poem.txt\n");
+        assert_eq!(output(&["--files-with-matches", "toad"]), "");
+    }
+
+    #[test]
+    fn with_filename() {
+        assert_eq!(
+            output(&["-H", "-n", "frog"]),
+            "poem.txt:7:How public, like a frog\n"
+        );
+        assert_eq!(output(&["--with-filename", "-c", "frog"]), "poem.txt:1\n");
+        assert!(config(&["-r", "frog"]).with_filename());
+    }
+
+    #[test]
+    fn recursive_search_and_file_errors() {
+        let root = std::env::temp_dir().join(format!("minigrep_lib_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("poems")).unwrap();
+        fs::write(root.join("poems/nobody.txt"), POEM).unwrap();
+        fs::write(root.join("poems/frog.md"), "a frog\n").unwrap();
+        fs::write(root.join("binary.txt"), b"frog\xFF\nfrog\n").unwrap();
+
+        let path = |path: &str| root.join(path).to_string_lossy().to_string();
+        let config = config(&[
+            "-r",
+            "-j1",
+            "--include",
+            "*.txt",
+            "-c",
+            "frog",
+            &path("poems"),
+            &path("missing"),
+            &path("binary.txt"),
+        ]);
+        let matcher = build_matcher(&config, &config.patterns().unwrap()).unwrap();
+        let mut printer = Printer::new(&config, Vec::new(), false);
+        let failures = search_files(&config, &*matcher, &mut printer).unwrap();
+        fs::remove_dir_all(&root).unwrap();
+
+        // the two failures are the missing file and poem.txt
+        assert_eq!(failures, 2);
+        assert_eq!(
+            String::from_utf8(printer.writer).unwrap(),
+            format!(
+                "{}:1\n{}:2\n",
+                root.join("poems").join("nobody.txt").display(),
+                root.join("binary.txt").display()
+            )
+        );
+        assert_eq!(
+            Config::build_with_env(
+                &["minigrep", "--include", "[z-a]", "x", "y"].map(String::from),
+                false
+            )
+            .unwrap_err()
+            .kind(),
+            clap::error::ErrorKind::ValueValidation
+        );
+    }
+
+    #[test]
+    fn parallel_search() {
+        let root = std::env::temp_dir().join(format!("minigrep_parallel_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        for dir in 0..4 {
+            fs::create_dir_all(root.join(format!("dir{}", dir))).unwrap();
+            for file in 0..10 {
+                let path = root.join(format!("dir{}/poem{}.txt", dir, file));
+                fs::write(path, POEM.repeat(file)).unwrap();
+            }
+        }
+        let root_path = root.to_string_lossy().to_string();
+
+        let search = |args: &[&str]| {
+            let config = config(&[args, &["-r", "frog", &root_path]].concat());
+            let matcher = build_matcher(&config, &config.patterns().unwrap()).unwrap();
+            let mut printer = Printer::new(&config, Vec::new(), false);
+            let failures = search_files(&config, &*matcher, &mut printer).unwrap();
+            (String::from_utf8(printer.writer).unwrap(), failures)
+        };
+        let (sequential, failures) = search(&["-j1", "-n", "-C1"]);
+
+        // poem.txt is missing
+        assert_eq!(failures, 1);
+        assert_eq!(sequential.matches("poem0.txt").count(), 0);
+        assert_eq!(sequential.matches("--\n").count(), 4 * 45 - 1);
+        assert_eq!(
+            search(&["-j4", "-n", "-C1", "--sort", "path"]),
+            (sequential, 1)
+        );
+
+        // the lines of each file stay together, whichever order the files are in
+        let (unsorted, _) = search(&["-j4", "-c"]);
+        let (sorted, _) = search(&["-j1", "-c"]);
+        let mut unsorted: Vec<&str> = unsorted.lines().collect();
+        unsorted.sort_unstable();
+        let mut sorted: Vec<&str> = sorted.lines().collect();
+        sorted.sort_unstable();
+        assert_eq!(unsorted, sorted);
+        assert_eq!(sorted.len(), 40);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn max_count() {
+        assert_eq!(selected_numbers(&["-m", "2", "you"]), vec![1, 2]);
+        assert_eq!(
+            selected_numbers(&["--max-count", "0", "you"]),
+            Vec::<usize>::new()
+        );
+        assert_eq!(output(&["-c", "-m1", "you"]), "1\n");
+    }
+
+    #[test]
+    fn streaming_lines() {
+        let config = config(&["-n", "frog"]);
+        let matcher = build_matcher(&config, &config.patterns().unwrap()).unwrap();
+        let long_line = format!("{}frog{}", "a".repeat(10_000), "b".repeat(10_000));
+        let contents = format!("frog\r\ntoad\n{}\nfrog", long_line);
+        // a tiny buffer makes every line span several reads
+        let reader = BufReader::with_capacity(4, contents.as_bytes());
+        let mut printer = Printer::new(&config, Vec::new(), false);
+        search_reader(&config, &*matcher, reader, Path::new("-"), &mut printer).unwrap();
+
+        assert_eq!(
+            String::from_utf8(printer.writer).unwrap(),
+            format!("1:frog\n3:{}\n4:frog\n", long_line)
+        );
+    }
+
+    #[test]
+    fn binary_files() {
+        let contents: &[u8] = b"frog\n\xFFfrog\xFE\nfrog\n";
+        let output = |args: &[&str], capacity| {
+            let config = config(args);
+            let matcher = build_matcher(&config, &config.patterns().unwrap()).unwrap();
+            let reader = BufReader::with_capacity(capacity, contents);
+            let mut printer = Printer::new(&config, Vec::new(), false);
+            search_reader(&config, &*matcher, reader, Path::new("-"), &mut printer).unwrap();
+            String::from_utf8(printer.writer).unwrap()
+        };
+
+        assert_eq!(output(&["frog"], 64), "frog\n\u{FFFD}frog\u{FFFD}\nfrog\n");
+        assert_eq!(output(&["-E", "^.frog.$"], 64), "\u{FFFD}frog\u{FFFD}\n");
+        assert_eq!(output(&["--binary-files=text", "-c", "frog"], 64), "3\n");
+        assert_eq!(output(&["--binary-files", "without-match", "frog"], 64), "");
+        assert_eq!(
+            output(&["--binary-files=without-match", "-c", "frog"], 64),
+            "0\n"
+        );
+        // the lines before the first invalid one are dropped too
+        assert_eq!(output(&["--binary-files=without-match", "frog"], 5), "");
+        assert_eq!(
+            output(&["--binary-files=without-match", "-c", "frog"], 5),
+            "0\n"
+        );
+        assert_eq!(
+            output(&["--binary-files=without-match", "-m1", "frog"], 5),
+            ""
+        );
+        assert_eq!(
+            output_of(
+                &config(&["--binary-files=without-match", "-n", "-A1", "frog"]),
+                POEM.as_bytes()
+            ),
+            "7:How public, like a frog\n8-To tell your name the livelong day\n"
+        );
+        assert_eq!(config(&["frog"]).binary_files, BinaryFiles::Text);
+    }
+
+    #[test]
+    fn byte_offset() {
+        assert_eq!(output(&["-b", "frog"]), "142:How public, like a frog\n");
+        assert_eq!(
+            output(&["-n", "--byte-offset", "-H", "frog"]),
+            "poem.txt:7:142:How public, like a frog\n"
+        );
+    }
+
+    #[test]
+    fn context() {
+        assert_eq!(
+            output(&["-n", "-A", "1", "-B", "2", "frog"]),
+            "5-\n6-How dreary to be somebody!\n7:How public, like a frog\n8-To tell your name the livelong day\n"
+        );
+        assert_eq!(
+            output(&["-C1", "-n", "-E", "^Are|^How d"]),
+            "1-I'm nobody! Who are you?\n2:Are you nobody, too?\n3-Then there's a pair of us - don't tell!\n--\n5-\n6:How dreary to be somebody!\n7-How public, like a frog\n"
+        );
+        // adjacent groups are merged, and -A overrides -C
+        assert_eq!(
+            output(&["-n", "-C", "2", "-A", "0", "-E", "^To"]),
+            "6-How dreary to be somebody!\n7-How public, like a frog\n8:To tell your name the livelong day\n9:To an admiring bog!\n"
+        );
+        // trailing context is printed after the last selected line
+        assert_eq!(
+            output(&["-m1", "-A1", "-n", "you"]),
+            "1:I'm nobody! Who are you?\n2-Are you nobody, too?\n"
+        );
+        assert_eq!(output(&["-c", "-C3", "frog"]), "1\n");
+    }
+
+    #[test]
+    fn color() {
+        assert_eq!(config(&["frog"]).color, ColorChoice::Auto);
+        assert_eq!(config(&["--color", "frog"]).color, ColorChoice::Auto);
+        assert_eq!(
+            output(&["--color=always", "-H", "-n", "-i", "o"])
+                .lines()
+                .nth(5)
+                .unwrap(),
+            "\x1b[35mpoem.txt\x1b[m\x1b[36m:\x1b[m\x1b[32m7\x1b[m\x1b[36m:\x1b[m\
+             H\x1b[01;31mo\x1b[mw public, like a fr\x1b[01;31mo\x1b[mg"
+        );
+        assert_eq!(output(&["--colour=always", "-v", "o"]), "\n");
+        assert_eq!(
+            output(&["--color=never", "frog"]),
+            "How public, like a frog\n"
+        );
+
+        let config = config(&["--color=auto", "frog"]);
+        let mut printer = Printer::new(&config, Vec::new(), true);
+        let matcher = build_matcher(&config, &config.patterns().unwrap()).unwrap();
+        search_reader(
+            &config,
+            &*matcher,
+            "a frog".as_bytes(),
+            Path::new("-"),
+            &mut printer,
+        )
+        .unwrap();
+        assert_eq!(printer.writer, b"a \x1b[01;31mfrog\x1b[m\n");
+    }
+
+    #[test]
+    fn json() {
+        let config = config(&["--json", "--color=always", "-B1", "-e", "frog", "-e", "bog"]);
+        let matcher = build_matcher(&config, &config.patterns().unwrap()).unwrap();
+        let mut printer = Printer::new(&config, Vec::new(), true);
+        let mut search = |contents: &[u8], name: &str| {
+            search_reader(&config, &*matcher, contents, Path::new(name), &mut printer).unwrap();
+        };
+        search(POEM.as_bytes(), "poem.txt");
+        search(b"toad\n", "toad.txt");
+        search(b"\"frog\"\tbog\r\nfrog\xFF\n", "a \"frog\".txt");
+        printer.finish().unwrap();
+
+        let output = String::from_utf8(printer.writer).unwrap();
+        assert_eq!(
+            output.lines().collect::<Vec<_>>(),
+            [
+                r#"{"type":"begin","data":{"path":{"text":"poem.txt"}}}"#,
+                r#"{"type":"context","data":{"path":{"text":"poem.txt"},"lines":{"text":"How dreary to be somebody!"},"line_number":6,"absolute_offset":115,"submatches":[]}}"#,
+                r#"{"type":"match","data":{"path":{"text":"poem.txt"},"lines":{"text":"How public, like a frog"},"line_number":7,"absolute_offset":142,"submatches":[{"match":{"text":"frog"},"start":19,"end":23}]}}"#,
+                r#"{"type":"context","data":{"path":{"text":"poem.txt"},"lines":{"text":"To tell your name the livelong day"},"line_number":8,"absolute_offset":166,"submatches":[]}}"#,
+                r#"{"type":"match","data":{"path":{"text":"poem.txt"},"lines":{"text":"To an admiring bog!"},"line_number":9,"absolute_offset":201,"submatches":[{"match":{"text":"bog"},"start":15,"end":18}]}}"#,
+                r#"{"type":"end","data":{"path":{"text":"poem.txt"},"stats":{"searches":1,"searches_with_match":1,"matched_lines":2,"matches":2}}}"#,
+                r#"{"type":"begin","data":{"path":{"text":"toad.txt"}}}"#,
+                r#"{"type":"end","data":{"path":{"text":"toad.txt"},"stats":{"searches":1,"searches_with_match":0,"matched_lines":0,"matches":0}}}"#,
+                r#"{"type":"begin","data":{"path":{"text":"a \"frog\".txt"}}}"#,
+                r#"{"type":"match","data":{"path":{"text":"a \"frog\".txt"},"lines":{"text":"\"frog\"\tbog"},"line_number":1,"absolute_offset":0,"submatches":[{"match":{"text":"frog"},"start":1,"end":5},{"match":{"text":"bog"},"start":7,"end":10}]}}"#,
+                r#"{"type":"match","data":{"path":{"text":"a \"frog\".txt"},"lines":{"bytes":"ZnJvZ/8="},"line_number":2,"absolute_offset":12,"submatches":[{"match":{"text":"frog"},"start":0,"end":4}]}}"#,
+                r#"{"type":"end","data":{"path":{"text":"a \"frog\".txt"},"stats":{"searches":1,"searches_with_match":1,"matched_lines":2,"matches":3}}}"#,
+                r#"{"type":"summary","data":{"stats":{"searches":3,"searches_with_match":2,"matched_lines":4,"matches":5}}}"#,
+            ]
+        );
+
+        let args = ["minigrep", "--json", "-c", "frog"].map(String::from);
+        let error = Config::build_with_env(&args, false).unwrap_err();
+        assert_eq!(error.kind(), clap::error::ErrorKind::ArgumentConflict);
+    }
 }
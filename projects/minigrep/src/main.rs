@@ -5,11 +5,8 @@ use minigrep::Config;
 
 fn main() {
     let args: Vec<String> = env::args().collect();
-    
-    let config = Config::build(&args).unwrap_or_else(|err| {
-        eprintln!("Problem parsing arguments: {}", err);
-        process::exit(1);
-    });
+
+    let config = Config::build(&args).unwrap_or_else(|err| err.exit());
 
     if let Err(e) = minigrep::run(config) {
         eprintln!("Application error: {}", e);
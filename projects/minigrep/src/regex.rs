@@ -0,0 +1,413 @@
+use std::error::Error;
+use std::fmt;
+use std::ops::Range;
+
+use nfa::Program;
+pub(crate) use nfa::{char_before, decode};
+pub(crate) use parse::is_word_char;
+use parse::{Assertion, Node, Parser, MAX_NESTING, MAX_REPEAT};
+
+mod nfa;
+mod parse;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ErrorKind {
+    UnclosedGroup,
+    UnmatchedParen,
+    UnclosedClass,
+    /// A class range whose end comes before its start, such as `[z-a]`
+    InvalidRange,
+    /// A quantifier with nothing before it, such as `*a` or `a**`
+    NothingToRepeat,
+    /// A counted repetition whose minimum exceeds its maximum, such as `a{3,1}`
+    InvalidRepetition,
+    RepetitionTooLarge,
+    /// Groups nested deeper than the limit, such as 20000 times `(?:`
+    NestingTooDeep,
+    TrailingBackslash,
+    UnknownEscape(char),
+    /// An escape standing for something other than characters inside a class, like `[\b]`
+    InvalidClassEscape,
+    InvalidHex,
+    UnclosedProperty,
+    UnknownProperty(String),
+    UnknownGroupFlag(char),
+    /// The compiled pattern exceeds the size limit of the engine
+    TooLarge,
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ErrorKind::UnclosedGroup => write!(f, "unclosed group"),
+            ErrorKind::UnmatchedParen => write!(f, "unmatched closing parenthesis"),
+            ErrorKind::UnclosedClass => write!(f, "unclosed character class"),
+            ErrorKind::InvalidRange => write!(f, "invalid character class range"),
+            ErrorKind::NothingToRepeat => {
+                write!(f, "repetition operator without anything to repeat")
+            }
+            ErrorKind::InvalidRepetition => {
+                write!(f, "invalid repetition, the minimum exceeds the maximum")
+            }
+            ErrorKind::RepetitionTooLarge => {
+                write!(f, "repetition count exceeds the limit of {}", MAX_REPEAT)
+            }
+            ErrorKind::NestingTooDeep => {
+                write!(
+                    f,
+                    "groups are nested deeper than the limit of {}",
+                    MAX_NESTING
+                )
+            }
+            ErrorKind::TrailingBackslash => write!(f, "pattern ends with a backslash"),
+            ErrorKind::UnknownEscape(c) => write!(f, "unknown escape sequence \\{}", c),
+            ErrorKind::InvalidClassEscape => {
+                write!(f, "escape sequence not allowed in a character class")
+            }
+            ErrorKind::InvalidHex => write!(f, "invalid hexadecimal escape sequence"),
+            ErrorKind::UnclosedProperty => write!(f, "unclosed Unicode property name"),
+            ErrorKind::UnknownProperty(name) => write!(f, "unknown Unicode property {}", name),
+            ErrorKind::UnknownGroupFlag(c) => write!(f, "unknown group flag {}", c),
+            ErrorKind::TooLarge => write!(f, "compiled pattern is too large"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Error compiling a pattern
+pub struct RegexError {
+    pub kind: ErrorKind,
+    /// The byte offset in the pattern of the token at fault
+    pub position: usize,
+}
+
+impl fmt::Display for RegexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at position {}", self.kind, self.position)
+    }
+}
+
+impl Error for RegexError {}
+
+#[derive(Debug, Clone)]
+/// A regular expression, matched in time linear in the length of the text
+///
+/// The syntax is the usual one:
+///
+/// - literals, with `\` escaping punctuation, and `\n`, `\t`, `\xHH` and `\x{HHHH}`
+/// - `.`, any character but a newline
+/// - classes such as `[a-z_]` or `[^0-9]`, and `\d`, `\w`, `\s` and their negations
+/// - Unicode properties `\p{Name}` or `\pN`, and their negation `\P{Name}`, for the
+///   properties `Alphabetic` (`L`), `Lowercase` (`Ll`), `Uppercase` (`Lu`),
+///   `White_Space`, `Number` (`N`), `Alphanumeric`, `Control` (`Cc`), `ASCII` and `Any`
+/// - the anchors `^` and `$` for the start and end of the text, and `\b` and `\B`
+/// - alternation `a|b` and groups `(...)`
+/// - the quantifiers `*`, `+`, `?`, `{n}`, `{n,}` and `{n,m}`, made lazy by a `?`
+/// - the flag groups `(?i)`, `(?-i)` and `(?i:...)` toggling case-insensitivity
+///
+/// Case-insensitive matching compares the simple case foldings of characters, so `ß`
+/// doesn't match `ss`.
+///
+/// Text that isn't valid UTF-8 is matched as if each invalid sequence were `U+FFFD`.
+pub struct Regex {
+    pattern: String,
+    program: Program,
+}
+
+impl Regex {
+    pub fn new(pattern: &str) -> Result<Regex, RegexError> {
+        RegexBuilder::new().build(pattern)
+    }
+
+    /// Compiles `pattern` so that it ignores case, as if it started with `(?i)`
+    pub fn case_insensitive(pattern: &str) -> Result<Regex, RegexError> {
+        RegexBuilder::new().case_insensitive(true).build(pattern)
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.pattern
+    }
+
+    pub fn is_match(&self, haystack: &str) -> bool {
+        self.find(haystack).is_some()
+    }
+
+    /// Returns the byte range of the leftmost match in `haystack`
+    pub fn find(&self, haystack: &str) -> Option<Range<usize>> {
+        self.find_at(haystack.as_bytes(), 0)
+    }
+
+    /// Returns the byte range of the leftmost match in `haystack` starting at or after
+    /// `start`
+    ///
+    /// Unlike searching `&haystack[start..]`, this sees the text before `start`, so that
+    /// `^` and `\b` behave as they do in the whole text.
+    pub fn find_at(&self, haystack: &[u8], start: usize) -> Option<Range<usize>> {
+        self.program.find_at(haystack, start)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+/// Compiles a `Regex` with options
+pub struct RegexBuilder {
+    case_insensitive: bool,
+    smart_case: bool,
+    whole_words: bool,
+    whole_text: bool,
+}
+
+impl RegexBuilder {
+    pub fn new() -> RegexBuilder {
+        RegexBuilder::default()
+    }
+
+    /// Ignores case, as if the pattern started with `(?i)`
+    pub fn case_insensitive(&mut self, yes: bool) -> &mut RegexBuilder {
+        self.case_insensitive = yes;
+        self
+    }
+
+    /// Ignores case if the pattern has no uppercase character, outside of escapes such as
+    /// `\W`
+    pub fn smart_case(&mut self, yes: bool) -> &mut RegexBuilder {
+        self.smart_case = yes;
+        self
+    }
+
+    /// Only matches text with no word character right before or after it, like `grep -w`
+    pub fn whole_words(&mut self, yes: bool) -> &mut RegexBuilder {
+        self.whole_words = yes;
+        self
+    }
+
+    /// Only matches the whole text, as if the pattern were `^(?:pattern)$`
+    pub fn whole_text(&mut self, yes: bool) -> &mut RegexBuilder {
+        self.whole_text = yes;
+        self
+    }
+
+    pub fn build(&self, pattern: &str) -> Result<Regex, RegexError> {
+        let mut node = Parser::parse(pattern, self.case_insensitive)?;
+        if self.smart_case && !self.case_insensitive && !node.has_uppercase() {
+            node = Parser::parse(pattern, true)?;
+        }
+
+        let surround = |node, before, after| Node::Concat(vec![before, node, after]);
+        if self.whole_words {
+            node = surround(
+                node,
+                Node::Assert(Assertion::NoWordBefore),
+                Node::Assert(Assertion::NoWordAfter),
+            );
+        }
+        if self.whole_text {
+            node = surround(
+                node,
+                Node::Assert(Assertion::StartText),
+                Node::Assert(Assertion::EndText),
+            );
+        }
+
+        Ok(Regex {
+            pattern: pattern.to_string(),
+            program: Program::compile(&node)?,
+        })
+    }
+}
+
+/// Escapes the punctuation of `literal`, so that it compiles to a pattern matching it
+pub fn escape(literal: &str) -> String {
+    let mut escaped = String::with_capacity(literal.len());
+    for c in literal.chars() {
+        if c.is_ascii_punctuation() {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn find(pattern: &str, haystack: &str) -> Option<Range<usize>> {
+        Regex::new(pattern).unwrap().find(haystack)
+    }
+
+    fn error(pattern: &str) -> (ErrorKind, usize) {
+        let error = Regex::new(pattern).unwrap_err();
+        (error.kind, error.position)
+    }
+
+    #[test]
+    fn literals_and_classes() {
+        assert_eq!(find("duct", "safe, fast, productive."), Some(15..19));
+        assert_eq!(find("a.c", "abc"), Some(0..3));
+        assert_eq!(find("a.c", "a\nc"), None);
+        assert_eq!(find("[0-9]+", "abc 123 def"), Some(4..7));
+        assert_eq!(find("[^a-c ]+", "abc xyz"), Some(4..7));
+        assert_eq!(find("[]a]+", "x]a]"), Some(1..4));
+        assert_eq!(find("[a-]+", "-a-"), Some(0..3));
+        assert_eq!(find(r"\d\s\w+", "no 1 way"), Some(3..8));
+        assert_eq!(find(r"\.\*\x41\x{1F600}", ".*A😀"), Some(0..7));
+    }
+
+    #[test]
+    fn anchors_and_boundaries() {
+        assert_eq!(find("^Rust", "Rust: Rust"), Some(0..4));
+        assert_eq!(find("^me", "Trust me"), None);
+        assert_eq!(find("me$", "Trust me"), Some(6..8));
+        assert_eq!(find(r"\bcat\b", "concat cat"), Some(7..10));
+        assert_eq!(find(r"\Bcat", "cat concat"), Some(7..10));
+
+        let regex = Regex::new(r"^a|\ba").unwrap();
+        assert_eq!(regex.find_at(b"ba a", 1), Some(3..4));
+    }
+
+    #[test]
+    fn alternation_and_quantifiers() {
+        assert_eq!(find("fast|safe", "safe, fast"), Some(0..4));
+        assert_eq!(find("gr(a|e)y", "grey"), Some(0..4));
+        assert_eq!(find("(?:ab)+", "xababa"), Some(1..5));
+        assert_eq!(find("ab*", "abbbc"), Some(0..4));
+        assert_eq!(find("ab*?", "abbbc"), Some(0..1));
+        assert_eq!(find("ab+?", "abbbc"), Some(0..2));
+        assert_eq!(find("colou?r", "color"), Some(0..5));
+        assert_eq!(find("a{2,3}", "aaaa"), Some(0..3));
+        assert_eq!(find("a{2,}", "aaaa"), Some(0..4));
+        assert_eq!(find("a{2}", "a aa"), Some(2..4));
+        assert_eq!(find("a{,2}", "a{,2}"), Some(0..5));
+        assert_eq!(find("x*", "abc"), Some(0..0));
+        assert_eq!(find("(a|ab)(c|bcd)", "abcd"), Some(0..4));
+    }
+
+    #[test]
+    fn unicode() {
+        assert_eq!(find(r"\p{L}+", "123 naïve"), Some(4..10));
+        assert_eq!(find(r"\p{Lu}\p{Ll}+", "the Ελλάδα"), Some(4..16));
+        assert_eq!(find(r"\P{Alphabetic}", "abc1"), Some(3..4));
+        assert_eq!(find(r"\pN+", "x٣٤"), Some(1..5));
+        assert_eq!(find(r"\w+", "¡hola!"), Some(2..6));
+        assert_eq!(find("[α-ω]+", "abγδ"), Some(2..6));
+        assert_eq!(find(r"\s", "a\u{3000}b"), Some(1..4));
+    }
+
+    #[test]
+    fn case_insensitivity() {
+        let regex = Regex::case_insensitive("rust|[x-z]").unwrap();
+        assert_eq!(regex.find("Trust me"), Some(1..5));
+        assert_eq!(regex.find("XYZ"), Some(0..1));
+        assert_eq!(find("(?i)Straße", "STRAßE"), Some(0..7));
+        assert_eq!(find("a(?i:b)c", "aBc"), Some(0..3));
+        assert_eq!(find("a(?i:b)c", "aBC"), None);
+        assert_eq!(find("(?i)[^a]", "A"), None);
+        assert_eq!(find("(?i)σοφος", "ΣΟΦΟΣ"), Some(0..10));
+        assert_eq!(find("(?i)[s]+", "Sſs"), Some(0..4));
+        assert_eq!(find("(?i)ı", "I"), None);
+        assert_eq!(find("(?i)I", "ı"), None);
+        assert_eq!(find("(?i)[i]", "İ"), None);
+    }
+
+    #[test]
+    fn smart_case() {
+        let smart_case = |pattern| RegexBuilder::new().smart_case(true).build(pattern).unwrap();
+        assert_eq!(smart_case("rust").find("RUST"), Some(0..4));
+        assert_eq!(smart_case(r"\W\S\p{Lu}").find(" xY"), Some(0..3));
+        assert_eq!(smart_case(r"\W\S\p{Lu}").find(" XY"), Some(0..3));
+        assert_eq!(smart_case("Rust").find("RUST"), None);
+        assert_eq!(smart_case("[A-Z]x").find("aX"), None);
+        assert_eq!(smart_case("(?-i)a").find("A"), None);
+    }
+
+    #[test]
+    fn builder_options() {
+        let words = RegexBuilder::new()
+            .whole_words(true)
+            .build("a+|-b")
+            .unwrap();
+        assert_eq!(words.find("aab aa"), Some(4..6));
+        assert_eq!(words.find("x-b -b"), Some(4..6));
+        assert_eq!(words.find("-bc"), None);
+
+        let line = RegexBuilder::new()
+            .whole_text(true)
+            .case_insensitive(true)
+            .build("a|ab")
+            .unwrap();
+        assert_eq!(line.find("AB"), Some(0..2));
+        assert_eq!(line.find("abc"), None);
+        assert_eq!(
+            RegexBuilder::new()
+                .whole_text(true)
+                .build("a)(b")
+                .unwrap_err(),
+            RegexError {
+                kind: ErrorKind::UnmatchedParen,
+                position: 1
+            }
+        );
+
+        let literal = "1+1=(2) [sic] a|b ^$ \\d";
+        assert_eq!(
+            Regex::new(&escape(literal)).unwrap().find(literal),
+            Some(0..literal.len())
+        );
+    }
+
+    #[test]
+    fn invalid_utf8() {
+        let regex = Regex::new("a.b").unwrap();
+        assert_eq!(regex.find_at(b"xa\xFFb", 0), Some(1..4));
+        assert_eq!(regex.find_at(b"a\xF0\x9F\x98b", 0), Some(0..5));
+    }
+
+    #[test]
+    fn linear_time() {
+        let haystack = "a".repeat(20_000);
+        assert_eq!(find("(a*)*b", &haystack), None);
+        assert_eq!(find("(a|aa)+$", &haystack), Some(0..20_000));
+    }
+
+    #[test]
+    fn deep_nesting() {
+        let nested = |depth: usize| format!("{}a{}", "(".repeat(depth), ")".repeat(depth));
+
+        assert_eq!(find(&nested(MAX_NESTING), "xa"), Some(1..2));
+        assert_eq!(
+            error(&nested(MAX_NESTING + 1)),
+            (ErrorKind::NestingTooDeep, MAX_NESTING)
+        );
+    }
+
+    #[test]
+    fn error_positions() {
+        assert_eq!(error("ab(cd"), (ErrorKind::UnclosedGroup, 2));
+        assert_eq!(error("ab)"), (ErrorKind::UnmatchedParen, 2));
+        assert_eq!(error("x[a-z"), (ErrorKind::UnclosedClass, 1));
+        assert_eq!(error("[a-cz-x]"), (ErrorKind::InvalidRange, 4));
+        assert_eq!(error("a|*b"), (ErrorKind::NothingToRepeat, 2));
+        assert_eq!(error("a**"), (ErrorKind::NothingToRepeat, 2));
+        assert_eq!(error("a{3,1}"), (ErrorKind::InvalidRepetition, 1));
+        assert_eq!(error("a{1001}"), (ErrorKind::RepetitionTooLarge, 1));
+        assert_eq!(error(r"ab\"), (ErrorKind::TrailingBackslash, 2));
+        assert_eq!(error(r"é\q"), (ErrorKind::UnknownEscape('q'), 2));
+        assert_eq!(error(r"[\b]"), (ErrorKind::InvalidClassEscape, 1));
+        assert_eq!(error(r"\xZZ"), (ErrorKind::InvalidHex, 0));
+        assert_eq!(
+            error(r"a\p{Klingon}"),
+            (ErrorKind::UnknownProperty("Klingon".to_string()), 1)
+        );
+        assert_eq!(error("(?x)"), (ErrorKind::UnknownGroupFlag('x'), 2));
+        assert_eq!(error("(a{1000}){1000}").0, ErrorKind::TooLarge);
+        assert_eq!(
+            error(&"(?:".repeat(20_000)),
+            (ErrorKind::NestingTooDeep, 750)
+        );
+        assert_eq!(
+            Regex::new("a(b").unwrap_err().to_string(),
+            "unclosed group at position 1"
+        );
+    }
+}
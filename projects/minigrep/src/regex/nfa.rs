@@ -0,0 +1,341 @@
+use std::char::REPLACEMENT_CHARACTER;
+use std::mem;
+use std::ops::Range;
+
+use super::parse::{is_word_char, Assertion, CharClass, Node};
+use super::{ErrorKind, RegexError};
+use crate::casefold::simple_fold;
+
+/// The most instructions a compiled pattern may have, to bound the cost of matching
+const MAX_INSTRUCTIONS: usize = 100_000;
+
+#[derive(Debug, Clone)]
+enum Inst {
+    Char {
+        c: char,
+        fold: bool,
+    },
+    /// A character of `Program::classes[class]`
+    Class {
+        class: usize,
+        fold: bool,
+    },
+    Assert(Assertion),
+    /// Continues at both targets, preferring the first one
+    Split(usize, usize),
+    Jump(usize),
+    Match,
+}
+
+#[derive(Debug, Clone)]
+/// A pattern compiled to a non-deterministic automaton, run as a Pike VM
+///
+/// The VM advances every live thread of the automaton in lockstep over the text, one
+/// character at a time, and keeps at most one thread per instruction, so matching takes
+/// `O(text length * program length)` time whatever the pattern. Threads are kept in
+/// priority order, which gives the same leftmost-first match a backtracking engine finds.
+pub(super) struct Program {
+    insts: Vec<Inst>,
+    classes: Vec<CharClass>,
+}
+
+impl Program {
+    pub fn compile(node: &Node) -> Result<Program, RegexError> {
+        let mut program = Program {
+            insts: Vec::new(),
+            classes: Vec::new(),
+        };
+        program.compile_node(node)?;
+        program.emit(Inst::Match)?;
+
+        Ok(program)
+    }
+
+    fn emit(&mut self, inst: Inst) -> Result<usize, RegexError> {
+        if self.insts.len() >= MAX_INSTRUCTIONS {
+            return Err(RegexError {
+                kind: ErrorKind::TooLarge,
+                position: 0,
+            });
+        }
+        self.insts.push(inst);
+
+        Ok(self.insts.len() - 1)
+    }
+
+    fn compile_node(&mut self, node: &Node) -> Result<(), RegexError> {
+        match node {
+            Node::Empty => (),
+            Node::Literal { c, fold } => {
+                self.emit(Inst::Char { c: *c, fold: *fold })?;
+            }
+            Node::Class { class, fold } => {
+                self.classes.push(class.clone());
+                self.emit(Inst::Class {
+                    class: self.classes.len() - 1,
+                    fold: *fold,
+                })?;
+            }
+            Node::Assert(assertion) => {
+                self.emit(Inst::Assert(*assertion))?;
+            }
+            Node::Concat(nodes) => {
+                for node in nodes {
+                    self.compile_node(node)?;
+                }
+            }
+            Node::Alternate(branches) => {
+                let mut jumps = Vec::new();
+                let (last, rest) = branches.split_last().unwrap();
+
+                for branch in rest {
+                    let split = self.emit(Inst::Split(0, 0))?;
+                    self.compile_node(branch)?;
+                    jumps.push(self.emit(Inst::Jump(0))?);
+                    self.insts[split] = Inst::Split(split + 1, self.insts.len());
+                }
+                self.compile_node(last)?;
+
+                let end = self.insts.len();
+                for jump in jumps {
+                    self.insts[jump] = Inst::Jump(end);
+                }
+            }
+            Node::Repeat {
+                node,
+                min,
+                max,
+                greedy,
+            } => {
+                for _ in 0..*min {
+                    self.compile_node(node)?;
+                }
+
+                let split = |next, end| match greedy {
+                    true => Inst::Split(next, end),
+                    false => Inst::Split(end, next),
+                };
+                match max {
+                    None => {
+                        let start = self.emit(Inst::Split(0, 0))?;
+                        self.compile_node(node)?;
+                        self.emit(Inst::Jump(start))?;
+                        self.insts[start] = split(start + 1, self.insts.len());
+                    }
+                    Some(max) => {
+                        let mut splits = Vec::new();
+                        for _ in *min..*max {
+                            splits.push(self.emit(Inst::Split(0, 0))?);
+                            self.compile_node(node)?;
+                        }
+                        let end = self.insts.len();
+                        for start in splits {
+                            self.insts[start] = split(start + 1, end);
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Finds the leftmost-first match in `haystack` starting at or after `start`
+    pub fn find_at(&self, haystack: &[u8], start: usize) -> Option<Range<usize>> {
+        let mut current = Threads::new(self.insts.len());
+        let mut next = Threads::new(self.insts.len());
+        let mut stack = Vec::new();
+        let mut matched = None;
+
+        let mut at = start;
+        let mut previous = char_before(haystack, start);
+        let mut c = decode(haystack, at);
+
+        loop {
+            if matched.is_none() {
+                let context = Context {
+                    at,
+                    len: haystack.len(),
+                    previous,
+                    next: c.map(|(c, _)| c),
+                };
+                self.add(&mut current, &mut stack, 0, at, &context);
+            }
+            if current.threads.is_empty() {
+                break;
+            }
+
+            let following = c.and_then(|(_, length)| decode(haystack, at + length));
+            let context = c.map(|(c, length)| Context {
+                at: at + length,
+                len: haystack.len(),
+                previous: Some(c),
+                next: following.map(|(c, _)| c),
+            });
+
+            for &(pc, thread_start) in &current.threads {
+                let advance = match (&self.insts[pc], c) {
+                    (Inst::Match, _) => {
+                        // lower priority threads can't win against this match
+                        matched = Some(thread_start..at);
+                        break;
+                    }
+                    (&Inst::Char { c, fold }, Some((c_here, _))) => chars_match(c, c_here, fold),
+                    (&Inst::Class { class, fold }, Some((c_here, _))) => {
+                        self.classes[class].contains(c_here, fold)
+                    }
+                    _ => false,
+                };
+                if let (true, Some(context)) = (advance, &context) {
+                    self.add(&mut next, &mut stack, pc + 1, thread_start, context);
+                }
+            }
+
+            let Some((c_here, length)) = c else {
+                break;
+            };
+            at += length;
+            previous = Some(c_here);
+            c = following;
+            mem::swap(&mut current, &mut next);
+            next.clear();
+        }
+
+        matched
+    }
+
+    /// Adds the thread at `pc` to `threads`, following jumps, splits and assertions
+    fn add(
+        &self,
+        threads: &mut Threads,
+        stack: &mut Vec<usize>,
+        pc: usize,
+        start: usize,
+        context: &Context,
+    ) {
+        stack.push(pc);
+        while let Some(pc) = stack.pop() {
+            if threads.contains(pc) {
+                continue;
+            }
+            threads.insert(pc, start);
+
+            match self.insts[pc] {
+                Inst::Jump(target) => stack.push(target),
+                Inst::Split(first, second) => {
+                    stack.push(second);
+                    stack.push(first);
+                }
+                Inst::Assert(assertion) if context.holds(assertion) => stack.push(pc + 1),
+                _ => (),
+            }
+        }
+    }
+}
+
+/// Ordered set of threads, as `(instruction, start of the match)`, with constant-time
+/// membership tests
+struct Threads {
+    threads: Vec<(usize, usize)>,
+    /// The index in `threads` of the thread at each instruction, if it is there
+    sparse: Vec<usize>,
+}
+
+impl Threads {
+    fn new(size: usize) -> Threads {
+        Threads {
+            threads: Vec::with_capacity(size),
+            sparse: vec![0; size],
+        }
+    }
+
+    fn contains(&self, pc: usize) -> bool {
+        let index = self.sparse[pc];
+        index < self.threads.len() && self.threads[index].0 == pc
+    }
+
+    fn insert(&mut self, pc: usize, start: usize) {
+        self.sparse[pc] = self.threads.len();
+        self.threads.push((pc, start));
+    }
+
+    fn clear(&mut self) {
+        self.threads.clear();
+    }
+}
+
+/// The surroundings of a position, to evaluate assertions
+struct Context {
+    at: usize,
+    len: usize,
+    previous: Option<char>,
+    next: Option<char>,
+}
+
+impl Context {
+    fn holds(&self, assertion: Assertion) -> bool {
+        let is_word = |c: Option<char>| c.is_some_and(is_word_char);
+        match assertion {
+            Assertion::StartText => self.at == 0,
+            Assertion::EndText => self.at == self.len,
+            Assertion::WordBoundary => is_word(self.previous) != is_word(self.next),
+            Assertion::NotWordBoundary => is_word(self.previous) == is_word(self.next),
+            Assertion::NoWordBefore => !is_word(self.previous),
+            Assertion::NoWordAfter => !is_word(self.next),
+        }
+    }
+}
+
+/// Decodes the character at `at` and its length in bytes
+///
+/// Bytes that aren't valid UTF-8 decode to `U+FFFD`, one maximal invalid sequence at a
+/// time, so that searching arbitrary bytes never fails.
+pub(crate) fn decode(bytes: &[u8], at: usize) -> Option<(char, usize)> {
+    let window = bytes.get(at..(at + 4).min(bytes.len()))?;
+    let chunk = window.utf8_chunks().next()?;
+
+    Some(match chunk.valid().chars().next() {
+        Some(c) => (c, c.len_utf8()),
+        None => (REPLACEMENT_CHARACTER, chunk.invalid().len()),
+    })
+}
+
+/// Decodes the character ending at `at`, if there is one
+pub(crate) fn char_before(bytes: &[u8], at: usize) -> Option<char> {
+    let mut start = at.checked_sub(1)?;
+    while start > 0 && at - start < 4 && bytes[start] & 0xC0 == 0x80 {
+        start -= 1;
+    }
+
+    decode(bytes, start).map(|(c, _)| c)
+}
+
+/// Returns characters other than `c` with the same simple case folding, such as `S` and
+/// `ſ` for `s`
+///
+/// These are the ones the standard library's case mappings lead to, which don't include
+/// the Kelvin sign `K` for `k` for example.
+pub(super) fn case_variants(c: char) -> impl Iterator<Item = char> {
+    let folded = simple_fold(c);
+    let candidates = [
+        single_char(c.to_lowercase()),
+        single_char(c.to_uppercase()),
+        Some(folded),
+        single_char(folded.to_uppercase()),
+    ];
+
+    candidates
+        .into_iter()
+        .flatten()
+        .filter(move |&v| v != c && simple_fold(v) == folded)
+}
+
+/// Returns the only character of `chars`, if there is exactly one
+fn single_char(mut chars: impl Iterator<Item = char>) -> Option<char> {
+    let first = chars.next();
+    first.filter(|_| chars.next().is_none())
+}
+
+fn chars_match(pattern: char, c: char, fold: bool) -> bool {
+    pattern == c || (fold && simple_fold(pattern) == simple_fold(c))
+}
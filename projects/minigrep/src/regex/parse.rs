@@ -0,0 +1,530 @@
+use super::{ErrorKind, RegexError};
+
+/// The largest count accepted in a counted repetition such as `a{2,5}`
+pub(super) const MAX_REPEAT: u32 = 1000;
+
+/// The deepest nesting of groups accepted, so that the recursive parsing, compiling and
+/// dropping of the syntax tree can't overflow the stack
+pub(super) const MAX_NESTING: usize = 250;
+
+#[derive(Debug, Clone, PartialEq)]
+/// The syntax tree of a pattern
+pub(super) enum Node {
+    /// Matches the empty string
+    Empty,
+    Literal {
+        c: char,
+        fold: bool,
+    },
+    Class {
+        class: CharClass,
+        fold: bool,
+    },
+    Assert(Assertion),
+    Concat(Vec<Node>),
+    Alternate(Vec<Node>),
+    Repeat {
+        node: Box<Node>,
+        min: u32,
+        max: Option<u32>,
+        greedy: bool,
+    },
+}
+
+impl Node {
+    /// Returns whether the pattern has an uppercase character in a literal or class range,
+    /// which escapes such as `\W` and `\p{Lu}` aren't
+    pub fn has_uppercase(&self) -> bool {
+        match self {
+            Node::Empty | Node::Assert(_) => false,
+            Node::Literal { c, .. } => c.is_uppercase(),
+            Node::Class { class, .. } => class.items.iter().any(|item| match item {
+                ClassItem::Range(first, last) => first.is_uppercase() || last.is_uppercase(),
+                ClassItem::Property { .. } => false,
+            }),
+            Node::Concat(nodes) | Node::Alternate(nodes) => nodes.iter().any(Node::has_uppercase),
+            Node::Repeat { node, .. } => node.has_uppercase(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// A zero-width assertion
+pub(super) enum Assertion {
+    /// `^`, the start of the text
+    StartText,
+    /// `$`, the end of the text
+    EndText,
+    /// `\b`
+    WordBoundary,
+    /// `\B`
+    NotWordBoundary,
+    /// No word character right before, added around whole-word patterns
+    NoWordBefore,
+    /// No word character right after, added around whole-word patterns
+    NoWordAfter,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// A set of characters, such as `[a-z\d]`, `\w` or `.`
+pub(super) struct CharClass {
+    pub items: Vec<ClassItem>,
+    pub negated: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) enum ClassItem {
+    /// The characters from the first to the second, both included
+    Range(char, char),
+    Property {
+        property: Property,
+        negated: bool,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// A named set of characters
+///
+/// The Unicode properties are the ones the standard library can test for, so that the
+/// engine doesn't need tables of its own.
+pub(super) enum Property {
+    /// `\d`, the ASCII digits
+    Digit,
+    /// `\w`, alphanumeric characters and `_`
+    Word,
+    /// `\s` or `\p{White_Space}`
+    WhiteSpace,
+    Alphabetic,
+    Lowercase,
+    Uppercase,
+    /// `\p{N}`, the characters of the general categories `Nd`, `Nl` and `No`
+    Numeric,
+    Alphanumeric,
+    /// `\p{Cc}`
+    Control,
+    Ascii,
+    Any,
+}
+
+impl Property {
+    /// Finds a property by name, ignoring case, spaces, `-` and `_` like Unicode does
+    fn from_name(name: &str) -> Option<Property> {
+        let name: String = name
+            .chars()
+            .filter(|c| !matches!(c, ' ' | '-' | '_'))
+            .flat_map(char::to_lowercase)
+            .collect();
+
+        let property = match name.as_str() {
+            "alphabetic" | "alpha" | "l" | "letter" => Property::Alphabetic,
+            "lowercase" | "lower" | "ll" => Property::Lowercase,
+            "uppercase" | "upper" | "lu" => Property::Uppercase,
+            "whitespace" | "space" => Property::WhiteSpace,
+            "n" | "number" | "numeric" => Property::Numeric,
+            "alnum" | "alphanumeric" => Property::Alphanumeric,
+            "cc" | "control" => Property::Control,
+            "ascii" => Property::Ascii,
+            "any" => Property::Any,
+            _ => return None,
+        };
+
+        Some(property)
+    }
+
+    pub fn contains(self, c: char) -> bool {
+        match self {
+            Property::Digit => c.is_ascii_digit(),
+            Property::Word => is_word_char(c),
+            Property::WhiteSpace => c.is_whitespace(),
+            Property::Alphabetic => c.is_alphabetic(),
+            Property::Lowercase => c.is_lowercase(),
+            Property::Uppercase => c.is_uppercase(),
+            Property::Numeric => c.is_numeric(),
+            Property::Alphanumeric => c.is_alphanumeric(),
+            Property::Control => c.is_control(),
+            Property::Ascii => c.is_ascii(),
+            Property::Any => true,
+        }
+    }
+}
+
+pub(crate) fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+impl ClassItem {
+    fn contains(&self, c: char) -> bool {
+        match *self {
+            ClassItem::Range(low, high) => low <= c && c <= high,
+            ClassItem::Property { property, negated } => property.contains(c) != negated,
+        }
+    }
+}
+
+impl CharClass {
+    /// The class of `.`, any character but a newline
+    fn dot() -> CharClass {
+        CharClass {
+            items: vec![ClassItem::Range('\n', '\n')],
+            negated: true,
+        }
+    }
+
+    fn single(item: ClassItem) -> CharClass {
+        CharClass {
+            items: vec![item],
+            negated: false,
+        }
+    }
+
+    /// Returns whether the class contains `c`, or any case variant of it if `fold` is set
+    pub fn contains(&self, c: char, fold: bool) -> bool {
+        let hit = |c: char| self.items.iter().any(|item| item.contains(c));
+        let found = hit(c) || (fold && super::nfa::case_variants(c).any(hit));
+        found != self.negated
+    }
+}
+
+/// What an escape sequence stands for
+enum Escape {
+    Literal(char),
+    Item(ClassItem),
+    Assert(Assertion),
+}
+
+/// Recursive descent parser keeping track of the byte offset it is at, for errors
+pub(super) struct Parser<'p> {
+    pattern: &'p str,
+    position: usize,
+    /// Whether literals and classes match case-insensitively, toggled by `(?i)`
+    fold: bool,
+    /// The number of groups around the position
+    depth: usize,
+}
+
+impl<'p> Parser<'p> {
+    pub fn parse(pattern: &'p str, fold: bool) -> Result<Node, RegexError> {
+        let mut parser = Parser {
+            pattern,
+            position: 0,
+            fold,
+            depth: 0,
+        };
+        let node = parser.parse_alternation()?;
+
+        match parser.peek() {
+            Some(')') => Err(parser.error(ErrorKind::UnmatchedParen, parser.position)),
+            _ => Ok(node),
+        }
+    }
+
+    fn error(&self, kind: ErrorKind, position: usize) -> RegexError {
+        RegexError { kind, position }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.pattern[self.position..].chars().next()
+    }
+
+    fn peek_second(&self) -> Option<char> {
+        self.pattern[self.position..].chars().nth(1)
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.position += c.len_utf8();
+        Some(c)
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        let found = self.peek() == Some(c);
+        if found {
+            self.bump();
+        }
+        found
+    }
+
+    fn parse_alternation(&mut self) -> Result<Node, RegexError> {
+        let mut branches = vec![self.parse_concat()?];
+        while self.eat('|') {
+            branches.push(self.parse_concat()?);
+        }
+
+        Ok(match branches.len() {
+            1 => branches.pop().unwrap(),
+            _ => Node::Alternate(branches),
+        })
+    }
+
+    fn parse_concat(&mut self) -> Result<Node, RegexError> {
+        let mut nodes = Vec::new();
+
+        while let Some(c) = self.peek() {
+            if c == '|' || c == ')' {
+                break;
+            }
+
+            let start = self.position;
+            if matches!(c, '*' | '+' | '?') || self.parse_counted()?.is_some() {
+                return Err(self.error(ErrorKind::NothingToRepeat, start));
+            }
+
+            let mut node = self.parse_atom()?;
+            if let Some((min, max)) = self.parse_quantifier()? {
+                node = Node::Repeat {
+                    node: Box::new(node),
+                    min,
+                    max,
+                    greedy: !self.eat('?'),
+                };
+            }
+            nodes.push(node);
+        }
+
+        Ok(match nodes.len() {
+            0 => Node::Empty,
+            1 => nodes.pop().unwrap(),
+            _ => Node::Concat(nodes),
+        })
+    }
+
+    fn parse_atom(&mut self) -> Result<Node, RegexError> {
+        let start = self.position;
+        let node = match self.bump().unwrap() {
+            '.' => Node::Class {
+                class: CharClass::dot(),
+                fold: false,
+            },
+            '^' => Node::Assert(Assertion::StartText),
+            '$' => Node::Assert(Assertion::EndText),
+            '(' => self.parse_group(start)?,
+            '[' => Node::Class {
+                class: self.parse_class(start)?,
+                fold: self.fold,
+            },
+            '\\' => match self.parse_escape(start)? {
+                Escape::Literal(c) => Node::Literal { c, fold: self.fold },
+                Escape::Item(item) => Node::Class {
+                    class: CharClass::single(item),
+                    fold: self.fold,
+                },
+                Escape::Assert(assertion) => Node::Assert(assertion),
+            },
+            c => Node::Literal { c, fold: self.fold },
+        };
+
+        Ok(node)
+    }
+
+    /// Parses a group whose `(` is at `start`, including the flag groups `(?i)`,
+    /// `(?-i)` and `(?i:...)`
+    fn parse_group(&mut self, start: usize) -> Result<Node, RegexError> {
+        let enclosing_fold = self.fold;
+
+        if self.eat('?') {
+            let mut enable = true;
+            let mut fold = self.fold;
+            loop {
+                let position = self.position;
+                match self.bump() {
+                    Some('i') => fold = enable,
+                    Some('-') if enable => enable = false,
+                    Some(':') => break,
+                    Some(')') => {
+                        // a bare flag group applies to the rest of the enclosing group
+                        self.fold = fold;
+                        return Ok(Node::Empty);
+                    }
+                    Some(c) => return Err(self.error(ErrorKind::UnknownGroupFlag(c), position)),
+                    None => return Err(self.error(ErrorKind::UnclosedGroup, start)),
+                }
+            }
+            self.fold = fold;
+        }
+
+        if self.depth == MAX_NESTING {
+            return Err(self.error(ErrorKind::NestingTooDeep, start));
+        }
+        self.depth += 1;
+        let node = self.parse_alternation()?;
+        if !self.eat(')') {
+            return Err(self.error(ErrorKind::UnclosedGroup, start));
+        }
+        self.depth -= 1;
+        self.fold = enclosing_fold;
+
+        Ok(node)
+    }
+
+    /// Parses a bracketed class whose `[` is at `start`
+    fn parse_class(&mut self, start: usize) -> Result<CharClass, RegexError> {
+        let negated = self.eat('^');
+        let mut items = Vec::new();
+
+        loop {
+            let item_start = self.position;
+            let low = match self.bump() {
+                None => return Err(self.error(ErrorKind::UnclosedClass, start)),
+                // a `]` first in the class is a literal
+                Some(']') if !items.is_empty() => break,
+                Some('\\') => match self.parse_escape(item_start)? {
+                    Escape::Literal(c) => c,
+                    Escape::Item(item) => {
+                        items.push(item);
+                        continue;
+                    }
+                    Escape::Assert(_) => {
+                        return Err(self.error(ErrorKind::InvalidClassEscape, item_start))
+                    }
+                },
+                Some(c) => c,
+            };
+
+            if self.peek() != Some('-') || matches!(self.peek_second(), None | Some(']')) {
+                items.push(ClassItem::Range(low, low));
+                continue;
+            }
+
+            self.bump();
+            let high_start = self.position;
+            let high = match self.bump() {
+                Some('\\') => match self.parse_escape(high_start)? {
+                    Escape::Literal(c) => c,
+                    _ => return Err(self.error(ErrorKind::InvalidRange, item_start)),
+                },
+                Some(c) => c,
+                None => return Err(self.error(ErrorKind::UnclosedClass, start)),
+            };
+            if high < low {
+                return Err(self.error(ErrorKind::InvalidRange, item_start));
+            }
+            items.push(ClassItem::Range(low, high));
+        }
+
+        Ok(CharClass { items, negated })
+    }
+
+    /// Parses the escape sequence whose backslash is at `start`
+    fn parse_escape(&mut self, start: usize) -> Result<Escape, RegexError> {
+        let c = self
+            .bump()
+            .ok_or_else(|| self.error(ErrorKind::TrailingBackslash, start))?;
+
+        let property = |property, negated| Escape::Item(ClassItem::Property { property, negated });
+        let escape = match c {
+            'd' | 'D' => property(Property::Digit, c == 'D'),
+            'w' | 'W' => property(Property::Word, c == 'W'),
+            's' | 'S' => property(Property::WhiteSpace, c == 'S'),
+            'p' | 'P' => property(self.parse_property_name(start)?, c == 'P'),
+            'b' => Escape::Assert(Assertion::WordBoundary),
+            'B' => Escape::Assert(Assertion::NotWordBoundary),
+            'n' => Escape::Literal('\n'),
+            't' => Escape::Literal('\t'),
+            'r' => Escape::Literal('\r'),
+            'f' => Escape::Literal('\x0C'),
+            'v' => Escape::Literal('\x0B'),
+            'x' => Escape::Literal(self.parse_hex(start)?),
+            c if c.is_ascii_punctuation() || c == ' ' => Escape::Literal(c),
+            c => return Err(self.error(ErrorKind::UnknownEscape(c), start)),
+        };
+
+        Ok(escape)
+    }
+
+    /// Parses the name of `\pL` or `\p{Name}`, whose backslash is at `start`
+    fn parse_property_name(&mut self, start: usize) -> Result<Property, RegexError> {
+        let name = if self.eat('{') {
+            let rest = &self.pattern[self.position..];
+            let end = rest
+                .find('}')
+                .ok_or_else(|| self.error(ErrorKind::UnclosedProperty, start))?;
+            self.position += end + 1;
+            &rest[..end]
+        } else {
+            let name_start = self.position;
+            self.bump()
+                .ok_or_else(|| self.error(ErrorKind::UnclosedProperty, start))?;
+            &self.pattern[name_start..self.position]
+        };
+
+        Property::from_name(name)
+            .ok_or_else(|| self.error(ErrorKind::UnknownProperty(name.to_string()), start))
+    }
+
+    /// Parses the code point of `\x7F` or `\x{1F600}`, whose backslash is at `start`
+    fn parse_hex(&mut self, start: usize) -> Result<char, RegexError> {
+        let rest = &self.pattern[self.position..];
+        let (digits, length) = match rest.strip_prefix('{') {
+            Some(braced) => match braced.find('}') {
+                Some(end) => (&braced[..end], end + 2),
+                None => return Err(self.error(ErrorKind::InvalidHex, start)),
+            },
+            None => match rest.get(..2) {
+                Some(digits) => (digits, 2),
+                None => return Err(self.error(ErrorKind::InvalidHex, start)),
+            },
+        };
+
+        let is_hex = digits.len() <= 6 && digits.bytes().all(|b| b.is_ascii_hexdigit());
+        let c = u32::from_str_radix(digits, 16)
+            .ok()
+            .filter(|_| is_hex)
+            .and_then(char::from_u32)
+            .ok_or_else(|| self.error(ErrorKind::InvalidHex, start))?;
+        self.position += length;
+
+        Ok(c)
+    }
+
+    fn parse_quantifier(&mut self) -> Result<Option<(u32, Option<u32>)>, RegexError> {
+        let quantifier = match self.peek() {
+            Some('*') => (0, None),
+            Some('+') => (1, None),
+            Some('?') => (0, Some(1)),
+            Some('{') => return self.parse_counted(),
+            _ => return Ok(None),
+        };
+        self.bump();
+
+        Ok(Some(quantifier))
+    }
+
+    /// Parses a counted repetition `{n}`, `{n,}` or `{n,m}` if there is one at the current
+    /// position, leaving any other `{` to be read as a literal
+    fn parse_counted(&mut self) -> Result<Option<(u32, Option<u32>)>, RegexError> {
+        let start = self.position;
+        let Some(rest) = self.pattern[start..].strip_prefix('{') else {
+            return Ok(None);
+        };
+        let Some(end) = rest.find('}') else {
+            return Ok(None);
+        };
+
+        let body = &rest[..end];
+        let (min, max) = match body.split_once(',') {
+            None => (body, Some(body)),
+            Some((min, "")) => (min, None),
+            Some((min, max)) => (min, Some(max)),
+        };
+
+        let is_count = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+        if !is_count(min) || !max.is_none_or(is_count) {
+            return Ok(None);
+        }
+
+        let count = |s: &str| {
+            s.parse::<u32>()
+                .ok()
+                .filter(|&count| count <= MAX_REPEAT)
+                .ok_or_else(|| self.error(ErrorKind::RepetitionTooLarge, start))
+        };
+        let min = count(min)?;
+        let max = max.map(count).transpose()?;
+        if max.is_some_and(|max| max < min) {
+            return Err(self.error(ErrorKind::InvalidRepetition, start));
+        }
+
+        self.position = start + end + 2;
+        Ok(Some((min, max)))
+    }
+}
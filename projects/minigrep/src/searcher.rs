@@ -0,0 +1,694 @@
+use std::borrow::Cow;
+use std::io::{self, Read};
+use std::ops::Range;
+
+use crate::casefold::{full_fold, simple_fold};
+use crate::regex::{char_before, decode, is_word_char, Regex};
+use aho_corasick::AhoCorasick;
+use substring::{find_byte, Finder};
+
+pub mod aho_corasick;
+pub mod substring;
+
+/// The number of bytes a `Searcher` reads at once
+const BLOCK_SIZE: usize = 64 * 1024;
+
+/// Finds the matches of a query in a line, possibly from several threads
+pub trait Matcher: Sync {
+    /// Returns the byte range of the first match in `line` starting at or after `start`
+    fn find_at(&self, line: &[u8], start: usize) -> Option<Range<usize>>;
+
+    fn is_match(&self, line: &[u8]) -> bool {
+        self.find_at(line, 0).is_some()
+    }
+
+    /// Returns whether the matches can be found in several lines at once, which requires
+    /// that they never span a newline nor depend on where lines start or end
+    fn is_line_oblivious(&self) -> bool {
+        false
+    }
+}
+
+impl<M: Matcher + ?Sized> Matcher for &M {
+    fn find_at(&self, line: &[u8], start: usize) -> Option<Range<usize>> {
+        (**self).find_at(line, start)
+    }
+
+    fn is_line_oblivious(&self) -> bool {
+        (**self).is_line_oblivious()
+    }
+}
+
+impl<M: Matcher + ?Sized> Matcher for Box<M> {
+    fn find_at(&self, line: &[u8], start: usize) -> Option<Range<usize>> {
+        (**self).find_at(line, start)
+    }
+
+    fn is_line_oblivious(&self) -> bool {
+        (**self).is_line_oblivious()
+    }
+}
+
+#[derive(Debug, Clone)]
+/// Matches a text exactly, with the substring algorithm suited to its length
+pub struct LiteralMatcher {
+    finder: Finder,
+}
+
+impl LiteralMatcher {
+    pub fn new(query: &str) -> LiteralMatcher {
+        LiteralMatcher {
+            finder: Finder::new(query.as_bytes()),
+        }
+    }
+}
+
+impl Matcher for LiteralMatcher {
+    fn find_at(&self, line: &[u8], start: usize) -> Option<Range<usize>> {
+        let found = start + self.finder.find(&line[start..])?;
+        Some(found..found + self.finder.needle().len())
+    }
+
+    fn is_line_oblivious(&self) -> bool {
+        !self.finder.needle().contains(&b'\n')
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// How a `CaseInsensitiveMatcher` compares cases
+pub enum Folding {
+    /// Folds every character to one character, so that `ß` doesn't match `ss`
+    Simple,
+    /// Folds some characters to several characters, so that `ß` matches `ss`
+    Full,
+}
+
+#[derive(Debug, Clone)]
+/// Matches a text ignoring case, by comparing the case foldings of the text and lines
+///
+/// A match always starts and ends at character boundaries of the line: with full folding,
+/// `s` doesn't match the first half of `ß`.
+pub struct CaseInsensitiveMatcher {
+    folded: Vec<char>,
+    folding: Folding,
+}
+
+impl CaseInsensitiveMatcher {
+    /// Creates a matcher of `query` with full case folding
+    pub fn new(query: &str) -> CaseInsensitiveMatcher {
+        CaseInsensitiveMatcher::with_folding(query, Folding::Full)
+    }
+
+    pub fn with_folding(query: &str, folding: Folding) -> CaseInsensitiveMatcher {
+        let folded = match folding {
+            Folding::Simple => query.chars().map(simple_fold).collect(),
+            Folding::Full => query.chars().flat_map(full_fold).collect(),
+        };
+
+        CaseInsensitiveMatcher { folded, folding }
+    }
+
+    /// Returns the end of the match of the query starting at `at`, if there is one
+    fn match_at(&self, line: &[u8], mut at: usize) -> Option<usize> {
+        let mut expected = self.folded.as_slice();
+        while !expected.is_empty() {
+            let (c, len) = decode(line, at)?;
+            let rest = match self.folding {
+                Folding::Simple => expected.strip_prefix(&[simple_fold(c)]),
+                Folding::Full => full_fold(c).try_fold(expected, |expected, folded| {
+                    expected.strip_prefix(&[folded])
+                }),
+            };
+            expected = rest?;
+            at += len;
+        }
+
+        Some(at)
+    }
+}
+
+impl Matcher for CaseInsensitiveMatcher {
+    fn find_at(&self, line: &[u8], start: usize) -> Option<Range<usize>> {
+        let mut at = start;
+        loop {
+            if let Some(end) = self.match_at(line, at) {
+                return Some(at..end);
+            }
+            let (_, len) = decode(line, at)?;
+            at += len;
+        }
+    }
+
+    fn is_line_oblivious(&self) -> bool {
+        !self.folded.contains(&'\n')
+    }
+}
+
+impl Matcher for Regex {
+    fn find_at(&self, line: &[u8], start: usize) -> Option<Range<usize>> {
+        Regex::find_at(self, line, start)
+    }
+}
+
+impl Matcher for AhoCorasick {
+    fn find_at(&self, line: &[u8], start: usize) -> Option<Range<usize>> {
+        AhoCorasick::find_at(self, line, start)
+    }
+
+    fn is_line_oblivious(&self) -> bool {
+        !self.has_newline()
+    }
+}
+
+/// Matches what any of several matchers matches, the leftmost match first and the longest
+/// of those
+pub struct AnyMatcher {
+    matchers: Vec<Box<dyn Matcher>>,
+}
+
+impl AnyMatcher {
+    pub fn new(matchers: Vec<Box<dyn Matcher>>) -> AnyMatcher {
+        AnyMatcher { matchers }
+    }
+}
+
+impl Matcher for AnyMatcher {
+    fn find_at(&self, line: &[u8], start: usize) -> Option<Range<usize>> {
+        self.matchers
+            .iter()
+            .filter_map(|matcher| matcher.find_at(line, start))
+            .min_by_key(|found| (found.start, usize::MAX - found.end))
+    }
+
+    fn is_line_oblivious(&self) -> bool {
+        self.matchers
+            .iter()
+            .all(|matcher| matcher.is_line_oblivious())
+    }
+}
+
+#[derive(Debug, Clone)]
+/// Matches what another matcher matches only with no word character right before or after
+/// it, like `grep -w`, or only the whole line, like `grep -x`
+pub struct WholeMatcher<M> {
+    matcher: M,
+    whole_line: bool,
+}
+
+impl<M: Matcher> WholeMatcher<M> {
+    /// Creates a matcher of the whole words matched by `matcher`
+    pub fn words(matcher: M) -> WholeMatcher<M> {
+        WholeMatcher {
+            matcher,
+            whole_line: false,
+        }
+    }
+
+    /// Creates a matcher of the whole lines matched by `matcher`
+    pub fn line(matcher: M) -> WholeMatcher<M> {
+        WholeMatcher {
+            matcher,
+            whole_line: true,
+        }
+    }
+}
+
+impl<M: Matcher> Matcher for WholeMatcher<M> {
+    fn find_at(&self, line: &[u8], start: usize) -> Option<Range<usize>> {
+        if self.whole_line {
+            let found = self.matcher.find_at(line, 0)?;
+            return (start == 0 && found == (0..line.len())).then_some(found);
+        }
+
+        let is_word = |c: Option<char>| c.is_some_and(is_word_char);
+        let mut at = start;
+        loop {
+            let found = self.matcher.find_at(line, at)?;
+            let next = decode(line, found.end).map(|(c, _)| c);
+            if !is_word(char_before(line, found.start)) && !is_word(next) {
+                return Some(found);
+            }
+            // a later match may start within this one
+            let (_, len) = decode(line, found.start)?;
+            at = found.start + len;
+        }
+    }
+
+    fn is_line_oblivious(&self) -> bool {
+        !self.whole_line && self.matcher.is_line_oblivious()
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+/// A line read by a `Searcher`
+pub struct Match {
+    /// The number of the line, starting from 1
+    pub line_number: usize,
+    /// The offset of the start of the line in the input
+    pub byte_offset: usize,
+    /// The line, without its `\n` or `\r\n` terminator
+    pub line: Vec<u8>,
+    /// The ranges of `line` matching the query, in order, without the empty ones
+    ///
+    /// There are none in the lines selected for not matching.
+    pub spans: Vec<Range<usize>>,
+}
+
+impl Clone for Match {
+    fn clone(&self) -> Match {
+        Match {
+            line_number: self.line_number,
+            byte_offset: self.byte_offset,
+            line: self.line.clone(),
+            spans: self.spans.clone(),
+        }
+    }
+
+    /// Copies `source` into the buffers of this line, which are reused
+    fn clone_from(&mut self, source: &Match) {
+        self.line_number = source.line_number;
+        self.byte_offset = source.byte_offset;
+        self.line.clone_from(&source.line);
+        self.spans.clone_from(&source.spans);
+    }
+}
+
+impl Match {
+    /// Returns the line, with its invalid UTF-8 replaced by `U+FFFD`
+    pub fn line_lossy(&self) -> Cow<'_, str> {
+        String::from_utf8_lossy(&self.line)
+    }
+}
+
+/// Searches the lines of a reader for the matches of a `Matcher`
+///
+/// The input is read in blocks, so the memory used doesn't grow with it but with its
+/// longest line. As an `Iterator`, or with `next_match`, a searcher yields the selected
+/// lines, and when the matcher is line-oblivious and the matching lines are selected, the
+/// lines are only split around the matches found in whole blocks.
+///
+/// ```
+/// use minigrep::searcher::{LiteralMatcher, Searcher};
+///
+/// let contents = "Rust:\nsafe, fast, productive.\nPick three.";
+/// let mut searcher = Searcher::new(LiteralMatcher::new("st"), contents.as_bytes());
+///
+/// let found = searcher.next().unwrap().unwrap();
+/// assert_eq!((found.line_number, found.byte_offset), (1, 0));
+/// assert_eq!(found.spans, [2..4]);
+/// let found = searcher.next().unwrap().unwrap();
+/// assert_eq!(found.line_lossy(), "safe, fast, productive.");
+/// assert!(searcher.next().is_none());
+/// ```
+pub struct Searcher<M, R> {
+    matcher: M,
+    reader: R,
+    invert_match: bool,
+    /// The input read, of which the bytes from `start` on aren't searched yet
+    buffer: Vec<u8>,
+    start: usize,
+    /// Whether the whole input was read
+    eof: bool,
+    /// The number of the lines searched
+    line_number: usize,
+    /// The offset of `start` in the input
+    byte_offset: usize,
+    /// The last line read by `next_line`, whose buffers are reused for the next one
+    line: Match,
+}
+
+impl<M: Matcher, R: Read> Searcher<M, R> {
+    pub fn new(matcher: M, reader: R) -> Searcher<M, R> {
+        Searcher {
+            matcher,
+            reader,
+            invert_match: false,
+            buffer: Vec::new(),
+            start: 0,
+            eof: false,
+            line_number: 0,
+            byte_offset: 0,
+            line: Match {
+                line_number: 0,
+                byte_offset: 0,
+                line: Vec::new(),
+                spans: Vec::new(),
+            },
+        }
+    }
+
+    /// Selects the lines that don't match rather than those that do
+    pub fn invert_match(&mut self, yes: bool) -> &mut Searcher<M, R> {
+        self.invert_match = yes;
+        self
+    }
+
+    /// Reads a block of the input after the bytes not searched yet, which are moved to the
+    /// start of the buffer
+    ///
+    /// # Returns
+    ///
+    /// Whether anything was read
+    fn fill(&mut self) -> io::Result<bool> {
+        if self.eof {
+            return Ok(false);
+        }
+
+        self.buffer.drain(..self.start);
+        self.start = 0;
+        let len = self.buffer.len();
+        self.buffer.resize(len + BLOCK_SIZE, 0);
+        let read = loop {
+            match self.reader.read(&mut self.buffer[len..]) {
+                Ok(read) => break read,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => {
+                    self.buffer.truncate(len);
+                    return Err(e);
+                }
+            }
+        };
+        self.buffer.truncate(len + read);
+        self.eof = read == 0;
+
+        Ok(read > 0)
+    }
+
+    /// Returns the end of the next line in the buffer, its newline included, reading the
+    /// input until it is complete, or `None` at the end of the input
+    fn line_end(&mut self) -> io::Result<Option<usize>> {
+        let mut searched = 0;
+        loop {
+            let unsearched = &self.buffer[self.start..];
+            if let Some(newline) = find_byte(b'\n', &unsearched[searched..]) {
+                return Ok(Some(self.start + searched + newline + 1));
+            }
+            searched = unsearched.len();
+
+            if !self.fill()? {
+                let end = self.buffer.len();
+                return Ok((self.start < end).then_some(end));
+            }
+        }
+    }
+
+    /// Skips the next `len` bytes of the buffer, which are whole lines
+    fn skip_lines(&mut self, len: usize) {
+        let skipped = &self.buffer[self.start..self.start + len];
+        self.line_number += count_newlines(skipped);
+        self.byte_offset += len;
+        self.start += len;
+    }
+
+    /// Reads the next line, whether it is selected or not
+    ///
+    /// The line is only borrowed, as its buffers are reused for the next line.
+    ///
+    /// # Returns
+    ///
+    /// The line and whether it is selected, or `None` at the end of the input
+    pub fn next_line(&mut self) -> io::Result<Option<(&Match, bool)>> {
+        let Some(end) = self.line_end()? else {
+            return Ok(None);
+        };
+
+        let mut line = &self.buffer[self.start..end];
+        if let Some(rest) = line.strip_suffix(b"\n") {
+            line = rest.strip_suffix(b"\r").unwrap_or(rest);
+        }
+        self.line.line.clear();
+        self.line.line.extend_from_slice(line);
+        let Match { line, spans, .. } = &mut self.line;
+        spans.clear();
+
+        let mut matched = false;
+        let mut start = 0;
+        while start <= line.len() {
+            let Some(found) = self.matcher.find_at(line, start) else {
+                break;
+            };
+            matched = true;
+            if self.invert_match {
+                // the line won't be selected, so its spans don't matter
+                break;
+            }
+
+            start = found.end + usize::from(found.is_empty());
+            if !found.is_empty() {
+                spans.push(found);
+            }
+        }
+        let selected = matched != self.invert_match;
+
+        self.line_number += 1;
+        self.line.line_number = self.line_number;
+        self.line.byte_offset = self.byte_offset;
+        self.byte_offset += end - self.start;
+        self.start = end;
+
+        Ok(Some((&self.line, selected)))
+    }
+
+    /// Reads the next selected line, which the `Iterator` yields a copy of, borrowed like
+    /// the lines of `next_line`
+    ///
+    /// # Returns
+    ///
+    /// The line, or `None` at the end of the input
+    pub fn next_match(&mut self) -> io::Result<Option<&Match>> {
+        let found = match !self.invert_match && self.matcher.is_line_oblivious() {
+            true => self.next_match_in_blocks()?,
+            false => loop {
+                match self.next_line()? {
+                    Some((_, true)) => break true,
+                    Some((_, false)) => continue,
+                    None => break false,
+                }
+            },
+        };
+
+        Ok(found.then_some(&self.line))
+    }
+
+    /// Reads the next matching line, searching the complete lines of the buffer at once
+    ///
+    /// # Returns
+    ///
+    /// Whether there was one
+    fn next_match_in_blocks(&mut self) -> io::Result<bool> {
+        loop {
+            let unsearched = &self.buffer[self.start..];
+            let lines_len = match unsearched.iter().rposition(|&byte| byte == b'\n') {
+                Some(newline) => newline + 1,
+                None if self.eof => unsearched.len(),
+                None => 0,
+            };
+            let lines = &unsearched[..lines_len];
+
+            if let Some(found) = self.matcher.find_at(lines, 0) {
+                let line_start = lines[..found.start]
+                    .iter()
+                    .rposition(|&byte| byte == b'\n')
+                    .map_or(0, |newline| newline + 1);
+                self.skip_lines(line_start);
+                // the line may still not match once its line break is stripped
+                match self.next_line()? {
+                    Some((_, true)) => return Ok(true),
+                    Some((_, false)) => continue,
+                    None => return Ok(false),
+                }
+            }
+            self.skip_lines(lines_len);
+
+            if !self.fill()? && self.start == self.buffer.len() {
+                return Ok(false);
+            }
+        }
+    }
+}
+
+/// Returns the number of newlines in `bytes`
+fn count_newlines(bytes: &[u8]) -> usize {
+    // counting in bytes, which can't overflow over 255 bytes, lets the loop be vectorized
+    bytes
+        .chunks(255)
+        .map(|chunk| {
+            let count = chunk
+                .iter()
+                .fold(0u8, |count, &byte| count + u8::from(byte == b'\n'));
+            usize::from(count)
+        })
+        .sum()
+}
+
+impl<M: Matcher, R: Read> Iterator for Searcher<M, R> {
+    type Item = io::Result<Match>;
+
+    fn next(&mut self) -> Option<io::Result<Match>> {
+        self.next_match().map(|found| found.cloned()).transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns the line number, offset and spans of each line selected in `contents`
+    fn matches(matcher: impl Matcher, contents: &str) -> Vec<String> {
+        Searcher::new(matcher, contents.as_bytes())
+            .map(|found| found.unwrap())
+            .map(|found| {
+                format!(
+                    "{}:{}:{:?}",
+                    found.line_number, found.byte_offset, found.spans
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn spans() {
+        let contents = "a frog, a toad\r\n\nfrogfrog";
+
+        assert_eq!(
+            matches(LiteralMatcher::new("frog"), contents),
+            ["1:0:[2..6]", "3:17:[0..4, 4..8]"]
+        );
+        assert_eq!(
+            matches(Regex::new("a|o").unwrap(), contents),
+            [
+                "1:0:[0..1, 4..5, 8..9, 11..12, 12..13]",
+                "3:17:[2..3, 6..7]"
+            ]
+        );
+        // an empty match selects the line but isn't a span
+        assert_eq!(
+            matches(Regex::new("x*").unwrap(), "ab\nxx"),
+            ["1:0:[]", "2:3:[0..2]"]
+        );
+    }
+
+    #[test]
+    fn invert_match() {
+        let mut searcher =
+            Searcher::new(CaseInsensitiveMatcher::new("FROG"), "Frog\ntoad".as_bytes());
+        searcher.invert_match(true);
+
+        let (found, selected) = searcher.next_line().unwrap().unwrap();
+        assert_eq!((found.line_lossy(), selected), ("Frog".into(), false));
+        let found = searcher.next().unwrap().unwrap();
+        assert_eq!((found.line_number, found.byte_offset), (2, 5));
+        assert!(found.spans.is_empty());
+        assert!(searcher.next().is_none());
+    }
+
+    #[test]
+    fn next_match() {
+        let contents = "a frog\nbees\nfrog and toad\nbog";
+        for invert in [false, true] {
+            let mut searcher = Searcher::new(LiteralMatcher::new("og"), contents.as_bytes());
+            searcher.invert_match(invert);
+            let mut numbers = Vec::new();
+            while let Some(found) = searcher.next_match().unwrap() {
+                numbers.push(found.line_number);
+            }
+
+            match invert {
+                false => assert_eq!(numbers, [1, 3, 4]),
+                true => assert_eq!(numbers, [2]),
+            }
+        }
+    }
+
+    #[test]
+    fn blocks() {
+        // lines crossing the blocks read, and a match in the last line without a newline
+        let mut contents = String::new();
+        for i in 0..20_000 {
+            contents.push_str(&format!("line {}\n", i));
+        }
+        contents.push_str("a frog");
+
+        let line_offset = |line: usize| contents.match_indices('\n').nth(line - 2).unwrap().0 + 1;
+        assert_eq!(
+            matches(LiteralMatcher::new("line 12345"), &contents),
+            [format!("12346:{}:[0..10]", line_offset(12346))]
+        );
+        assert_eq!(
+            matches(LiteralMatcher::new("frog"), &contents),
+            [format!("20001:{}:[2..6]", contents.len() - 6)]
+        );
+        let patterns = AhoCorasick::new(&["e 9999", "e 19999", "frog"]);
+        assert_eq!(matches(patterns, &contents).len(), 3);
+        assert!(matches(LiteralMatcher::new("toad"), &contents).is_empty());
+        assert_eq!(
+            matches(LiteralMatcher::new("a\r"), "a\r\nab\n"),
+            Vec::<String>::new()
+        );
+    }
+
+    #[test]
+    fn any_matcher() {
+        let matcher = AnyMatcher::new(vec![
+            Box::new(LiteralMatcher::new("frog")),
+            Box::new(LiteralMatcher::new("a fr")),
+            Box::new(Regex::new("t.ad").unwrap()),
+        ]);
+        assert!(!matcher.is_line_oblivious());
+        assert_eq!(
+            matches(matcher, "a frog, a toad\ntoads"),
+            ["1:0:[0..4, 10..14]", "2:15:[0..4]"]
+        );
+    }
+
+    #[test]
+    fn case_insensitive_ranges() {
+        let matcher = CaseInsensitiveMatcher::new("STRASSE");
+        assert_eq!(matcher.find_at("Die Straße".as_bytes(), 0), Some(4..11));
+        let matcher = CaseInsensitiveMatcher::new("straße");
+        assert_eq!(matcher.find_at("Die STRASSE!".as_bytes(), 0), Some(4..11));
+        assert_eq!(matcher.find_at("Die STRAẞE".as_bytes(), 0), Some(4..12));
+        let matcher = CaseInsensitiveMatcher::with_folding("strasse", Folding::Simple);
+        assert_eq!(matcher.find_at("Die Straße".as_bytes(), 0), None);
+        let matcher = CaseInsensitiveMatcher::with_folding("straße", Folding::Simple);
+        assert_eq!(matcher.find_at("DIE STRAẞE".as_bytes(), 0), Some(4..12));
+
+        // a match doesn't end or start inside the folding of a character
+        let matcher = CaseInsensitiveMatcher::new("s");
+        assert_eq!(matcher.find_at("ßs".as_bytes(), 0), Some(2..3));
+        let matcher = CaseInsensitiveMatcher::new("I");
+        assert_eq!(matcher.find_at("xİ".as_bytes(), 0), None);
+        assert_eq!(matcher.find_at("ıI".as_bytes(), 0), Some(2..3));
+        assert_eq!(matcher.find_at(b"\xFFi", 0), Some(1..2));
+        assert_eq!(matcher.find_at(b"iIi", 1), Some(1..2));
+        let matcher = CaseInsensitiveMatcher::new("σοφος");
+        assert_eq!(matcher.find_at("ΣΟΦΟΣ".as_bytes(), 0), Some(0..10));
+        let matcher = CaseInsensitiveMatcher::new("");
+        assert_eq!(matcher.find_at(b"ab", 2), Some(2..2));
+
+        let matcher = LiteralMatcher::new("ab");
+        assert_eq!(matcher.find_at(b"abab", 1), Some(2..4));
+        assert_eq!(matcher.find_at(b"aba", 1), None);
+    }
+
+    #[test]
+    fn whole_matcher() {
+        let matcher = WholeMatcher::words(CaseInsensitiveMatcher::new("STRASSE"));
+        assert!(matcher.is_line_oblivious());
+        assert_eq!(
+            matcher.find_at("Straßen, Straße".as_bytes(), 0),
+            Some(10..17)
+        );
+        assert_eq!(matcher.find_at("die_straße".as_bytes(), 0), None);
+        let matcher = WholeMatcher::words(LiteralMatcher::new("aa"));
+        assert_eq!(matcher.find_at(b"aaa aa", 0), Some(4..6));
+        assert_eq!(matcher.find_at(b"aa", 1), None);
+        assert_eq!(matcher.find_at("éaaé aa".as_bytes(), 0), Some(7..9));
+
+        let matcher = WholeMatcher::line(CaseInsensitiveMatcher::new("die strasse"));
+        assert!(!matcher.is_line_oblivious());
+        assert_eq!(matcher.find_at("Die Straße".as_bytes(), 0), Some(0..11));
+        assert_eq!(matcher.find_at("Die Straße".as_bytes(), 1), None);
+        assert_eq!(matcher.find_at("Die Straßen".as_bytes(), 0), None);
+    }
+}
@@ -0,0 +1,260 @@
+use std::collections::VecDeque;
+use std::ops::Range;
+
+/// The state of the empty prefix, where the search starts
+const ROOT: usize = 0;
+
+#[derive(Debug, Clone)]
+/// Searches for any of several byte strings in one pass, with an Aho-Corasick automaton
+///
+/// The automaton is the trie of the patterns, in which each state also links to the state
+/// of its longest proper suffix in the trie, to fall back to when the next byte doesn't
+/// continue any pattern. Following these links ahead of time gives every state a full
+/// table of transitions, so that the search reads each byte with a single lookup. Of the
+/// matches, the leftmost one is found, and the longest of the leftmost ones.
+///
+/// ```
+/// use minigrep::searcher::aho_corasick::AhoCorasick;
+///
+/// let patterns = AhoCorasick::new(&["fast", "safe", "safe, fast"]);
+/// assert_eq!(patterns.find_at(b"safe, fast, productive.", 0), Some(0..10));
+/// assert_eq!(patterns.find_at(b"safe, fast, productive.", 1), Some(6..10));
+/// ```
+pub struct AhoCorasick {
+    states: Vec<State>,
+    /// The state after reading each byte in each state, at `state * 256 + byte`, the root
+    /// going to itself for the bytes no pattern starts with
+    transitions: Vec<usize>,
+    /// Whether one of the patterns is empty, which matches everywhere
+    has_empty: bool,
+    /// Whether one of the patterns has a newline
+    has_newline: bool,
+}
+
+#[derive(Debug, Clone)]
+struct State {
+    /// The transitions of the trie, sorted by byte
+    next: Vec<(u8, usize)>,
+    /// The state of the longest proper suffix of this state that is in the trie
+    fail: usize,
+    /// The length of the prefix of this state
+    depth: usize,
+    /// Whether a pattern ends at this state
+    terminal: bool,
+    /// The length of the longest pattern which is a suffix of this state
+    longest_match: Option<usize>,
+}
+
+impl State {
+    fn new(depth: usize) -> State {
+        State {
+            next: Vec::new(),
+            fail: ROOT,
+            depth,
+            terminal: false,
+            longest_match: None,
+        }
+    }
+}
+
+impl AhoCorasick {
+    pub fn new<P: AsRef<[u8]>>(patterns: &[P]) -> AhoCorasick {
+        let mut states = vec![State::new(0)];
+        let mut has_empty = false;
+        let mut has_newline = false;
+
+        for pattern in patterns {
+            let pattern = pattern.as_ref();
+            has_empty |= pattern.is_empty();
+            has_newline |= pattern.contains(&b'\n');
+
+            let mut state = ROOT;
+            for &byte in pattern {
+                state = match states[state].next.binary_search_by_key(&byte, |&(b, _)| b) {
+                    Ok(index) => states[state].next[index].1,
+                    Err(index) => {
+                        states.push(State::new(states[state].depth + 1));
+                        let child = states.len() - 1;
+                        states[state].next.insert(index, (byte, child));
+                        child
+                    }
+                };
+            }
+            states[state].terminal = state != ROOT;
+        }
+
+        let mut automaton = AhoCorasick {
+            transitions: vec![ROOT; states.len() * 256],
+            states,
+            has_empty,
+            has_newline,
+        };
+        automaton.link_suffixes();
+        automaton
+    }
+
+    /// Sets the suffix links and the transitions of the states, shallowest first so that
+    /// those of the shorter suffixes are known
+    ///
+    /// A state goes to its child in the trie for the bytes that continue it, and for the
+    /// others where the state of its longest proper suffix goes.
+    fn link_suffixes(&mut self) {
+        for &(byte, child) in &self.states[ROOT].next {
+            self.transitions[usize::from(byte)] = child;
+        }
+        let mut queue: VecDeque<usize> = self.states[ROOT].next.iter().map(|&(_, c)| c).collect();
+        for &state in &queue {
+            let state = &mut self.states[state];
+            state.longest_match = state.terminal.then_some(state.depth);
+        }
+
+        while let Some(state) = queue.pop_front() {
+            let fail = self.states[state].fail;
+            self.transitions
+                .copy_within(fail * 256..(fail + 1) * 256, state * 256);
+
+            for index in 0..self.states[state].next.len() {
+                let (byte, child) = self.states[state].next[index];
+                let child_fail = self.step(fail, byte);
+                self.transitions[state * 256 + usize::from(byte)] = child;
+
+                let child_state = &self.states[child];
+                let longest_match = match child_state.terminal {
+                    true => Some(child_state.depth),
+                    false => self.states[child_fail].longest_match,
+                };
+                let child_state = &mut self.states[child];
+                child_state.fail = child_fail;
+                child_state.longest_match = longest_match;
+                queue.push_back(child);
+            }
+        }
+    }
+
+    /// Returns the state after reading `byte` in `state`
+    fn step(&self, state: usize, byte: u8) -> usize {
+        self.transitions[state * 256 + usize::from(byte)]
+    }
+
+    /// Returns whether one of the patterns has a newline
+    pub fn has_newline(&self) -> bool {
+        self.has_newline
+    }
+
+    /// Returns the range of the leftmost-longest match in `haystack` starting at or after
+    /// `start`
+    pub fn find_at(&self, haystack: &[u8], start: usize) -> Option<Range<usize>> {
+        let found = self.find_non_empty(haystack, start);
+        match self.has_empty {
+            true => found
+                .filter(|found| found.start == start)
+                .or(Some(start..start)),
+            false => found,
+        }
+    }
+
+    fn find_non_empty(&self, haystack: &[u8], start: usize) -> Option<Range<usize>> {
+        let mut best: Option<Range<usize>> = None;
+        let mut state = ROOT;
+        let mut i = start;
+        while i < haystack.len() {
+            if state == ROOT {
+                // skip the bytes no pattern starts with
+                match haystack[i..]
+                    .iter()
+                    .position(|&byte| self.step(ROOT, byte) != ROOT)
+                {
+                    Some(skipped) => i += skipped,
+                    None => break,
+                }
+            }
+
+            state = self.step(state, haystack[i]);
+            let current = &self.states[state];
+            if let Some(best) = &best {
+                // every match still in progress starts after the best one
+                if i + 1 - current.depth > best.start {
+                    break;
+                }
+            }
+            if let Some(length) = current.longest_match {
+                let found = i + 1 - length..i + 1;
+                // a later match starting at the same place or before is longer
+                if best.as_ref().is_none_or(|best| found.start <= best.start) {
+                    best = Some(found);
+                }
+            }
+            i += 1;
+        }
+
+        best
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns the leftmost-longest match by trying every pattern at every position
+    fn naive_find(patterns: &[&[u8]], haystack: &[u8]) -> Option<Range<usize>> {
+        (0..=haystack.len()).find_map(|start| {
+            patterns
+                .iter()
+                .filter(|pattern| haystack[start..].starts_with(pattern))
+                .map(|pattern| start..start + pattern.len())
+                .max_by_key(|found| found.end)
+        })
+    }
+
+    #[test]
+    fn leftmost_longest() {
+        let patterns = AhoCorasick::new(&["he", "she", "his", "hers", "s"]);
+        assert_eq!(patterns.find_at(b"ushers", 0), Some(1..4));
+        assert_eq!(patterns.find_at(b"ushers", 2), Some(2..6));
+        assert_eq!(patterns.find_at(b"ushers", 3), Some(5..6));
+        assert_eq!(patterns.find_at(b"xhix", 0), None);
+
+        let patterns = AhoCorasick::new(&["abcd", "bc"]);
+        assert_eq!(patterns.find_at(b"abce", 0), Some(1..3));
+
+        let none: [&str; 0] = [];
+        assert_eq!(AhoCorasick::new(&none).find_at(b"abc", 0), None);
+        let empty = AhoCorasick::new(&["", "bc"]);
+        assert_eq!(empty.find_at(b"abc", 0), Some(0..0));
+        assert_eq!(empty.find_at(b"abc", 1), Some(1..3));
+        assert!(AhoCorasick::new(&["a\nb"]).has_newline());
+    }
+
+    #[test]
+    fn agrees_with_naive_search() {
+        let mut state = 0x9e37_79b9_7f4a_7c15_u64;
+        let mut next = |bound: u64| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state % bound
+        };
+        let mut random_bytes = |max_len: u64| -> Vec<u8> {
+            let len = next(max_len);
+            (0..len).map(|_| b'a' + next(3) as u8).collect()
+        };
+
+        for _ in 0..2000 {
+            let patterns: Vec<Vec<u8>> = (0..5).map(|_| random_bytes(5)).collect();
+            let patterns: Vec<&[u8]> = patterns
+                .iter()
+                .map(|pattern| pattern.as_slice())
+                .filter(|pattern| !pattern.is_empty())
+                .collect();
+            let haystack = random_bytes(30);
+
+            assert_eq!(
+                AhoCorasick::new(&patterns).find_at(&haystack, 0),
+                naive_find(&patterns, &haystack),
+                "{:?} in {:?}",
+                patterns,
+                haystack
+            );
+        }
+    }
+}
@@ -0,0 +1,340 @@
+use std::cmp;
+
+/// The shortest needle searched with Horspool's algorithm by default
+///
+/// Horspool's algorithm moves the window by at most the length of the needle, so shorter
+/// needles are searched faster with the Two-Way algorithm, which skips every window whose
+/// last byte isn't in the needle.
+pub const MIN_HORSPOOL_LEN: usize = 4;
+
+/// The longest needle searched with Horspool's algorithm by default
+///
+/// Horspool's algorithm skips more of the haystack the longer the needle: on text it
+/// searches needles of 32 to 1024 bytes 3 to 8 times faster than the Two-Way algorithm.
+/// Its worst case, comparing most of the needle at every byte, measures about 7 times
+/// slower than the Two-Way algorithm over the same range, so the longest needles, whose
+/// comparisons can still grow, are left to the Two-Way algorithm.
+pub const MAX_HORSPOOL_LEN: usize = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// An algorithm searching for a byte string
+pub enum Algorithm {
+    /// Compares every byte of the haystack with the only byte of the needle
+    Byte,
+    /// Boyer-Moore-Horspool, which skips ahead by how far the last byte of the window is
+    /// from the end of the needle
+    Horspool,
+    /// Crochemore-Perrin's Two-Way, which runs in linear time with constant memory
+    TwoWay,
+}
+
+#[derive(Debug, Clone)]
+/// Searches for a byte string with an algorithm suited to its length
+///
+/// ```
+/// use minigrep::searcher::substring::{Algorithm, Finder};
+///
+/// let finder = Finder::new(b"productive");
+/// assert_eq!(finder.algorithm(), Algorithm::Horspool);
+/// assert_eq!(finder.find(b"safe, fast, productive."), Some(12));
+/// ```
+pub struct Finder {
+    needle: Vec<u8>,
+    searcher: Searcher,
+}
+
+#[derive(Debug, Clone)]
+enum Searcher {
+    Empty,
+    Byte(u8),
+    Horspool(Box<[usize; 256]>),
+    TwoWay(TwoWay),
+}
+
+impl Finder {
+    /// Creates a finder of `needle`, searched byte by byte if it is one byte long, with
+    /// Horspool's algorithm from `MIN_HORSPOOL_LEN` to `MAX_HORSPOOL_LEN` bytes and with
+    /// Two-Way otherwise
+    pub fn new(needle: &[u8]) -> Finder {
+        let algorithm = match needle.len() {
+            0 | 1 => Algorithm::Byte,
+            MIN_HORSPOOL_LEN..=MAX_HORSPOOL_LEN => Algorithm::Horspool,
+            _ => Algorithm::TwoWay,
+        };
+
+        Finder::with_algorithm(needle, algorithm)
+    }
+
+    /// Creates a finder of `needle` searched with `algorithm`, which is `Byte` only for
+    /// needles of one byte
+    pub fn with_algorithm(needle: &[u8], algorithm: Algorithm) -> Finder {
+        let searcher = match (needle, algorithm) {
+            ([], _) => Searcher::Empty,
+            (&[byte], _) => Searcher::Byte(byte),
+            (_, Algorithm::Byte | Algorithm::Horspool) => {
+                Searcher::Horspool(Box::new(horspool_shifts(needle)))
+            }
+            (_, Algorithm::TwoWay) => Searcher::TwoWay(TwoWay::new(needle)),
+        };
+
+        Finder {
+            needle: needle.to_vec(),
+            searcher,
+        }
+    }
+
+    pub fn needle(&self) -> &[u8] {
+        &self.needle
+    }
+
+    pub fn algorithm(&self) -> Algorithm {
+        match self.searcher {
+            Searcher::Empty | Searcher::Byte(_) => Algorithm::Byte,
+            Searcher::Horspool(_) => Algorithm::Horspool,
+            Searcher::TwoWay(_) => Algorithm::TwoWay,
+        }
+    }
+
+    /// Returns the offset of the first occurrence of the needle in `haystack`
+    pub fn find(&self, haystack: &[u8]) -> Option<usize> {
+        match &self.searcher {
+            Searcher::Empty => Some(0),
+            Searcher::Byte(byte) => find_byte(*byte, haystack),
+            Searcher::Horspool(shifts) => horspool_find(&self.needle, shifts, haystack),
+            Searcher::TwoWay(two_way) => two_way.find(&self.needle, haystack),
+        }
+    }
+}
+
+/// Returns the offset of the first `byte` in `haystack`
+pub fn find_byte(byte: u8, haystack: &[u8]) -> Option<usize> {
+    haystack.iter().position(|&b| b == byte)
+}
+
+/// Returns how far the window can move when its last byte is each byte value
+fn horspool_shifts(needle: &[u8]) -> [usize; 256] {
+    let last = needle.len() - 1;
+    let mut shifts = [needle.len(); 256];
+    for (i, &byte) in needle[..last].iter().enumerate() {
+        shifts[usize::from(byte)] = last - i;
+    }
+
+    shifts
+}
+
+fn horspool_find(needle: &[u8], shifts: &[usize; 256], haystack: &[u8]) -> Option<usize> {
+    let last = needle.len() - 1;
+    let mut position = 0;
+    while position + needle.len() <= haystack.len() {
+        let byte = haystack[position + last];
+        if byte == needle[last] && haystack[position..position + last] == needle[..last] {
+            return Some(position);
+        }
+        position += shifts[usize::from(byte)];
+    }
+
+    None
+}
+
+#[derive(Debug, Clone)]
+/// The critical factorization of a needle, which the Two-Way algorithm matches the right
+/// part of first, then the left part
+struct TwoWay {
+    /// The bytes of the needle, as bits indexed by their low 6 bits, to skip the windows
+    /// whose last byte isn't in the needle
+    byteset: u64,
+    /// Where the right part starts
+    critical: usize,
+    period: usize,
+    /// Whether the period is longer than the right part, in which case the needle isn't
+    /// periodic and there is nothing to remember about the previous window
+    long_period: bool,
+}
+
+impl TwoWay {
+    fn new(needle: &[u8]) -> TwoWay {
+        let byteset = needle
+            .iter()
+            .fold(0, |byteset, &byte| byteset | 1 << (byte & 63));
+        let (critical_less, period_less) = maximal_suffix(needle, false);
+        let (critical_greater, period_greater) = maximal_suffix(needle, true);
+        let (critical, period) = match critical_less > critical_greater {
+            true => (critical_less, period_less),
+            false => (critical_greater, period_greater),
+        };
+
+        if needle[..critical] == needle[period..period + critical] {
+            TwoWay {
+                byteset,
+                critical,
+                period,
+                long_period: false,
+            }
+        } else {
+            TwoWay {
+                byteset,
+                critical,
+                period: cmp::max(critical, needle.len() - critical) + 1,
+                long_period: true,
+            }
+        }
+    }
+
+    fn find(&self, needle: &[u8], haystack: &[u8]) -> Option<usize> {
+        let mut position = 0;
+        // how much of the start of the needle is known to match, thanks to the period
+        let mut memory = 0;
+        'windows: while position + needle.len() <= haystack.len() {
+            let window = &haystack[position..position + needle.len()];
+            if self.byteset >> (window[needle.len() - 1] & 63) & 1 == 0 {
+                // no occurrence overlaps the last byte
+                position += needle.len();
+                memory = 0;
+                continue;
+            }
+
+            let right_start = cmp::max(self.critical, memory);
+            for i in right_start..needle.len() {
+                if needle[i] != window[i] {
+                    position += i - self.critical + 1;
+                    memory = 0;
+                    continue 'windows;
+                }
+            }
+
+            for i in (memory..self.critical).rev() {
+                if needle[i] != window[i] {
+                    position += self.period;
+                    if !self.long_period {
+                        memory = needle.len() - self.period;
+                    }
+                    continue 'windows;
+                }
+            }
+
+            return Some(position);
+        }
+
+        None
+    }
+}
+
+/// Returns the start and period of the maximal suffix of `needle`, for the byte order or
+/// its reverse
+fn maximal_suffix(needle: &[u8], reversed: bool) -> (usize, usize) {
+    let mut start = 0;
+    let mut candidate = 1;
+    let mut offset = 0;
+    let mut period = 1;
+
+    while let Some(&a) = needle.get(candidate + offset) {
+        let b = needle[start + offset];
+        if (a < b) != reversed && a != b {
+            // the candidate is smaller, so the suffix so far is a period
+            candidate += offset + 1;
+            offset = 0;
+            period = candidate - start;
+        } else if a == b {
+            if offset + 1 == period {
+                candidate += offset + 1;
+                offset = 0;
+            } else {
+                offset += 1;
+            }
+        } else {
+            // the candidate is larger and becomes the suffix
+            start = candidate;
+            candidate += 1;
+            offset = 0;
+            period = 1;
+        }
+    }
+
+    (start, period)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn naive_find(needle: &[u8], haystack: &[u8]) -> Option<usize> {
+        (0..=haystack.len().checked_sub(needle.len())?)
+            .find(|&i| &haystack[i..i + needle.len()] == needle)
+    }
+
+    #[test]
+    fn algorithm_by_length() {
+        assert_eq!(Finder::new(b"").algorithm(), Algorithm::Byte);
+        assert_eq!(Finder::new(b"a").algorithm(), Algorithm::Byte);
+        assert_eq!(Finder::new(b"ab").algorithm(), Algorithm::TwoWay);
+        assert_eq!(
+            Finder::new(&[b'a'; MIN_HORSPOOL_LEN]).algorithm(),
+            Algorithm::Horspool
+        );
+        assert_eq!(
+            Finder::new(&[b'a'; MAX_HORSPOOL_LEN]).algorithm(),
+            Algorithm::Horspool
+        );
+        assert_eq!(
+            Finder::new(&[b'a'; MAX_HORSPOOL_LEN + 1]).algorithm(),
+            Algorithm::TwoWay
+        );
+        assert_eq!(Finder::new(b"").find(b"abc"), Some(0));
+        assert_eq!(Finder::new(b"c").find(b"abc"), Some(2));
+    }
+
+    #[test]
+    fn agrees_with_naive_search() {
+        // a pseudo-random generator, so that failures can be reproduced
+        let mut state = 0x2545_f491_4f6c_dd1d_u64;
+        let mut next = |bound: u64| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state % bound
+        };
+
+        for _ in 0..5000 {
+            // small alphabets make periodic needles and near misses likely
+            let alphabet = next(3) + 2;
+            let haystack: Vec<u8> = (0..next(40)).map(|_| b'a' + next(alphabet) as u8).collect();
+            let needle: Vec<u8> = match next(2) {
+                0 if haystack.len() > 2 => {
+                    let start = next(haystack.len() as u64 - 1) as usize;
+                    let end = start + 1 + next((haystack.len() - start) as u64) as usize;
+                    haystack[start..end].to_vec()
+                }
+                _ => (0..next(8) + 1)
+                    .map(|_| b'a' + next(alphabet) as u8)
+                    .collect(),
+            };
+
+            let expected = naive_find(&needle, &haystack);
+            for algorithm in [Algorithm::Horspool, Algorithm::TwoWay] {
+                assert_eq!(
+                    Finder::with_algorithm(&needle, algorithm).find(&haystack),
+                    expected,
+                    "{:?} searching {:?} in {:?}",
+                    algorithm,
+                    String::from_utf8_lossy(&needle),
+                    String::from_utf8_lossy(&haystack)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn periodic_needles() {
+        let needle = b"abaabaabaab".repeat(8);
+        let mut haystack = b"abaabaabaa".repeat(20);
+        haystack.extend_from_slice(&needle);
+
+        let finder = Finder::with_algorithm(&needle, Algorithm::TwoWay);
+        assert_eq!(finder.find(&haystack), naive_find(&needle, &haystack));
+        assert_eq!(
+            finder.find(&haystack[1..]),
+            naive_find(&needle, &haystack[1..])
+        );
+        assert_eq!(Finder::new(&[b'a'; 100]).find(&[b'a'; 99]), None);
+    }
+}
@@ -0,0 +1,343 @@
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::glob::Glob;
+use ignore::IgnoreFile;
+
+pub mod ignore;
+
+#[derive(Debug)]
+/// Error reaching a file to search, which doesn't stop the walk
+pub enum WalkError {
+    Io {
+        path: PathBuf,
+        error: io::Error,
+    },
+    /// A directory containing itself through a symbolic link
+    Loop(PathBuf),
+    /// A directory given without `-r`
+    IsDirectory(PathBuf),
+}
+
+impl fmt::Display for WalkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WalkError::Io { path, error } => write!(f, "{}: {}", path.display(), error),
+            WalkError::Loop(path) => write!(f, "{}: recursive directory loop", path.display()),
+            WalkError::IsDirectory(path) => write!(f, "{}: is a directory", path.display()),
+        }
+    }
+}
+
+impl Error for WalkError {}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WalkOptions<'g> {
+    /// Whether to search the files under directories rather than report them as errors
+    pub recursive: bool,
+    /// If not empty, only the files whose name matches one of these are searched
+    pub include: &'g [Glob],
+    /// The files and directories whose name matches one of these are skipped
+    pub exclude: &'g [Glob],
+    /// Whether to skip `.git` directories and what `.gitignore` files list, when recursing
+    pub gitignore: bool,
+}
+
+/// Calls `visit` with every file to search under `paths`, or with the error that prevented
+/// reaching it, in order
+///
+/// The entries of each directory are visited sorted by name. Symbolic links are followed,
+/// and a link to a directory that contains it is reported as a loop instead of being
+/// walked again.
+pub fn walk(
+    paths: &[PathBuf],
+    options: &WalkOptions,
+    visit: &mut dyn FnMut(Result<PathBuf, WalkError>),
+) {
+    let mut walker = Walker {
+        options,
+        visit,
+        ancestors: Vec::new(),
+        ignore_files: Vec::new(),
+    };
+
+    for path in paths {
+        walker.walk_root(path);
+    }
+}
+
+struct Walker<'w, 'g> {
+    options: &'w WalkOptions<'g>,
+    visit: &'w mut dyn FnMut(Result<PathBuf, WalkError>),
+    /// The canonical paths of the directories being walked, to detect loops
+    ancestors: Vec<PathBuf>,
+    /// The ignore files of the directories being walked
+    ignore_files: Vec<IgnoreFile>,
+}
+
+impl Walker<'_, '_> {
+    fn report_io(&mut self, path: &Path, error: io::Error) {
+        (self.visit)(Err(WalkError::Io {
+            path: path.to_path_buf(),
+            error,
+        }));
+    }
+
+    fn walk_root(&mut self, path: &Path) {
+        let metadata = match fs::metadata(path) {
+            Ok(metadata) => metadata,
+            Err(error) => return self.report_io(path, error),
+        };
+
+        if !metadata.is_dir() {
+            if self.is_selected(path, false) {
+                (self.visit)(Ok(path.to_path_buf()));
+            }
+        } else if self.options.recursive {
+            self.walk_dir(path);
+        } else {
+            (self.visit)(Err(WalkError::IsDirectory(path.to_path_buf())));
+        }
+    }
+
+    fn walk_dir(&mut self, dir: &Path) {
+        let canonical = match fs::canonicalize(dir) {
+            Ok(canonical) => canonical,
+            Err(error) => return self.report_io(dir, error),
+        };
+        if self.ancestors.contains(&canonical) {
+            return (self.visit)(Err(WalkError::Loop(dir.to_path_buf())));
+        }
+
+        let mut entries =
+            match fs::read_dir(dir).and_then(|entries| entries.collect::<Result<Vec<_>, _>>()) {
+                Ok(entries) => entries,
+                Err(error) => return self.report_io(dir, error),
+            };
+        entries.sort_by_key(|entry| entry.file_name());
+
+        let ignore_file = match self.options.gitignore {
+            true => IgnoreFile::read(dir).unwrap_or_else(|error| {
+                self.report_io(&dir.join(ignore::IGNORE_FILE_NAME), error);
+                None
+            }),
+            false => None,
+        };
+        let has_ignore_file = ignore_file.is_some();
+        self.ignore_files.extend(ignore_file);
+        self.ancestors.push(canonical);
+
+        for entry in entries {
+            let path = entry.path();
+            // follows symbolic links, unlike `entry.metadata()`
+            let metadata = match fs::metadata(&path) {
+                Ok(metadata) => metadata,
+                Err(error) => {
+                    self.report_io(&path, error);
+                    continue;
+                }
+            };
+
+            if metadata.is_dir() {
+                if self.is_selected(&path, true) {
+                    self.walk_dir(&path);
+                }
+            } else if metadata.is_file() && self.is_selected(&path, false) {
+                (self.visit)(Ok(path));
+            }
+        }
+
+        self.ancestors.pop();
+        if has_ignore_file {
+            self.ignore_files.pop();
+        }
+    }
+
+    /// Returns whether `path` passes the filters, which it must to be searched or walked
+    fn is_selected(&self, path: &Path, is_dir: bool) -> bool {
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy())
+            .unwrap_or_default();
+
+        if self.options.exclude.iter().any(|glob| glob.is_match(&name)) {
+            return false;
+        }
+        if !is_dir
+            && !self.options.include.is_empty()
+            && !self.options.include.iter().any(|glob| glob.is_match(&name))
+        {
+            return false;
+        }
+
+        !(self.options.gitignore && self.is_ignored(path, is_dir, &name))
+    }
+
+    fn is_ignored(&self, path: &Path, is_dir: bool, name: &str) -> bool {
+        if is_dir && name == ".git" {
+            return true;
+        }
+
+        // the deepest ignore file has the last word
+        self.ignore_files
+            .iter()
+            .rev()
+            .find_map(|ignore_file| ignore_file.matched(path, is_dir))
+            .unwrap_or(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A directory tree under the temporary directory, removed when dropped
+    struct TempTree(PathBuf);
+
+    impl TempTree {
+        fn new(name: &str, files: &[(&str, &str)]) -> TempTree {
+            let root =
+                std::env::temp_dir().join(format!("minigrep_{}_{}", name, std::process::id()));
+            let _ = fs::remove_dir_all(&root);
+            for (path, contents) in files {
+                let path = root.join(path);
+                fs::create_dir_all(path.parent().unwrap()).unwrap();
+                fs::write(path, contents).unwrap();
+            }
+            TempTree(root)
+        }
+    }
+
+    impl Drop for TempTree {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    /// Walks `paths` relative to `root`, returning the files relative to `root` and the
+    /// errors as strings
+    fn walk_relative(root: &Path, paths: &[&str], options: &WalkOptions) -> Vec<String> {
+        let paths: Vec<PathBuf> = paths.iter().map(|path| root.join(path)).collect();
+        let mut visited = Vec::new();
+        walk(&paths, options, &mut |file| {
+            visited.push(match file {
+                Ok(path) => path
+                    .strip_prefix(root)
+                    .unwrap()
+                    .to_string_lossy()
+                    .replace('\\', "/"),
+                Err(error) => {
+                    format!("error: {}", error).replace(&root.to_string_lossy().to_string(), "ROOT")
+                }
+            })
+        });
+        visited
+    }
+
+    fn sample_tree(name: &str) -> TempTree {
+        TempTree::new(
+            name,
+            &[
+                (".gitignore", "*.log\n/build/\n"),
+                ("b.txt", "b"),
+                ("a.rs", "a"),
+                ("debug.log", "log"),
+                ("build/out.txt", "out"),
+                ("src/.gitignore", "!keep.log\n"),
+                ("src/keep.log", "kept"),
+                ("src/main.rs", "main"),
+                ("src/build/gen.rs", "generated"),
+                (".git/HEAD", "ref"),
+            ],
+        )
+    }
+
+    #[test]
+    fn recursive_with_gitignore() {
+        let tree = sample_tree("walk_gitignore");
+        let options = WalkOptions {
+            recursive: true,
+            gitignore: true,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            walk_relative(&tree.0, &["."], &options),
+            [
+                ".gitignore",
+                "a.rs",
+                "b.txt",
+                "src/.gitignore",
+                "src/build/gen.rs",
+                "src/keep.log",
+                "src/main.rs"
+            ]
+        );
+
+        let options = WalkOptions {
+            gitignore: false,
+            ..options
+        };
+        assert_eq!(walk_relative(&tree.0, &["."], &options).len(), 10);
+    }
+
+    #[test]
+    fn include_and_exclude() {
+        let tree = sample_tree("walk_globs");
+        let include = [Glob::new("*.rs").unwrap()];
+        let exclude = [Glob::new("src").unwrap(), Glob::new("b*").unwrap()];
+        let options = WalkOptions {
+            recursive: true,
+            include: &include,
+            exclude: &exclude,
+            gitignore: false,
+        };
+
+        assert_eq!(walk_relative(&tree.0, &["."], &options), ["a.rs"]);
+        assert_eq!(
+            walk_relative(&tree.0, &["src/main.rs", "b.txt"], &options),
+            ["src/main.rs"]
+        );
+    }
+
+    #[test]
+    fn errors_do_not_stop_the_walk() {
+        let tree = sample_tree("walk_errors");
+        let options = WalkOptions::default();
+
+        let visited = walk_relative(&tree.0, &["missing.txt", "src", "a.rs"], &options);
+        let separator = std::path::MAIN_SEPARATOR;
+
+        assert_eq!(visited.len(), 3);
+        assert!(visited[0].starts_with(&format!("error: ROOT{}missing.txt: ", separator)));
+        assert_eq!(
+            visited[1],
+            format!("error: ROOT{}src: is a directory", separator)
+        );
+        assert_eq!(visited[2], "a.rs");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlink_loops() {
+        let tree = TempTree::new("walk_loops", &[("dir/file.txt", "x")]);
+        std::os::unix::fs::symlink(&tree.0, tree.0.join("dir/up")).unwrap();
+        std::os::unix::fs::symlink(tree.0.join("dir/file.txt"), tree.0.join("link.txt")).unwrap();
+        let options = WalkOptions {
+            recursive: true,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            walk_relative(&tree.0, &["."], &options),
+            [
+                "dir/file.txt",
+                "error: ROOT/./dir/up: recursive directory loop",
+                "link.txt"
+            ]
+        );
+    }
+}
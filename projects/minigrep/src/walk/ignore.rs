@@ -0,0 +1,133 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::glob::Glob;
+
+/// The name of the ignore files read in every directory
+pub const IGNORE_FILE_NAME: &str = ".gitignore";
+
+/// One line of an ignore file
+#[derive(Debug, Clone)]
+struct Rule {
+    glob: Glob,
+    /// Whether the rule starts with `!`, re-including what an earlier rule ignored
+    negated: bool,
+    /// Whether the rule ends with `/`, only matching directories
+    dir_only: bool,
+}
+
+#[derive(Debug, Clone)]
+/// The rules of a `.gitignore` file, which apply to the paths under its directory
+///
+/// Each line is a glob, a line starting with `#` is a comment and a trailing `/` restricts
+/// the rule to directories. A glob with a `/` at its start or middle is matched against
+/// the path relative to the directory, and any other glob against the name of each file
+/// at any depth. A leading `!` negates a rule, and the last matching rule wins.
+pub struct IgnoreFile {
+    dir: PathBuf,
+    rules: Vec<Rule>,
+}
+
+impl IgnoreFile {
+    /// Reads the ignore file of `dir`, if it has one
+    pub fn read(dir: &Path) -> io::Result<Option<IgnoreFile>> {
+        match fs::read_to_string(dir.join(IGNORE_FILE_NAME)) {
+            Ok(contents) => Ok(Some(IgnoreFile::parse(dir, &contents))),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Parses the rules of the ignore file of `dir`, skipping the invalid ones like git does
+    pub fn parse(dir: &Path, contents: &str) -> IgnoreFile {
+        let rules = contents.lines().filter_map(Rule::parse).collect();
+
+        IgnoreFile {
+            dir: dir.to_path_buf(),
+            rules,
+        }
+    }
+
+    /// Returns `Some(true)` if `path` is ignored, `Some(false)` if a negated rule includes it
+    /// again and `None` if no rule matches it
+    pub fn matched(&self, path: &Path, is_dir: bool) -> Option<bool> {
+        let relative = path.strip_prefix(&self.dir).ok()?;
+
+        self.rules
+            .iter()
+            .rev()
+            .find(|rule| (is_dir || !rule.dir_only) && rule.glob.is_match_path(relative))
+            .map(|rule| !rule.negated)
+    }
+}
+
+impl Rule {
+    fn parse(line: &str) -> Option<Rule> {
+        let line = line.trim_end();
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+
+        let (negated, line) = match line.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, line),
+        };
+        let (dir_only, line) = match line.strip_suffix('/') {
+            Some(rest) => (true, rest),
+            None => (false, line),
+        };
+
+        let glob = match line.strip_prefix('/') {
+            Some(anchored) => Glob::new(anchored),
+            None if line.contains('/') => Glob::new(line),
+            None => Glob::new(&format!("**/{}", line)),
+        };
+
+        Some(Rule {
+            glob: glob.ok()?,
+            negated,
+            dir_only,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rules() {
+        let root = Path::new("project");
+        let ignore = IgnoreFile::parse(
+            root,
+            "# build output\n\
+             /target\n\
+             *.log\n\
+             !keep.log\n\
+             cache/\n\
+             docs/*.html\n\
+             \\#notes\n\
+             \n\
+             [z-a]\n",
+        );
+        let matched = |path: &str, is_dir| ignore.matched(&root.join(path), is_dir);
+
+        assert_eq!(matched("target", true), Some(true));
+        assert_eq!(matched("src/target", true), None);
+        assert_eq!(matched("debug.log", false), Some(true));
+        assert_eq!(matched("src/debug.log", false), Some(true));
+        assert_eq!(matched("src/keep.log", false), Some(false));
+        assert_eq!(matched("src/cache", true), Some(true));
+        assert_eq!(matched("src/cache", false), None);
+        assert_eq!(matched("docs/index.html", false), Some(true));
+        assert_eq!(matched("docs/api/index.html", false), None);
+        assert_eq!(matched("#notes", false), Some(true));
+        assert_eq!(matched("main.rs", false), None);
+        assert_eq!(
+            ignore.matched(Path::new("elsewhere/debug.log"), false),
+            None
+        );
+        assert_eq!(ignore.rules.len(), 6);
+    }
+}